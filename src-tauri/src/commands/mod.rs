@@ -217,22 +217,36 @@ pub async fn save_config(
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
     config: AppConfig
 ) -> Result<(), String> {
+    // 校验局域网访问控制配置
+    crate::proxy::ip_filter::IpFilter::try_from_config(&config.proxy.ip_filter)?;
+    let bind_address = config.proxy.bind_address.trim();
+    if !bind_address.is_empty() && bind_address.parse::<std::net::IpAddr>().is_err() {
+        return Err(format!("无效的绑定地址: {}", bind_address));
+    }
+    // 校验命名客户端 Key
+    crate::proxy::client_keys::validate(&config.proxy.api_key, &config.proxy.client_keys)?;
+    config.proxy.key_limits.validate(&config.proxy.client_keys)?;
+    // 校验 Unix socket 监听配置
+    config.proxy.unix_socket.validate()?;
+    // 校验脱敏规则 (自定义正则)
+    modules::redaction::Redactor::new(&config.proxy.redaction)?;
+
     modules::save_app_config(&config)?;
     
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
-    // 热更新正在运行的服务
-    let instance_lock = proxy_state.instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        // 更新模型映射
-        instance.axum_server.update_mapping(&config.proxy).await;
-        // 更新上游代理
-        instance.axum_server.update_proxy(config.proxy.upstream_proxy.clone()).await;
-        // 更新安全策略 (auth)
-        instance.axum_server.update_security(&config.proxy).await;
-        // 更新 z.ai 配置
-        instance.axum_server.update_zai(&config.proxy).await;
+    // 更新脱敏规则与事件通知输出 (与反代服务是否运行无关)
+    modules::redaction::configure(&config.proxy.redaction)?;
+    crate::proxy::events::bus().configure(config.proxy.events.clone());
+
+    // 热更新正在运行的服务 (含端口 / 监听地址切换，无需重启)
+    let mut instance_lock = proxy_state.instance.write().await;
+    if let Some(instance) = instance_lock.as_mut() {
+        let result = instance.axum_server.apply_config(&config.proxy).await;
+        // 以实际生效的配置为准 (切换监听失败时保留原端口)
+        instance.config = instance.axum_server.runtime().load().config.clone();
+        result?;
         tracing::debug!("已同步热更新反代服务配置");
     }
     
@@ -462,6 +476,99 @@ pub async fn save_text_file(path: String, content: String) -> Result<(), String>
     std::fs::write(&path, content).map_err(|e| format!("写入文件失败: {}", e))
 }
 
+// --- 令牌加密 ---
+
+/// 获取令牌库状态 (是否启用加密 / 是否已解锁)
+#[tauri::command]
+pub async fn get_token_vault_status() -> Result<modules::token_vault::VaultStatus, String> {
+    Ok(modules::token_vault::status())
+}
+
+/// 启用令牌静态加密并迁移现有账号文件
+#[tauri::command]
+pub async fn enable_token_encryption(
+    source: modules::token_vault::KeySource,
+    passphrase: Option<String>,
+    key_file: Option<String>,
+) -> Result<modules::token_vault::VaultStatus, String> {
+    // PBKDF2 与批量改写文件较耗时，放到阻塞线程执行
+    tokio::task::spawn_blocking(move || modules::token_vault::enable(source, passphrase, key_file))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 关闭令牌加密 (解密全部账号文件)
+#[tauri::command]
+pub async fn disable_token_encryption() -> Result<modules::token_vault::VaultStatus, String> {
+    tokio::task::spawn_blocking(modules::token_vault::disable)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 解锁令牌库，解锁后迁移遗留的明文账号并重新加载反代账号
+#[tauri::command]
+pub async fn unlock_token_vault(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    passphrase: Option<String>,
+) -> Result<modules::token_vault::VaultStatus, String> {
+    let status = tokio::task::spawn_blocking(move || {
+        let status = modules::token_vault::unlock(passphrase.as_deref())?;
+        modules::token_vault::migrate_accounts()?;
+        Ok::<_, String>(status)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let instance_lock = proxy_state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        if let Err(e) = instance.token_manager.load_accounts().await {
+            modules::logger::log_warn(&format!("解锁后重新加载反代账号失败: {}", e));
+        }
+    }
+    Ok(status)
+}
+
+/// 使用导出口令加密导出全部账号的 refresh_token，返回导出数量
+#[tauri::command]
+pub async fn export_accounts_bundle(path: String, passphrase: String) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        let accounts: Vec<_> = modules::account::export_accounts()?
+            .into_iter()
+            .map(|(email, refresh_token)| modules::token_vault::BundleAccount { email, refresh_token })
+            .collect();
+        let bundle = modules::token_vault::seal_bundle(&accounts, &passphrase)?;
+        let content = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+        std::fs::write(&path, content).map_err(|e| format!("写入文件失败: {}", e))?;
+        Ok(accounts.len())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// 导入加密导出包中的账号 (逐个通过 refresh_token 重新获取账号信息)
+#[tauri::command]
+pub async fn import_accounts_bundle(
+    app: tauri::AppHandle,
+    path: String,
+    passphrase: String,
+) -> Result<Vec<Account>, String> {
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let bundle: modules::token_vault::AccountBundle =
+        serde_json::from_str(&content).map_err(|_| "不是有效的账号导出包".to_string())?;
+    let entries = tokio::task::spawn_blocking(move || modules::token_vault::open_bundle(&bundle, &passphrase))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut imported = Vec::new();
+    for entry in entries {
+        match add_account(app.clone(), entry.email.clone(), entry.refresh_token).await {
+            Ok(account) => imported.push(account),
+            Err(e) => modules::logger::log_warn(&format!("导入账号 {} 失败: {}", entry.email, e)),
+        }
+    }
+    Ok(imported)
+}
+
 /// 清理日志缓存
 #[tauri::command]
 pub async fn clear_log_cache() -> Result<(), String> {
@@ -618,6 +725,47 @@ fn compare_versions(latest: &str, current: &str) -> bool {
     false
 }
 
+/// 更新账号分组标签 (用于反代分组路由)
+#[tauri::command]
+pub async fn update_account_groups(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    groups: Vec<String>,
+) -> Result<Account, String> {
+    let account = modules::account::update_account_groups(&account_id, groups)?;
+    modules::logger::log_info(&format!(
+        "账号分组已更新: {} -> {:?}",
+        account.email, account.groups
+    ));
+
+    // 如果反代服务正在运行,重新加载账号池
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(account)
+}
+
+/// 更新账号调度参数 (加权轮询权重、优先级、排空标记)
+#[tauri::command]
+pub async fn update_account_scheduling(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    weight: Option<u32>,
+    priority: Option<i32>,
+    drain: Option<bool>,
+) -> Result<Account, String> {
+    let account =
+        modules::account::update_account_scheduling(&account_id, weight, priority, drain)?;
+    modules::logger::log_info(&format!(
+        "账号调度参数已更新: {} -> weight={}, priority={}, drain={}",
+        account.email, account.weight, account.priority, account.drain
+    ));
+
+    // 如果反代服务正在运行,重新加载账号池 (会话绑定保留，排空账号仅停止接受新会话)
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(account)
+}
+
 /// 切换账号的反代禁用状态
 #[tauri::command]
 pub async fn toggle_proxy_status(
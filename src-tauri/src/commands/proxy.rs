@@ -14,12 +14,23 @@ pub struct ProxyStatus {
     pub port: u16,
     pub base_url: String,
     pub active_accounts: usize,
+    /// 当前连接数 (停机排空期间为尚未结束的连接数)
+    #[serde(default)]
+    pub active_connections: usize,
+    /// 是否正在停机排空
+    #[serde(default)]
+    pub draining: bool,
+    /// 正在监听的 Unix socket 路径
+    #[serde(default)]
+    pub unix_socket_path: Option<String>,
 }
 
 /// 反代服务全局状态
 pub struct ProxyServiceState {
     pub instance: Arc<RwLock<Option<ProxyServiceInstance>>>,
     pub monitor: Arc<RwLock<Option<Arc<ProxyMonitor>>>>,
+    /// 已停止但仍在排空连接的服务器
+    pub draining: Arc<std::sync::Mutex<Option<Arc<crate::proxy::server::ConnectionTracker>>>>,
 }
 
 /// 反代服务实例
@@ -28,6 +39,10 @@ pub struct ProxyServiceInstance {
     pub token_manager: Arc<TokenManager>,
     pub axum_server: crate::proxy::AxumServer,
     pub server_handle: tokio::task::JoinHandle<()>,
+    /// 账号目录监听 (随实例 drop 自动停止)
+    pub account_watcher: Option<crate::proxy::account_watcher::AccountWatcher>,
+    /// 请求日志定期清理 (随实例 drop 自动停止)
+    pub log_maintenance: crate::proxy::monitor::LogMaintenance,
 }
 
 impl ProxyServiceState {
@@ -35,6 +50,7 @@ impl ProxyServiceState {
         Self {
             instance: Arc::new(RwLock::new(None)),
             monitor: Arc::new(RwLock::new(None)),
+            draining: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 }
@@ -62,6 +78,7 @@ pub async fn start_proxy_service(
         // Sync enabled state from config
         if let Some(monitor) = monitor_lock.as_ref() {
             monitor.set_enabled(config.enable_logging);
+            monitor.set_storage_config(config.log_storage.clone());
         }
     }
     
@@ -76,6 +93,8 @@ pub async fn start_proxy_service(
     let token_manager = Arc::new(TokenManager::new(accounts_dir));
     // 同步 UI 传递的调度配置
     token_manager.update_sticky_config(config.scheduling.clone()).await;
+    // 同步账号分组路由配置
+    token_manager.update_group_config(config.account_groups.clone()).await;
     
     // 3. 加载账号
     let active_accounts = token_manager.load_accounts().await
@@ -92,29 +111,33 @@ pub async fn start_proxy_service(
     // 启动 Axum 服务器
     let (axum_server, server_handle) =
         match crate::proxy::AxumServer::start(
-            config.get_bind_address().to_string(),
-            config.port,
+            config.clone(),
             token_manager.clone(),
-            config.anthropic_mapping.clone(),
-            config.openai_mapping.clone(),
-            config.custom_mapping.clone(),
-            config.request_timeout,
-            config.upstream_proxy.clone(),
-            crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
-            config.zai.clone(),
             monitor.clone(),
-
         ).await {
             Ok((server, handle)) => (server, handle),
             Err(e) => return Err(format!("启动 Axum 服务器失败: {}", e)),
         };
     
+    // 监听账号目录，增量热加载账号变化
+    let account_watcher = match crate::proxy::account_watcher::AccountWatcher::start(token_manager.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            tracing::warn!("账号热加载不可用: {}", e);
+            None
+        }
+    };
+    
+    let unix_socket_path = axum_server.unix_socket_path().map(|p| p.display().to_string());
+
     // 创建服务实例
     let instance = ProxyServiceInstance {
         config: config.clone(),
         token_manager: token_manager.clone(), // Clone for ProxyServiceInstance
         axum_server,
         server_handle,
+        account_watcher,
+        log_maintenance: crate::proxy::monitor::LogMaintenance::start(monitor.clone()),
     };
     
     *instance_lock = Some(instance);
@@ -128,8 +151,11 @@ pub async fn start_proxy_service(
     Ok(ProxyStatus {
         running: true,
         port: config.port,
-        base_url: format!("http://127.0.0.1:{}", config.port),
+        base_url: config.local_base_url(),
         active_accounts,
+        active_connections: 0,
+        draining: false,
+        unix_socket_path,
     })
 }
 
@@ -138,17 +164,21 @@ pub async fn start_proxy_service(
 pub async fn stop_proxy_service(
     state: State<'_, ProxyServiceState>,
 ) -> Result<(), String> {
-    let mut instance_lock = state.instance.write().await;
-    
-    if instance_lock.is_none() {
-        return Err("服务未运行".to_string());
-    }
-    
-    // 停止 Axum 服务器
-    if let Some(instance) = instance_lock.take() {
-        instance.axum_server.stop();
-        // 等待服务器任务完成
-        instance.server_handle.await.ok();
+    // 先移出实例并释放锁，排空期间状态查询与重新启动不被阻塞
+    let instance = state.instance.write().await.take().ok_or("服务未运行")?;
+
+    // 停止 Axum 服务器: 停止接受连接，等待进行中的请求完成
+    let connections = instance.axum_server.connections();
+    *state.draining.lock().unwrap() = Some(connections.clone());
+    instance
+        .axum_server
+        .stop(Duration::from_secs(instance.config.drain_timeout_secs));
+    // 等待服务器任务完成
+    instance.server_handle.await.ok();
+
+    let mut draining = state.draining.lock().unwrap();
+    if draining.as_ref().is_some_and(|d| Arc::ptr_eq(d, &connections)) {
+        *draining = None;
     }
     
     Ok(())
@@ -165,15 +195,27 @@ pub async fn get_proxy_status(
         Some(instance) => Ok(ProxyStatus {
             running: true,
             port: instance.config.port,
-            base_url: format!("http://127.0.0.1:{}", instance.config.port),
+            base_url: instance.config.local_base_url(),
             active_accounts: instance.token_manager.len(),
+            active_connections: instance.axum_server.connections().active(),
+            draining: false,
+            unix_socket_path: instance
+                .axum_server
+                .unix_socket_path()
+                .map(|p| p.display().to_string()),
         }),
-        None => Ok(ProxyStatus {
-            running: false,
-            port: 0,
-            base_url: String::new(),
-            active_accounts: 0,
-        }),
+        None => {
+            let draining = state.draining.lock().unwrap().clone();
+            Ok(ProxyStatus {
+                running: false,
+                port: 0,
+                base_url: String::new(),
+                active_accounts: 0,
+                active_connections: draining.as_ref().map_or(0, |d| d.active()),
+                draining: draining.is_some_and(|d| d.is_draining()),
+                unix_socket_path: None,
+            })
+        }
     }
 }
 
@@ -195,15 +237,180 @@ pub async fn get_proxy_stats(
 pub async fn get_proxy_logs(
     state: State<'_, ProxyServiceState>,
     limit: Option<usize>,
+    filter: Option<crate::proxy::monitor::ProxyLogFilter>,
 ) -> Result<Vec<ProxyRequestLog>, String> {
     let monitor_lock = state.monitor.read().await;
     if let Some(monitor) = monitor_lock.as_ref() {
-        Ok(monitor.get_logs(limit.unwrap_or(100)).await)
+        Ok(monitor.get_logs(limit.unwrap_or(100), &filter.unwrap_or_default()).await)
     } else {
         Ok(Vec::new())
     }
 }
 
+/// 按条件检索请求日志 (时间范围 / 状态 / 模型 / URL / 账号 / 错误 / 耗时 / 全文)，游标分页
+#[tauri::command]
+pub async fn search_proxy_logs(
+    query: crate::proxy::monitor::LogSearchQuery,
+) -> Result<crate::proxy::monitor::LogSearchPage, String> {
+    tokio::task::spawn_blocking(move || crate::modules::proxy_db::search_logs(&query))
+        .await
+        .map_err(|e| format!("检索日志失败: {}", e))?
+}
+
+/// 重放一条已记录的请求 (可强制指定账号 / 映射模型 / 提供方)，
+/// 结果作为新日志写入 (replay_of 指向原始日志)，并返回两次请求的对比
+#[tauri::command]
+pub async fn replay_proxy_request(
+    state: State<'_, ProxyServiceState>,
+    id: String,
+    overrides: Option<crate::proxy::replay::ReplayOverrides>,
+) -> Result<crate::proxy::replay::ReplayDiff, String> {
+    let (router, api_key) = {
+        let instance_lock = state.instance.read().await;
+        let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+        (instance.axum_server.router(), instance.config.api_key.clone())
+    };
+    let monitor_enabled = state.monitor.read().await.as_ref().is_some_and(|m| m.is_enabled());
+    if !monitor_enabled {
+        return Err("请先开启请求监控，重放结果需要写入日志".to_string());
+    }
+
+    let original = load_proxy_log(id.clone()).await?;
+    let replay_id = crate::proxy::replay::replay(router, Some(&api_key), &original, overrides.unwrap_or_default()).await?;
+    let replayed = load_proxy_log(replay_id).await?;
+    Ok(crate::proxy::replay::diff_logs(&original, &replayed))
+}
+
+/// 对比原始请求与其重放结果
+#[tauri::command]
+pub async fn diff_proxy_logs(
+    original_id: String,
+    replay_id: String,
+) -> Result<crate::proxy::replay::ReplayDiff, String> {
+    let original = load_proxy_log(original_id).await?;
+    let replayed = load_proxy_log(replay_id).await?;
+    Ok(crate::proxy::replay::diff_logs(&original, &replayed))
+}
+
+/// 等待写线程落盘后按 ID 读取日志
+async fn load_proxy_log(id: String) -> Result<ProxyRequestLog, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::flush_logs()?;
+        crate::modules::proxy_db::get_log(&id)?.ok_or_else(|| format!("日志不存在: {}", id))
+    })
+    .await
+    .map_err(|e| format!("读取日志失败: {}", e))?
+}
+
+/// 按检索条件将请求日志导出到文件 (JSONL / HAR 1.2 / OTLP JSON)
+#[tauri::command]
+pub async fn export_proxy_logs(
+    request: crate::modules::proxy_export::LogExportRequest,
+    path: String,
+) -> Result<crate::modules::proxy_export::ExportReport, String> {
+    let base_url = crate::modules::config::load_app_config()?.proxy.local_base_url();
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::create(&path).map_err(|e| format!("创建导出文件失败: {}", e))?;
+        let mut writer = std::io::BufWriter::new(file);
+        let count = crate::modules::proxy_db::export_logs(&request, &base_url, &mut writer)?;
+        let bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        Ok(crate::modules::proxy_export::ExportReport { path, format: request.format, count, bytes })
+    })
+    .await
+    .map_err(|e| format!("导出日志失败: {}", e))?
+}
+
+/// 生成局域网使用的自签名证书，返回 (证书路径, 私钥路径)
+/// `hosts` 为额外写入证书的主机名 / IP (如本机局域网地址)
+#[tauri::command]
+pub async fn generate_proxy_tls_cert(hosts: Option<Vec<String>>) -> Result<(String, String), String> {
+    let dir = crate::modules::account::get_data_dir()?.join(crate::proxy::tls::TLS_DIR);
+    let (cert, key) = crate::proxy::tls::generate_self_signed(&dir, &hosts.unwrap_or_default())?;
+    Ok((cert.to_string_lossy().to_string(), key.to_string_lossy().to_string()))
+}
+
+/// 立即重新加载 TLS 证书 (证书文件变化时也会自动加载)
+#[tauri::command]
+pub async fn reload_proxy_tls(state: State<'_, ProxyServiceState>) -> Result<(), String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+    instance.axum_server.reload_tls()
+}
+
+/// 获取各 API Key (及 IP) 当前的 token 预算用量
+#[tauri::command]
+pub async fn get_proxy_key_budgets(
+    state: State<'_, ProxyServiceState>,
+) -> Result<std::collections::HashMap<String, crate::proxy::key_limits::BudgetUsage>, String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+    Ok(instance.axum_server.key_limiter().usage())
+}
+
+/// 清零指定计量主体 (为空时清零全部) 的预算用量
+#[tauri::command]
+pub async fn reset_proxy_key_budget(
+    state: State<'_, ProxyServiceState>,
+    subject: Option<String>,
+) -> Result<(), String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("服务未运行")?;
+    instance.axum_server.key_limiter().reset(subject.as_deref())
+}
+
+/// 向所有已配置的事件输出发送一条测试事件，返回每个输出的投递结果
+#[tauri::command]
+pub async fn test_proxy_events() -> Result<Vec<crate::proxy::events::SinkDelivery>, String> {
+    Ok(crate::proxy::events::bus().test_fire().await)
+}
+
+/// 获取日志写入队列指标 (排队 / 丢弃 / 批次)，用于判断日志写入是否跟不上请求
+#[tauri::command]
+pub async fn get_proxy_log_writer_stats() -> Result<crate::modules::proxy_log_store::LogWriterStats, String> {
+    crate::modules::proxy_db::writer_stats()
+}
+
+/// 立即按保留策略清理请求日志 (full_vacuum 为 true 时执行完整 VACUUM)
+#[tauri::command]
+pub async fn run_proxy_log_maintenance(
+    full_vacuum: Option<bool>,
+) -> Result<crate::modules::proxy_log_storage::MaintenanceReport, String> {
+    let config = crate::modules::config::load_app_config()?.proxy.log_storage;
+    tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::run_maintenance(&config, full_vacuum.unwrap_or(false))
+    })
+    .await
+    .map_err(|e| format!("日志维护失败: {}", e))?
+}
+
+/// 获取按时间桶聚合的用量时间序列 (小时/天 × 模型 × 账号 × API Key)
+#[tauri::command]
+pub async fn get_proxy_usage_timeseries(
+    query: crate::modules::proxy_usage::UsageQuery,
+) -> Result<Vec<crate::modules::proxy_usage::UsageRow>, String> {
+    tokio::task::spawn_blocking(move || crate::modules::proxy_db::get_usage(&query, true))
+        .await
+        .map_err(|e| format!("查询用量失败: {}", e))?
+}
+
+/// 获取时间范围内按维度汇总的用量 (不分时间桶)
+#[tauri::command]
+pub async fn get_proxy_usage_breakdown(
+    query: crate::modules::proxy_usage::UsageQuery,
+) -> Result<Vec<crate::modules::proxy_usage::UsageRow>, String> {
+    tokio::task::spawn_blocking(move || crate::modules::proxy_db::get_usage(&query, false))
+        .await
+        .map_err(|e| format!("查询用量失败: {}", e))?
+}
+
+/// 根据已有请求日志重建用量汇总表
+#[tauri::command]
+pub async fn rebuild_proxy_usage() -> Result<u64, String> {
+    tokio::task::spawn_blocking(crate::modules::proxy_db::rebuild_usage)
+        .await
+        .map_err(|e| format!("重建用量统计失败: {}", e))?
+}
+
 /// 设置监控开启状态
 #[tauri::command]
 pub async fn set_proxy_monitor_enabled(
@@ -260,10 +467,9 @@ pub async fn update_model_mapping(
 ) -> Result<(), String> {
     let instance_lock = state.instance.read().await;
     
-    // 1. 如果服务正在运行，立即更新内存中的映射 (这里目前只更新了 anthropic_mapping 的 RwLock, 
-    // 后续可以根据需要让 resolve_model_route 直接读取全量 config)
+    // 1. 如果服务正在运行，立即替换运行时快照中的模型映射
     if let Some(instance) = instance_lock.as_ref() {
-        instance.axum_server.update_mapping(&config).await;
+        instance.axum_server.update_mapping(&config);
         tracing::debug!("后端服务已接收全量模型映射配置");
     }
     
@@ -409,6 +615,7 @@ pub async fn update_proxy_scheduling_config(
 ) -> Result<(), String> {
     let instance_lock = state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
+        instance.axum_server.runtime().update(|c| c.scheduling = config.clone());
         instance.token_manager.update_sticky_config(config).await;
         Ok(())
     } else {
@@ -416,6 +623,72 @@ pub async fn update_proxy_scheduling_config(
     }
 }
 
+/// 使用已记录的请求日志离线模拟调度策略 (不影响线上流量)
+#[tauri::command]
+pub async fn simulate_scheduling_policy(
+    config: crate::proxy::scheduling::simulator::SimulationConfig,
+    limit: Option<usize>,
+) -> Result<crate::proxy::scheduling::simulator::SimulationReport, String> {
+    use crate::proxy::scheduling::simulator;
+
+    tokio::task::spawn_blocking(move || {
+        let logs = crate::modules::proxy_db::get_logs(limit.unwrap_or(5000))?;
+        let accounts = crate::modules::account::list_accounts()?;
+        let tokens = simulator::tokens_from_accounts(&accounts);
+        if tokens.is_empty() {
+            return Err("没有可用于模拟的账号".to_string());
+        }
+        Ok(simulator::simulate(&logs, tokens, &config))
+    })
+    .await
+    .map_err(|e| format!("模拟任务失败: {}", e))?
+}
+
+/// 获取当前账号分组路由配置
+#[tauri::command]
+pub async fn get_proxy_account_group_config(
+    state: State<'_, ProxyServiceState>,
+) -> Result<crate::proxy::account_groups::AccountGroupConfig, String> {
+    let instance_lock = state.instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        Ok(instance.token_manager.get_group_config().await)
+    } else {
+        let app_config = crate::modules::config::load_app_config()?;
+        Ok(app_config.proxy.account_groups)
+    }
+}
+
+/// 更新账号分组路由配置 (热更新并持久化)
+#[tauri::command]
+pub async fn update_proxy_account_group_config(
+    state: State<'_, ProxyServiceState>,
+    config: crate::proxy::account_groups::AccountGroupConfig,
+) -> Result<(), String> {
+    {
+        let instance_lock = state.instance.read().await;
+        if let Some(instance) = instance_lock.as_ref() {
+            instance.axum_server.runtime().update(|c| c.account_groups = config.clone());
+            instance.token_manager.update_group_config(config.clone()).await;
+        }
+    }
+
+    let mut app_config = crate::modules::config::load_app_config()?;
+    app_config.proxy.account_groups = config;
+    crate::modules::config::save_app_config(&app_config)
+}
+
+/// 获取当前活跃的会话粘性绑定 (会话 -> 账号、绑定时长、命中次数)
+#[tauri::command]
+pub async fn get_proxy_session_bindings(
+    state: State<'_, ProxyServiceState>,
+) -> Result<Vec<crate::proxy::scheduling::SessionBindingInfo>, String> {
+    let instance_lock = state.instance.read().await;
+    match instance_lock.as_ref() {
+        Some(instance) => Ok(instance.token_manager.session_bindings()),
+        None => Ok(Vec::new()),
+    }
+}
+
 /// 清除所有会话粘性绑定
 #[tauri::command]
 pub async fn clear_proxy_session_bindings(
@@ -42,6 +42,22 @@ pub fn run() {
             info!("Setup starting...");
             modules::tray::create_tray(app.handle())?;
             info!("Tray created");
+
+            // 令牌库: 密钥文件模式或设置了口令环境变量时自动解锁
+            // 未能解锁时由前端启动后查询状态并弹出解锁提示
+            let vault = modules::token_vault::try_auto_unlock();
+            if vault.enabled && !vault.unlocked {
+                info!("Token vault is locked, waiting for unlock from UI");
+            }
+
+            // 事件总线: 桌面通知需要 AppHandle
+            proxy::events::bus().set_app_handle(app.handle().clone());
+            if let Ok(config) = modules::config::load_app_config() {
+                if let Err(e) = modules::redaction::configure(&config.proxy.redaction) {
+                    tracing::warn!("脱敏规则无效，使用默认规则: {}", e);
+                }
+                proxy::events::bus().configure(config.proxy.events);
+            }
             
             // 自动启动反代服务
             let handle = app.handle().clone();
@@ -102,6 +118,12 @@ pub fn run() {
             commands::import_custom_db,
             commands::sync_account_from_db,
             commands::save_text_file,
+            commands::get_token_vault_status,
+            commands::enable_token_encryption,
+            commands::disable_token_encryption,
+            commands::unlock_token_vault,
+            commands::export_accounts_bundle,
+            commands::import_accounts_bundle,
             commands::clear_log_cache,
             commands::open_data_folder,
             commands::get_data_dir_path,
@@ -109,11 +131,27 @@ pub fn run() {
             commands::get_antigravity_path,
             commands::check_for_updates,
             commands::toggle_proxy_status,
+            commands::update_account_groups,
+            commands::update_account_scheduling,
             // 反代服务命令
             commands::proxy::start_proxy_service,
             commands::proxy::stop_proxy_service,
             commands::proxy::get_proxy_status,
             commands::proxy::get_proxy_stats,
+            commands::proxy::search_proxy_logs,
+            commands::proxy::replay_proxy_request,
+            commands::proxy::diff_proxy_logs,
+            commands::proxy::export_proxy_logs,
+            commands::proxy::test_proxy_events,
+            commands::proxy::get_proxy_key_budgets,
+            commands::proxy::generate_proxy_tls_cert,
+            commands::proxy::reload_proxy_tls,
+            commands::proxy::reset_proxy_key_budget,
+            commands::proxy::run_proxy_log_maintenance,
+            commands::proxy::get_proxy_log_writer_stats,
+            commands::proxy::get_proxy_usage_timeseries,
+            commands::proxy::get_proxy_usage_breakdown,
+            commands::proxy::rebuild_proxy_usage,
             commands::proxy::get_proxy_logs,
             commands::proxy::set_proxy_monitor_enabled,
             commands::proxy::clear_proxy_logs,
@@ -123,7 +161,11 @@ pub fn run() {
             commands::proxy::fetch_zai_models,
             commands::proxy::get_proxy_scheduling_config,
             commands::proxy::update_proxy_scheduling_config,
+            commands::proxy::get_proxy_session_bindings,
             commands::proxy::clear_proxy_session_bindings,
+            commands::proxy::simulate_scheduling_policy,
+            commands::proxy::get_proxy_account_group_config,
+            commands::proxy::update_proxy_account_group_config,
             // Autostart 命令
             commands::autostart::toggle_auto_launch,
             commands::autostart::is_auto_launch_enabled,
@@ -27,10 +27,26 @@ pub struct Account {
     /// Unix timestamp when the proxy was disabled.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub proxy_disabled_at: Option<i64>,
+    /// Account group tags used by proxy pool routing (e.g. "team-a", "image-gen").
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Relative share of traffic under weighted scheduling (higher = more requests).
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Scheduling priority; lower values are preferred, higher ones are only used as overflow.
+    #[serde(default)]
+    pub priority: i32,
+    /// Draining accounts accept no new session bindings; existing sticky sessions keep using them.
+    #[serde(default)]
+    pub drain: bool,
     pub created_at: i64,
     pub last_used: i64,
 }
 
+fn default_weight() -> u32 {
+    1
+}
+
 impl Account {
     pub fn new(id: String, email: String, token: TokenData) -> Self {
         let now = chrono::Utc::now().timestamp();
@@ -46,6 +62,10 @@ impl Account {
             proxy_disabled: false,
             proxy_disabled_reason: None,
             proxy_disabled_at: None,
+            groups: Vec::new(),
+            weight: default_weight(),
+            priority: 0,
+            drain: false,
             created_at: now,
             last_used: now,
         }
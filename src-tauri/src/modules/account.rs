@@ -95,7 +95,10 @@ pub fn load_account(account_id: &str) -> Result<Account, String> {
     let content = fs::read_to_string(&account_path)
         .map_err(|e| format!("读取账号数据失败: {}", e))?;
     
-    serde_json::from_str(&content)
+    let mut value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("解析账号数据失败: {}", e))?;
+    modules::token_vault::decrypt_account(&mut value)?;
+    serde_json::from_value(value)
         .map_err(|e| format!("解析账号数据失败: {}", e))
 }
 
@@ -104,7 +107,10 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let accounts_dir = get_accounts_dir()?;
     let account_path = accounts_dir.join(format!("{}.json", account.id));
     
-    let content = serde_json::to_string_pretty(account)
+    let mut value = serde_json::to_value(account)
+        .map_err(|e| format!("序列化账号数据失败: {}", e))?;
+    modules::token_vault::encrypt_account(&mut value)?;
+    let content = serde_json::to_string_pretty(&value)
         .map_err(|e| format!("序列化账号数据失败: {}", e))?;
     
     fs::write(&account_path, content)
@@ -418,12 +424,44 @@ pub fn set_current_account_id(account_id: &str) -> Result<(), String> {
 /// 更新账号配额
 pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), String> {
     let mut account = load_account(account_id)?;
+    crate::proxy::events::check_quota(&account.email, &quota);
     account.update_quota(quota);
     save_account(&account)
 }
 
+/// 更新账号分组标签
+pub fn update_account_groups(account_id: &str, groups: Vec<String>) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    account.groups = crate::proxy::account_groups::normalize_groups(groups);
+    save_account(&account)?;
+    Ok(account)
+}
+
+/// 更新账号调度参数 (权重 / 优先级 / 排空)，未提供的字段保持不变
+pub fn update_account_scheduling(
+    account_id: &str,
+    weight: Option<u32>,
+    priority: Option<i32>,
+    drain: Option<bool>,
+) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    if let Some(weight) = weight {
+        if weight == 0 {
+            return Err("权重必须大于 0".to_string());
+        }
+        account.weight = weight;
+    }
+    if let Some(priority) = priority {
+        account.priority = priority;
+    }
+    if let Some(drain) = drain {
+        account.drain = drain;
+    }
+    save_account(&account)?;
+    Ok(account)
+}
+
 /// 导出所有账号的 refresh_token
-#[allow(dead_code)]
 pub fn export_accounts() -> Result<Vec<(String, String)>, String> {
     let accounts = list_accounts()?;
     let mut exports = Vec::new();
@@ -530,6 +568,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                             account.disabled_at = Some(chrono::Utc::now().timestamp());
                             account.disabled_reason = Some(format!("invalid_grant: {}", e));
                             let _ = save_account(account);
+                            crate::proxy::events::account_disabled(&account.email, &format!("invalid_grant: {}", e));
                         }
                         return Err(AppError::OAuth(e));
                     }
@@ -3,6 +3,7 @@ use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, Env
 use std::fs;
 use std::path::PathBuf;
 use crate::modules::account::get_data_dir;
+use crate::modules::redaction::RedactingMakeWriter;
 
 // 自定义本地时区时间格式化器
 struct LocalTimer;
@@ -43,16 +44,17 @@ pub fn init_logger() {
     let file_appender = tracing_appender::rolling::daily(log_dir, "app.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     
-    // 2. 终端输出层（使用本地时区）
+    // 2. 终端输出层（使用本地时区，输出前脱敏）
     let console_layer = fmt::Layer::new()
+        .with_writer(RedactingMakeWriter::new(std::io::stdout))
         .with_target(false)
         .with_thread_ids(false)
         .with_level(true)
         .with_timer(LocalTimer);
         
-    // 3. 文件输出层 (关闭 ANSI 格式化，使用本地时区)
+    // 3. 文件输出层 (关闭 ANSI 格式化，使用本地时区，输出前脱敏)
     let file_layer = fmt::Layer::new()
-        .with_writer(non_blocking)
+        .with_writer(RedactingMakeWriter::new(non_blocking))
         .with_ansi(false)
         .with_target(true)
         .with_level(true)
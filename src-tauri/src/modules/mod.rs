@@ -10,6 +10,12 @@ pub mod migration;
 pub mod tray;
 pub mod i18n;
 pub mod proxy_db;
+pub mod proxy_usage;
+pub mod proxy_log_storage;
+pub mod proxy_log_store;
+pub mod proxy_export;
+pub mod redaction;
+pub mod token_vault;
 
 use crate::models;
 
@@ -1,16 +1,40 @@
-use rusqlite::{params, Connection};
+use once_cell::sync::OnceCell;
+use rusqlite::{functions::FunctionFlags, params, Connection, OptionalExtension};
+use crate::modules::proxy_log_store::{LogStore, LogWriterStats};
+use crate::modules::proxy_export::{self, LogExportRequest};
+use crate::modules::proxy_log_storage::{self, MaintenanceReport};
+use crate::proxy::config::{LogCompression, LogStorageConfig};
 use std::path::PathBuf;
-use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::monitor::{LogSearchPage, LogSearchQuery, ProxyLogFilter, ProxyRequestLog, StatusClass};
+
+/// 查询日志时使用的列 (顺序与 row_to_log 对应)
+const LOG_COLUMNS: &str = "id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens,
+                account_email, mapped_model, provider, attempts, rotations, api_key, ttft_ms, stream_duration_ms, replay_of";
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
 
 pub fn get_proxy_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::modules::account::get_data_dir()?;
     Ok(data_dir.join("proxy_logs.db"))
 }
 
+static STORE: OnceCell<LogStore> = OnceCell::new();
+
+/// 全局日志库 (首次使用时打开数据库并启动写线程)
+fn store() -> Result<&'static LogStore, String> {
+    STORE.get_or_try_init(|| LogStore::open(&get_proxy_db_path()?, init_schema))
+}
+
 pub fn init_db() -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    
+    store().map(|_| ())
+}
+
+pub(crate) fn init_schema(conn: &Connection) -> Result<(), String> {
+    if let Err(e) = proxy_log_storage::ensure_incremental_vacuum(conn) {
+        tracing::warn!("Failed to enable incremental vacuum for proxy DB: {}", e);
+    }
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
             id TEXT PRIMARY KEY,
@@ -30,22 +54,172 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN response_body TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN input_tokens INTEGER", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN output_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN provider TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN attempts INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN rotations INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN api_key TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN ttft_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN stream_duration_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN replay_of TEXT", []);
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
         [],
     ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_account_email ON request_logs (account_email)",
+        [],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_replay_of ON request_logs (replay_of)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    if let Err(e) = init_fts(conn) {
+        // FTS5 不可用时全文检索退化为逐行解压后的子串匹配
+        tracing::warn!("Failed to initialize log full-text index: {}", e);
+    }
+    crate::modules::proxy_usage::init_tables(conn)?;
 
     Ok(())
 }
 
-pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+/// 请求/响应报文的 FTS5 全文索引
+/// 报文可能以压缩形式存储，因此使用无内容表 (contentless)，由 save_log 写入明文索引
+fn init_fts(conn: &Connection) -> Result<(), String> {
+    let definition: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'request_logs_fts'",
+            [],
+            |row| row.get(0),
+        )
+        .ok();
+    if definition.as_deref().is_some_and(|sql| sql.contains("content='request_logs'")) {
+        // 旧版外部内容索引无法处理压缩报文，重建
+        conn.execute_batch(
+            "DROP TRIGGER IF EXISTS request_logs_fts_ai;
+             DROP TRIGGER IF EXISTS request_logs_fts_au;
+             DROP TRIGGER IF EXISTS request_logs_fts_ad;
+             DROP TABLE request_logs_fts;",
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let existed = fts_available(conn);
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS request_logs_fts USING fts5(
+            request_body, response_body, content='', contentless_delete=1
+        );
+        CREATE TRIGGER IF NOT EXISTS request_logs_fts_ad AFTER DELETE ON request_logs BEGIN
+            DELETE FROM request_logs_fts WHERE rowid = old.rowid;
+        END;",
+    )
+    .map_err(|e| e.to_string())?;
+
+    if !existed {
+        // 首次创建索引时为已有日志建立索引
+        let mut stmt = conn
+            .prepare("SELECT rowid, request_body, response_body FROM request_logs")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    proxy_log_storage::decode_body(row.get_ref(1)?),
+                    proxy_log_storage::decode_body(row.get_ref(2)?),
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let (rowid, request_body, response_body) = row.map_err(|e| e.to_string())?;
+            index_bodies(conn, rowid, request_body.as_deref(), response_body.as_deref())?;
+        }
+    }
+    Ok(())
+}
 
+fn index_bodies(
+    conn: &Connection,
+    rowid: i64,
+    request_body: Option<&str>,
+    response_body: Option<&str>,
+) -> Result<(), String> {
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        "INSERT INTO request_logs_fts (rowid, request_body, response_body) VALUES (?1, ?2, ?3)",
+        params![rowid, request_body, response_body],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 注册 SQL 函数 decode_body(col)，返回报文明文 (压缩存储时先解压)
+fn register_decode_body(conn: &Connection) -> Result<(), String> {
+    conn.create_scalar_function(
+        "decode_body",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok(proxy_log_storage::decode_body(ctx.get_raw(0))),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn fts_available(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'request_logs_fts'",
+        [],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+fn row_to_log(row: &rusqlite::Row<'_>) -> rusqlite::Result<ProxyRequestLog> {
+    Ok(ProxyRequestLog {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        method: row.get(2)?,
+        url: row.get(3)?,
+        status: row.get(4)?,
+        duration: row.get(5)?,
+        model: row.get(6)?,
+        error: row.get(7)?,
+        request_body: row.get_ref(8).ok().and_then(proxy_log_storage::decode_body),
+        response_body: row.get_ref(9).ok().and_then(proxy_log_storage::decode_body),
+        input_tokens: row.get(10).unwrap_or(None),
+        output_tokens: row.get(11).unwrap_or(None),
+        account_email: row.get(12).unwrap_or(None),
+        mapped_model: row.get(13).unwrap_or(None),
+        provider: row.get(14).unwrap_or(None),
+        attempts: row.get(15).unwrap_or(None),
+        rotations: row.get(16).unwrap_or(None),
+        api_key: row.get(17).unwrap_or(None),
+        ttft_ms: row.get(18).unwrap_or(None),
+        stream_duration_ms: row.get(19).unwrap_or(None),
+        replay_of: row.get(20).unwrap_or(None),
+    })
+}
+
+/// 将日志放入写线程队列 (不阻塞)；队列已满时丢弃并返回 false
+pub fn enqueue_log(log: ProxyRequestLog, compression: LogCompression) -> Result<bool, String> {
+    Ok(store()?.enqueue(log, compression))
+}
+
+/// 日志写入队列指标
+pub fn writer_stats() -> Result<LogWriterStats, String> {
+    Ok(store()?.stats())
+}
+
+/// 写入一条日志及其全文索引与用量汇总 (由写线程在批量事务中调用)
+pub(crate) fn insert_log(
+    conn: &Connection,
+    log: &ProxyRequestLog,
+    compression: LogCompression,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens,
+                                   account_email, mapped_model, provider, attempts, rotations, api_key, ttft_ms, stream_duration_ms, replay_of)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)",
         params![
             log.id,
             log.timestamp,
@@ -55,43 +229,49 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.duration,
             log.model,
             log.error,
-            log.request_body,
-            log.response_body,
+            proxy_log_storage::encode_body(log.request_body.as_deref(), compression),
+            proxy_log_storage::encode_body(log.response_body.as_deref(), compression),
             log.input_tokens,
             log.output_tokens,
+            log.account_email,
+            log.mapped_model,
+            log.provider,
+            log.attempts,
+            log.rotations,
+            log.api_key,
+            log.ttft_ms.map(|v| v as i64),
+            log.stream_duration_ms.map(|v| v as i64),
+            log.replay_of,
         ],
     ).map_err(|e| e.to_string())?;
-
-    Ok(())
+    if fts_available(conn) && (log.request_body.is_some() || log.response_body.is_some()) {
+        index_bodies(conn, conn.last_insert_rowid(), log.request_body.as_deref(), log.response_body.as_deref())?;
+    }
+    crate::modules::proxy_usage::record(conn, log)
 }
 
 pub fn get_logs(limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens
-         FROM request_logs 
-         ORDER BY timestamp DESC 
-         LIMIT ?1"
-    ).map_err(|e| e.to_string())?;
+    get_logs_filtered(&ProxyLogFilter::default(), limit)
+}
+
+/// 按账号 / 模型 / 映射模型 / 提供方 / 尝试次数过滤查询日志
+pub fn get_logs_filtered(filter: &ProxyLogFilter, limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
+    let conn = store()?.reader()?;
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    push_filter_conditions(filter, &mut conditions, &mut args);
+    args.push(Box::new(limit as i64));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM request_logs {} ORDER BY timestamp DESC LIMIT ?",
+        LOG_COLUMNS,
+        where_clause(&conditions)
+    )).map_err(|e| e.to_string())?;
 
-    let logs_iter = stmt.query_map([limit], |row| {
-        Ok(ProxyRequestLog {
-            id: row.get(0)?,
-            timestamp: row.get(1)?,
-            method: row.get(2)?,
-            url: row.get(3)?,
-            status: row.get(4)?,
-            duration: row.get(5)?,
-            model: row.get(6)?,
-            error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
-            input_tokens: row.get(10).unwrap_or(None),
-            output_tokens: row.get(11).unwrap_or(None),
-        })
-    }).map_err(|e| e.to_string())?;
+    let logs_iter = stmt
+        .query_map(rusqlite::params_from_iter(args.iter()), row_to_log)
+        .map_err(|e| e.to_string())?;
 
     let mut logs = Vec::new();
     for log in logs_iter {
@@ -100,9 +280,175 @@ pub fn get_logs(limit: usize) -> Result<Vec<ProxyRequestLog>, String> {
     Ok(logs)
 }
 
+/// 按 ID 查询单条日志
+pub fn get_log(id: &str) -> Result<Option<ProxyRequestLog>, String> {
+    let conn = store()?.reader()?;
+    conn.query_row(
+        &format!("SELECT {} FROM request_logs WHERE id = ?1", LOG_COLUMNS),
+        [id],
+        row_to_log,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// 按检索条件导出日志 (JSONL / HAR / OTLP)，返回导出条数
+pub fn export_logs<W: std::io::Write>(
+    request: &LogExportRequest,
+    base_url: &str,
+    out: &mut W,
+) -> Result<u64, String> {
+    let conn = store()?.reader()?;
+    proxy_export::export_logs(request, base_url, |query| search_logs_with_conn(&conn, query), out)
+}
+
+/// 等待写线程将已入队的日志落盘
+pub fn flush_logs() -> Result<(), String> {
+    store()?.flush()
+}
+
+/// 按条件检索日志，按时间倒序游标分页
+pub fn search_logs(query: &LogSearchQuery) -> Result<LogSearchPage, String> {
+    let conn = store()?.reader()?;
+    search_logs_with_conn(&conn, query)
+}
+
+fn search_logs_with_conn(conn: &Connection, query: &LogSearchQuery) -> Result<LogSearchPage, String> {
+    let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    push_filter_conditions(&query.filter, &mut conditions, &mut args);
+
+    if let Some(start) = query.start_ms {
+        conditions.push("timestamp >= ?".to_string());
+        args.push(Box::new(start));
+    }
+    if let Some(end) = query.end_ms {
+        conditions.push("timestamp < ?".to_string());
+        args.push(Box::new(end));
+    }
+    if let Some(class) = query.status_class {
+        conditions.push(
+            match class {
+                StatusClass::Success => "status >= 200 AND status < 300",
+                StatusClass::Redirect => "status >= 300 AND status < 400",
+                StatusClass::ClientError => "status >= 400 AND status < 500",
+                StatusClass::ServerError => "status >= 500",
+                StatusClass::Error => "(status < 200 OR status >= 400)",
+            }
+            .to_string(),
+        );
+    }
+    if let Some(url) = query.url_contains.as_deref().filter(|s| !s.is_empty()) {
+        conditions.push("instr(url, ?) > 0".to_string());
+        args.push(Box::new(url.to_string()));
+    }
+    if let Some(error) = query.error_contains.as_deref().filter(|s| !s.is_empty()) {
+        conditions.push("instr(error, ?) > 0".to_string());
+        args.push(Box::new(error.to_string()));
+    }
+    if let Some(min) = query.min_duration_ms {
+        conditions.push("duration >= ?".to_string());
+        args.push(Box::new(min as i64));
+    }
+    if let Some(text) = query.text.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        if fts_available(conn) {
+            conditions.push(
+                "rowid IN (SELECT rowid FROM request_logs_fts WHERE request_logs_fts MATCH ?)".to_string(),
+            );
+            args.push(Box::new(fts_phrase(text)));
+        } else {
+            // 报文可能以 zstd / gzip 压缩存储，需解压后再匹配 (全表扫描)
+            register_decode_body(conn)?;
+            conditions.push(
+                "(instr(decode_body(request_body), ?) > 0 OR instr(decode_body(response_body), ?) > 0)".to_string(),
+            );
+            args.push(Box::new(text.to_string()));
+            args.push(Box::new(text.to_string()));
+        }
+    }
+    if let Some(cursor) = query.cursor.as_deref() {
+        let (timestamp, id) = parse_cursor(cursor)?;
+        conditions.push("(timestamp < ? OR (timestamp = ? AND id < ?))".to_string());
+        args.push(Box::new(timestamp));
+        args.push(Box::new(timestamp));
+        args.push(Box::new(id));
+    }
+    // 多取一条用于判断是否还有下一页
+    args.push(Box::new((limit + 1) as i64));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM request_logs {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+        LOG_COLUMNS,
+        where_clause(&conditions)
+    )).map_err(|e| e.to_string())?;
+
+    let mut logs = stmt
+        .query_map(rusqlite::params_from_iter(args.iter()), row_to_log)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let next_cursor = if logs.len() > limit {
+        logs.truncate(limit);
+        logs.last().map(|log| format!("{}:{}", log.timestamp, log.id))
+    } else {
+        None
+    };
+    Ok(LogSearchPage { logs, next_cursor })
+}
+
+fn push_filter_conditions(
+    filter: &ProxyLogFilter,
+    conditions: &mut Vec<String>,
+    args: &mut Vec<Box<dyn rusqlite::ToSql>>,
+) {
+    for (column, value) in [
+        ("account_email", &filter.account_email),
+        ("model", &filter.model),
+        ("mapped_model", &filter.mapped_model),
+        ("provider", &filter.provider),
+        ("api_key", &filter.api_key),
+        ("replay_of", &filter.replay_of),
+    ] {
+        if let Some(v) = value {
+            conditions.push(format!("{} = ?", column));
+            args.push(Box::new(v.clone()));
+        }
+    }
+    if let Some(min) = filter.min_attempts {
+        conditions.push("COALESCE(attempts, 0) >= ?".to_string());
+        args.push(Box::new(min));
+    }
+}
+
+fn where_clause(conditions: &[String]) -> String {
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
+/// 将用户输入转为 FTS5 短语查询，避免特殊字符触发语法错误
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+/// 游标格式: "<timestamp>:<id>"
+fn parse_cursor(cursor: &str) -> Result<(i64, String), String> {
+    let (timestamp, id) = cursor
+        .split_once(':')
+        .ok_or_else(|| format!("无效的分页游标: {}", cursor))?;
+    let timestamp = timestamp
+        .parse()
+        .map_err(|_| format!("无效的分页游标: {}", cursor))?;
+    Ok((timestamp, id.to_string()))
+}
+
 pub fn get_stats() -> Result<crate::proxy::monitor::ProxyStats, String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let conn = store()?.reader()?;
 
     let total_requests: u64 = conn.query_row(
         "SELECT COUNT(*) FROM request_logs",
@@ -130,8 +476,165 @@ pub fn get_stats() -> Result<crate::proxy::monitor::ProxyStats, String> {
 }
 
 pub fn clear_logs() -> Result<(), String> {
-    let db_path = get_proxy_db_path()?;
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
-    Ok(())
+    store()?.write(|conn| {
+        conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
+        crate::modules::proxy_usage::clear(conn)
+    })
+}
+
+/// 按保留策略清理日志并回收空间
+pub fn run_maintenance(config: &LogStorageConfig, full_vacuum: bool) -> Result<MaintenanceReport, String> {
+    let config = config.clone();
+    store()?.write(move |conn| {
+        proxy_log_storage::run_maintenance(conn, &config, chrono::Utc::now().timestamp_millis(), full_vacuum)
+    })
+}
+
+/// 查询用量汇总 (`bucketed` 为 true 时返回时间序列)
+pub fn get_usage(
+    query: &crate::modules::proxy_usage::UsageQuery,
+    bucketed: bool,
+) -> Result<Vec<crate::modules::proxy_usage::UsageRow>, String> {
+    let conn = store()?.reader()?;
+    crate::modules::proxy_usage::query(&conn, query, bucketed)
+}
+
+/// 根据现有日志重建用量汇总，返回处理的日志条数
+pub fn rebuild_usage() -> Result<u64, String> {
+    store()?.write(|conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let count = crate::modules::proxy_usage::rebuild(&tx)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(count)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(id: &str, timestamp: i64, url: &str, status: u16, body: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: id.to_string(),
+            timestamp,
+            method: "POST".to_string(),
+            url: url.to_string(),
+            status,
+            duration: timestamp as u64,
+            error: (status >= 400).then(|| format!("upstream error {}", status)),
+            request_body: Some(body.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        for (i, (url, status, body)) in [
+            ("/v1/messages", 200, "hello world"),
+            ("/v1/messages", 429, "quota exhausted please"),
+            ("/v1/chat/completions", 500, "tool call payload"),
+            ("/v1/messages", 200, "another hello"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            insert_log(
+                &conn,
+                &log(&format!("id-{}", i), 1000 + i as i64, url, status, body),
+                LogCompression::Zstd,
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_search_filters_and_full_text() {
+        let conn = setup();
+
+        let page = search_logs_with_conn(&conn, &LogSearchQuery {
+            status_class: Some(StatusClass::Error),
+            url_contains: Some("messages".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(page.logs.len(), 1);
+        assert_eq!(page.logs[0].id, "id-1");
+
+        let page = search_logs_with_conn(&conn, &LogSearchQuery {
+            text: Some("hello".to_string()),
+            ..Default::default()
+        }).unwrap();
+        let ids: Vec<_> = page.logs.iter().map(|l| l.id.as_str()).collect();
+        assert_eq!(ids, vec!["id-3", "id-0"]);
+
+        let page = search_logs_with_conn(&conn, &LogSearchQuery {
+            error_contains: Some("500".to_string()),
+            min_duration_ms: Some(1001),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(page.logs.len(), 1);
+        assert_eq!(page.logs[0].id, "id-2");
+    }
+
+    #[test]
+    fn test_compressed_bodies_are_searchable_and_decoded() {
+        let conn = setup();
+        let body = format!("{} needle", "padding ".repeat(200));
+        insert_log(&conn, &log("big", 2000, "/v1/messages", 200, &body), LogCompression::Gzip).unwrap();
+
+        let stored_type: String = conn
+            .query_row("SELECT typeof(request_body) FROM request_logs WHERE id = 'big'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored_type, "blob");
+
+        let page = search_logs_with_conn(&conn, &LogSearchQuery {
+            text: Some("needle".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(page.logs.len(), 1);
+        assert_eq!(page.logs[0].request_body.as_deref(), Some(body.as_str()));
+
+        // 删除日志后索引同步删除
+        conn.execute("DELETE FROM request_logs WHERE id = 'big'", []).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM request_logs_fts WHERE request_logs_fts MATCH 'needle'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_text_search_without_fts_decodes_bodies() {
+        let conn = setup();
+        conn.execute_batch("DROP TRIGGER request_logs_fts_ad; DROP TABLE request_logs_fts;").unwrap();
+        let body = format!("{} needle", "padding ".repeat(200));
+        insert_log(&conn, &log("big", 2000, "/v1/messages", 200, &body), LogCompression::Zstd).unwrap();
+
+        for (text, expected) in [("needle", vec!["big"]), ("hello", vec!["id-3", "id-0"])] {
+            let page = search_logs_with_conn(&conn, &LogSearchQuery {
+                text: Some(text.to_string()),
+                ..Default::default()
+            }).unwrap();
+            let ids: Vec<_> = page.logs.iter().map(|l| l.id.as_str()).collect();
+            assert_eq!(ids, expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_search_cursor_pagination() {
+        let conn = setup();
+        let mut query = LogSearchQuery {
+            limit: Some(3),
+            ..Default::default()
+        };
+        let first = search_logs_with_conn(&conn, &query).unwrap();
+        assert_eq!(first.logs.len(), 3);
+        assert_eq!(first.next_cursor.as_deref(), Some("1001:id-1"));
+
+        query.cursor = first.next_cursor;
+        let second = search_logs_with_conn(&conn, &query).unwrap();
+        assert_eq!(second.logs.len(), 1);
+        assert_eq!(second.logs[0].id, "id-0");
+        assert!(second.next_cursor.is_none());
+    }
 }
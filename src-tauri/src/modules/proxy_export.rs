@@ -0,0 +1,368 @@
+// 请求日志导出
+// 支持 JSONL (每行一条 ProxyRequestLog)、HAR 1.2 (浏览器开发者工具 / 抓包工具可直接打开)
+// 以及 OTLP/JSON (OpenTelemetry trace)，按页读取日志并流式写出，不在内存中累积全部结果
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+
+use crate::modules::redaction;
+use crate::proxy::monitor::{LogSearchPage, LogSearchQuery, ProxyRequestLog};
+
+/// 每次从数据库读取的条数
+const EXPORT_PAGE_SIZE: usize = 500;
+const CREATOR_NAME: &str = "Antigravity Tools";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Jsonl,
+    Har,
+    Otlp,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Har | ExportFormat::Otlp => "application/json",
+        }
+    }
+
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Har => "har",
+            ExportFormat::Otlp => "json",
+        }
+    }
+}
+
+/// 导出请求: 检索条件 (时间范围 / 过滤 / 全文) + 导出格式
+/// 检索条件中的 cursor 可用于从某条日志之后开始导出，limit 被忽略
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LogExportRequest {
+    #[serde(flatten)]
+    pub query: LogSearchQuery,
+    pub format: ExportFormat,
+}
+
+/// 导出结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub path: String,
+    pub format: ExportFormat,
+    pub count: u64,
+    pub bytes: u64,
+}
+
+/// 按时间倒序逐页读取日志并写出，返回导出条数
+/// `fetch_page` 为分页查询 (通常为 proxy_db 的检索)，`base_url` 用于拼接 HAR 中的绝对 URL
+pub fn export_logs<W: Write>(
+    request: &LogExportRequest,
+    base_url: &str,
+    mut fetch_page: impl FnMut(&LogSearchQuery) -> Result<LogSearchPage, String>,
+    out: &mut W,
+) -> Result<u64, String> {
+    let mut query = request.query.clone();
+    query.limit = Some(EXPORT_PAGE_SIZE);
+
+    let io = |e: std::io::Error| format!("写入导出数据失败: {}", e);
+    out.write_all(prologue(request.format).as_bytes()).map_err(io)?;
+
+    let mut count = 0u64;
+    loop {
+        let page = fetch_page(&query)?;
+        for mut log in page.logs {
+            // 导出前再次脱敏 (覆盖启用脱敏之前写入的日志)
+            redaction::redact_field(&mut log.request_body);
+            redaction::redact_field(&mut log.response_body);
+            redaction::redact_field(&mut log.error);
+            let line = match request.format {
+                ExportFormat::Jsonl => serde_json::to_string(&log),
+                ExportFormat::Har => serde_json::to_string(&har_entry(&log, base_url)),
+                ExportFormat::Otlp => serde_json::to_string(&otlp_span(&log)),
+            }
+            .map_err(|e| e.to_string())?;
+            let separator = match request.format {
+                ExportFormat::Jsonl => "",
+                _ if count > 0 => ",",
+                _ => "",
+            };
+            out.write_all(separator.as_bytes()).map_err(io)?;
+            out.write_all(line.as_bytes()).map_err(io)?;
+            if request.format == ExportFormat::Jsonl {
+                out.write_all(b"\n").map_err(io)?;
+            }
+            count += 1;
+        }
+        match page.next_cursor {
+            Some(cursor) => query.cursor = Some(cursor),
+            None => break,
+        }
+    }
+
+    out.write_all(epilogue(request.format).as_bytes()).map_err(io)?;
+    out.flush().map_err(io)?;
+    Ok(count)
+}
+
+/// 外层结构的开头 (条目逐条写在其中)
+fn prologue(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Jsonl => String::new(),
+        ExportFormat::Har => format!(
+            r#"{{"log":{{"version":"1.2","creator":{{"name":"{}","version":"{}"}},"pages":[],"entries":["#,
+            CREATOR_NAME,
+            env!("CARGO_PKG_VERSION")
+        ),
+        ExportFormat::Otlp => format!(
+            r#"{{"resourceSpans":[{{"resource":{{"attributes":[{}]}},"scopeSpans":[{{"scope":{{"name":"antigravity.proxy","version":"{}"}},"spans":["#,
+            json!({ "key": "service.name", "value": { "stringValue": CREATOR_NAME } }),
+            env!("CARGO_PKG_VERSION")
+        ),
+    }
+}
+
+fn epilogue(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Jsonl => "",
+        ExportFormat::Har => "]}}",
+        ExportFormat::Otlp => "]}]}]}",
+    }
+}
+
+fn mime_type(body: &str) -> &'static str {
+    if serde_json::from_str::<Value>(body).is_ok() {
+        "application/json"
+    } else {
+        "text/plain"
+    }
+}
+
+fn har_entry(log: &ProxyRequestLog, base_url: &str) -> Value {
+    let started = chrono::DateTime::from_timestamp_millis(log.timestamp)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let url = format!("{}{}", base_url.trim_end_matches('/'), log.url);
+    let query_string: Vec<Value> = log
+        .url
+        .split_once('?')
+        .map(|(_, query)| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut request = json!({
+        "method": log.method,
+        "url": url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": [],
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": log.request_body.as_ref().map_or(-1, |b| b.len() as i64),
+    });
+    if let Some(body) = &log.request_body {
+        request["postData"] = json!({ "mimeType": mime_type(body), "text": body });
+    }
+
+    let response_body = log.response_body.as_deref().unwrap_or_default();
+    let status_text = axum::http::StatusCode::from_u16(log.status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or_default();
+    // 流式响应按首 token 时间拆分等待与接收
+    let wait = log.ttft_ms.unwrap_or(log.duration).min(log.duration);
+
+    json!({
+        "startedDateTime": started,
+        "time": log.duration,
+        "request": request,
+        "response": {
+            "status": log.status,
+            "statusText": status_text,
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": [],
+            "content": {
+                "size": response_body.len(),
+                "mimeType": mime_type(response_body),
+                "text": response_body,
+            },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": -1,
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": wait, "receive": log.duration - wait },
+        "comment": log.error.clone().unwrap_or_default(),
+        "_id": log.id,
+        "_model": log.model,
+        "_mappedModel": log.mapped_model,
+        "_provider": log.provider,
+        "_account": log.account_email,
+        "_apiKey": log.api_key,
+        "_inputTokens": log.input_tokens,
+        "_outputTokens": log.output_tokens,
+        "_attempts": log.attempts,
+        "_replayOf": log.replay_of,
+    })
+}
+
+/// 由日志 ID 派生 trace / span ID (同一条日志多次导出结果一致)
+fn otlp_ids(log_id: &str) -> (String, String) {
+    let trace_id = match uuid::Uuid::parse_str(log_id) {
+        Ok(id) => id.simple().to_string(),
+        Err(_) => Sha256::digest(log_id.as_bytes())
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    };
+    let span_id = trace_id[..16].to_string();
+    (trace_id, span_id)
+}
+
+fn otlp_span(log: &ProxyRequestLog) -> Value {
+    let (trace_id, span_id) = otlp_ids(&log.id);
+    let start_ns = log.timestamp.max(0) as u128 * 1_000_000;
+    let end_ns = start_ns + log.duration as u128 * 1_000_000;
+    let path = log.url.split('?').next().unwrap_or_default();
+
+    let mut attributes = vec![
+        json!({ "key": "http.request.method", "value": { "stringValue": log.method } }),
+        json!({ "key": "url.path", "value": { "stringValue": path } }),
+        // OTLP/JSON 中 int64 以字符串表示
+        json!({ "key": "http.response.status_code", "value": { "intValue": log.status.to_string() } }),
+    ];
+    let mut push_str = |key: &str, value: &Option<String>| {
+        if let Some(v) = value {
+            attributes.push(json!({ "key": key, "value": { "stringValue": v } }));
+        }
+    };
+    push_str("gen_ai.request.model", &log.model);
+    push_str("gen_ai.response.model", &log.mapped_model);
+    push_str("gen_ai.system", &log.provider);
+    push_str("antigravity.account", &log.account_email);
+    push_str("antigravity.api_key", &log.api_key);
+    push_str("antigravity.replay_of", &log.replay_of);
+    push_str("error.message", &log.error);
+    for (key, value) in [
+        ("gen_ai.usage.input_tokens", log.input_tokens.map(u64::from)),
+        ("gen_ai.usage.output_tokens", log.output_tokens.map(u64::from)),
+        ("antigravity.attempts", log.attempts.map(u64::from)),
+        ("antigravity.ttft_ms", log.ttft_ms),
+    ] {
+        if let Some(v) = value {
+            attributes.push(json!({ "key": key, "value": { "intValue": v.to_string() } }));
+        }
+    }
+
+    // status code: 1 = OK, 2 = ERROR
+    let status = if log.status >= 400 || log.error.is_some() {
+        json!({ "code": 2, "message": log.error.clone().unwrap_or_default() })
+    } else {
+        json!({ "code": 1 })
+    };
+
+    json!({
+        "traceId": trace_id,
+        "spanId": span_id,
+        "name": format!("{} {}", log.method, path),
+        "kind": 2, // SPAN_KIND_SERVER
+        "startTimeUnixNano": start_ns.to_string(),
+        "endTimeUnixNano": end_ns.to_string(),
+        "attributes": attributes,
+        "status": status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_logs() -> Vec<ProxyRequestLog> {
+        (0..3)
+            .map(|i| ProxyRequestLog {
+                id: format!("log-{}", i),
+                timestamp: 1_700_000_000_000 + i,
+                method: "POST".to_string(),
+                url: "/v1beta/models/gemini:generateContent?alt=sse".to_string(),
+                status: if i == 1 { 429 } else { 200 },
+                duration: 120,
+                model: Some("gemini-2.5-pro".to_string()),
+                request_body: Some(r#"{"contents":[]}"#.to_string()),
+                response_body: Some("ok".to_string()),
+                input_tokens: Some(10),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// 模拟分页查询 (每页 2 条)
+    fn export(format: ExportFormat) -> (u64, String) {
+        let logs = sample_logs();
+        let request = LogExportRequest { format, ..Default::default() };
+        let mut out = Vec::new();
+        let count = export_logs(
+            &request,
+            "http://127.0.0.1:8045",
+            |query| {
+                let start: usize = query.cursor.as_deref().map_or(0, |c| c.parse().unwrap());
+                let end = (start + 2).min(logs.len());
+                Ok(LogSearchPage {
+                    logs: logs[start..end].to_vec(),
+                    next_cursor: (end < logs.len()).then(|| end.to_string()),
+                })
+            },
+            &mut out,
+        )
+        .unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_jsonl_export() {
+        let (count, text) = export(ExportFormat::Jsonl);
+        assert_eq!(count, 3);
+        let ids: Vec<String> = text
+            .lines()
+            .map(|l| serde_json::from_str::<ProxyRequestLog>(l).unwrap().id)
+            .collect();
+        assert_eq!(ids, ["log-0", "log-1", "log-2"]);
+    }
+
+    #[test]
+    fn test_har_export() {
+        let (_, text) = export(ExportFormat::Har);
+        let har: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(har["log"]["version"], "1.2");
+        let entries = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0]["request"]["url"],
+            "http://127.0.0.1:8045/v1beta/models/gemini:generateContent?alt=sse"
+        );
+        assert_eq!(entries[0]["request"]["queryString"][0]["name"], "alt");
+        assert_eq!(entries[0]["request"]["postData"]["mimeType"], "application/json");
+        assert_eq!(entries[1]["response"]["statusText"], "Too Many Requests");
+    }
+
+    #[test]
+    fn test_otlp_export() {
+        let (_, text) = export(ExportFormat::Otlp);
+        let otlp: Value = serde_json::from_str(&text).unwrap();
+        let spans = otlp["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0]["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(spans[0]["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(spans[0]["name"], "POST /v1beta/models/gemini:generateContent");
+        assert_eq!(spans[0]["endTimeUnixNano"], "1700000000120000000");
+        assert_eq!(spans[1]["status"]["code"], 2);
+    }
+}
@@ -0,0 +1,337 @@
+// 请求日志存储策略
+// 写入前: 敏感信息脱敏、base64 数据替换为占位符、按方向截断报文、压缩大报文
+// 定期维护: 按保留天数 / 条数 / 数据库大小清理旧日志，并执行增量 VACUUM
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+use crate::modules::redaction;
+use crate::proxy::config::{LogCompression, LogStorageConfig};
+use crate::proxy::monitor::ProxyRequestLog;
+
+/// 小于该长度的报文不压缩 (压缩收益低于开销)
+const COMPRESS_MIN_BYTES: usize = 512;
+/// 数据库超过大小上限时，每轮删除的最旧日志比例
+const SIZE_PRUNE_FRACTION: i64 = 10;
+const SIZE_PRUNE_MAX_ROUNDS: usize = 20;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+static BASE64_DATA_URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"data:([\w.+-]+/[\w.+-]+);base64,[A-Za-z0-9+/=]+").unwrap());
+/// JSON 中的裸 base64 字段 (如 Gemini inlineData.data)
+static BASE64_RUN: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9+/]{1024,}={0,2}").unwrap());
+
+/// 将 base64 数据替换为 `[base64 <mime>, <n> bytes]` 形式的占位符
+pub fn redact_base64(body: &str) -> Cow<'_, str> {
+    let replaced = BASE64_DATA_URL.replace_all(body, |caps: &regex::Captures| {
+        let payload = caps[0].len() - caps[0].find(',').map_or(0, |i| i + 1);
+        format!("[base64 {}, {} bytes]", &caps[1], payload * 3 / 4)
+    });
+    match BASE64_RUN.replace_all(&replaced, |caps: &regex::Captures| {
+        format!("[base64 {} bytes]", caps[0].len() * 3 / 4)
+    }) {
+        Cow::Borrowed(_) => replaced,
+        Cow::Owned(s) => Cow::Owned(s),
+    }
+}
+
+/// 按字节上限截断报文 (保证 UTF-8 边界)，limit 为 0 时不保存
+pub fn truncate_body(body: String, limit_bytes: usize) -> Option<String> {
+    truncate_captured_body(body, limit_bytes, 0)
+}
+
+/// 截断已采集的报文，`uncaptured` 为采集阶段未保留的字节数
+/// 截断标记计入上限内，因此结果不超过 limit，重复截断不会改变内容
+pub fn truncate_captured_body(body: String, limit_bytes: usize, uncaptured: usize) -> Option<String> {
+    if limit_bytes == 0 {
+        return None;
+    }
+    if uncaptured == 0 && body.len() <= limit_bytes {
+        return Some(body);
+    }
+    let total = body.len() + uncaptured;
+    let marker_len = truncation_marker(total).len();
+    let mut end = body.len().min(limit_bytes.saturating_sub(marker_len));
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(format!("{}{}", &body[..end], truncation_marker(total - end)))
+}
+
+fn truncation_marker(dropped: usize) -> String {
+    format!("…[truncated {} bytes]", dropped)
+}
+
+static LOSSY_MARKER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"…\[truncated \d+ bytes\]$|\[base64 (?:[\w.+-]+/[\w.+-]+, )?\d+ bytes\]|\[REDACTED(?::\w+)?\]").unwrap()
+});
+
+/// 报文是否经过截断、base64 替换或敏感信息脱敏 (已无法还原原始内容)
+pub fn is_lossy(body: &str) -> bool {
+    LOSSY_MARKER.is_match(body)
+}
+
+/// 写入前按存储策略处理日志中的报文
+pub fn prepare_log(log: &mut ProxyRequestLog, config: &LogStorageConfig) {
+    redaction::redact_field(&mut log.request_body);
+    redaction::redact_field(&mut log.response_body);
+    redaction::redact_field(&mut log.error);
+    let process = |body: Option<String>, limit_kb: usize| {
+        body.and_then(|b| {
+            let b = if config.redact_base64 {
+                redact_base64(&b).into_owned()
+            } else {
+                b
+            };
+            truncate_body(b, limit_kb * 1024)
+        })
+    };
+    log.request_body = process(log.request_body.take(), config.request_body_limit_kb);
+    log.response_body = process(log.response_body.take(), config.response_body_limit_kb);
+    if let Some(error) = log.error.take() {
+        // 错误信息通常来自响应报文，沿用响应方向的上限
+        log.error = truncate_body(error, config.response_body_limit_kb.max(4) * 1024);
+    }
+}
+
+/// 压缩报文用于存储 (小报文与压缩失败时保留原文)
+pub fn encode_body(body: Option<&str>, compression: LogCompression) -> Value {
+    let Some(body) = body else {
+        return Value::Null;
+    };
+    if body.len() < COMPRESS_MIN_BYTES {
+        return Value::Text(body.to_string());
+    }
+    let compressed = match compression {
+        LogCompression::None => None,
+        LogCompression::Zstd => zstd::encode_all(body.as_bytes(), 3).ok(),
+        LogCompression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(body.as_bytes())
+                .ok()
+                .and_then(|_| encoder.finish().ok())
+        }
+    };
+    match compressed {
+        Some(bytes) if bytes.len() < body.len() => Value::Blob(bytes),
+        _ => Value::Text(body.to_string()),
+    }
+}
+
+/// 读取报文列: TEXT 为原文，BLOB 按魔数识别压缩格式
+pub fn decode_body(value: ValueRef<'_>) -> Option<String> {
+    match value {
+        ValueRef::Text(text) => Some(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(bytes) => {
+            let decoded = if bytes.starts_with(&ZSTD_MAGIC) {
+                zstd::decode_all(bytes).ok()
+            } else if bytes.starts_with(&GZIP_MAGIC) {
+                let mut out = Vec::new();
+                flate2::read::GzDecoder::new(bytes)
+                    .read_to_end(&mut out)
+                    .ok()
+                    .map(|_| out)
+            } else {
+                Some(bytes.to_vec())
+            };
+            Some(
+                decoded
+                    .map(|b| String::from_utf8_lossy(&b).into_owned())
+                    .unwrap_or_else(|| "[Undecodable Body]".to_string()),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// 维护执行结果
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct MaintenanceReport {
+    pub deleted_by_age: u64,
+    pub deleted_by_rows: u64,
+    pub deleted_by_size: u64,
+    /// 维护完成后的数据库文件大小 (字节)
+    pub db_size_bytes: u64,
+    pub full_vacuum: bool,
+}
+
+/// 确保数据库使用增量 auto_vacuum (旧数据库需一次完整 VACUUM 才能切换)
+pub fn ensure_incremental_vacuum(conn: &Connection) -> Result<(), String> {
+    let mode: i64 = conn
+        .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if mode != 2 {
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 按保留策略清理日志 (用量汇总表不受影响)，随后回收空闲页
+pub fn run_maintenance(
+    conn: &Connection,
+    config: &LogStorageConfig,
+    now_ms: i64,
+    full_vacuum: bool,
+) -> Result<MaintenanceReport, String> {
+    let mut report = MaintenanceReport {
+        full_vacuum,
+        ..Default::default()
+    };
+
+    if config.max_age_days > 0 {
+        let cutoff = now_ms - config.max_age_days as i64 * 86_400_000;
+        report.deleted_by_age = conn
+            .execute("DELETE FROM request_logs WHERE timestamp < ?1", [cutoff])
+            .map_err(|e| e.to_string())? as u64;
+    }
+
+    if config.max_rows > 0 {
+        report.deleted_by_rows = conn
+            .execute(
+                "DELETE FROM request_logs WHERE rowid IN (
+                    SELECT rowid FROM request_logs ORDER BY timestamp DESC LIMIT -1 OFFSET ?1
+                )",
+                [config.max_rows as i64],
+            )
+            .map_err(|e| e.to_string())? as u64;
+    }
+
+    if config.max_db_size_mb > 0 {
+        let max_bytes = config.max_db_size_mb * 1024 * 1024;
+        for _ in 0..SIZE_PRUNE_MAX_ROUNDS {
+            if used_bytes(conn)? <= max_bytes {
+                break;
+            }
+            let rows: i64 = conn
+                .query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            if rows == 0 {
+                break;
+            }
+            report.deleted_by_size += conn
+                .execute(
+                    "DELETE FROM request_logs WHERE rowid IN (
+                        SELECT rowid FROM request_logs ORDER BY timestamp ASC LIMIT ?1
+                    )",
+                    [(rows / SIZE_PRUNE_FRACTION).max(1)],
+                )
+                .map_err(|e| e.to_string())? as u64;
+        }
+    }
+
+    if full_vacuum {
+        conn.execute_batch("VACUUM").map_err(|e| e.to_string())?;
+    } else {
+        conn.execute_batch("PRAGMA incremental_vacuum")
+            .map_err(|e| e.to_string())?;
+    }
+    report.db_size_bytes = file_bytes(conn)?;
+    Ok(report)
+}
+
+fn pragma(conn: &Connection, name: &str) -> Result<u64, String> {
+    conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, i64>(0))
+        .map(|v| v.max(0) as u64)
+        .map_err(|e| e.to_string())
+}
+
+/// 实际占用的数据页大小 (不含空闲页)
+fn used_bytes(conn: &Connection) -> Result<u64, String> {
+    let pages = pragma(conn, "page_count")?.saturating_sub(pragma(conn, "freelist_count")?);
+    Ok(pages * pragma(conn, "page_size")?)
+}
+
+fn file_bytes(conn: &Connection) -> Result<u64, String> {
+    Ok(pragma(conn, "page_count")? * pragma(conn, "page_size")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_base64_placeholders() {
+        let image = "A".repeat(2000);
+        let body = format!(
+            r#"{{"url":"data:image/png;base64,{}","inline":{{"data":"{}"}},"text":"hi"}}"#,
+            image, image
+        );
+        let redacted = redact_base64(&body);
+        assert_eq!(
+            redacted,
+            r#"{"url":"[base64 image/png, 1500 bytes]","inline":{"data":"[base64 1500 bytes]"},"text":"hi"}"#
+        );
+        assert!(matches!(redact_base64("plain text"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_truncate_respects_char_boundary() {
+        let truncated = truncate_body("你好世界".repeat(10), 40).unwrap();
+        assert_eq!(truncated, "你好世界你…[truncated 105 bytes]");
+        assert!(truncated.len() <= 40);
+        // 再次截断保持不变
+        assert_eq!(truncate_body(truncated.clone(), 40).unwrap(), truncated);
+        assert_eq!(
+            truncate_captured_body("abc".to_string(), 40, 10).unwrap(),
+            "abc…[truncated 10 bytes]"
+        );
+        assert_eq!(truncate_body("abc".to_string(), 10).unwrap(), "abc");
+        assert!(truncate_body("abc".to_string(), 0).is_none());
+
+        assert!(is_lossy(&truncated));
+        assert!(is_lossy(r#"{"url":"[base64 image/png, 1500 bytes]"}"#));
+        assert!(!is_lossy(r#"{"text":"[truncated] bytes"}"#));
+        assert!(is_lossy(r#"{"key":"[REDACTED:api_key]"}"#));
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let body = "hello world ".repeat(200);
+        for compression in [LogCompression::Zstd, LogCompression::Gzip] {
+            let encoded = encode_body(Some(&body), compression);
+            let Value::Blob(bytes) = &encoded else {
+                panic!("expected compressed blob for {:?}", compression);
+            };
+            assert_eq!(decode_body(ValueRef::Blob(bytes)).unwrap(), body);
+        }
+        assert_eq!(encode_body(Some("short"), LogCompression::Zstd), Value::Text("short".to_string()));
+    }
+
+    #[test]
+    fn test_retention_by_age_and_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE request_logs (id TEXT PRIMARY KEY, timestamp INTEGER)")
+            .unwrap();
+        let day = 86_400_000;
+        let now = 100 * day;
+        for i in 0..10 {
+            conn.execute(
+                "INSERT INTO request_logs (id, timestamp) VALUES (?1, ?2)",
+                rusqlite::params![format!("id-{}", i), now - i * day],
+            )
+            .unwrap();
+        }
+        let config = LogStorageConfig {
+            max_age_days: 7,
+            max_rows: 5,
+            max_db_size_mb: 0,
+            ..Default::default()
+        };
+        let report = run_maintenance(&conn, &config, now, false).unwrap();
+        assert_eq!(report.deleted_by_age, 2);
+        assert_eq!(report.deleted_by_rows, 3);
+        let oldest: i64 = conn
+            .query_row("SELECT MIN(timestamp) FROM request_logs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(oldest, now - 4 * day);
+    }
+}
@@ -0,0 +1,336 @@
+// 请求日志数据库连接管理
+// 写入: 专用写线程持有唯一的 WAL 连接，通过有界通道接收日志并按批次在事务中写入
+// 读取: 只读连接池，查询不再每次新建连接
+use rusqlite::Connection;
+use serde::Serialize;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::proxy::config::LogCompression;
+use crate::proxy::monitor::ProxyRequestLog;
+
+/// 写入队列容量，队列满时新日志会被丢弃 (不阻塞请求路径)
+pub const WRITE_QUEUE_CAPACITY: usize = 4096;
+/// 单个事务最多写入的日志条数
+const MAX_BATCH: usize = 256;
+/// 攒批等待时间
+const BATCH_WINDOW: Duration = Duration::from_millis(50);
+/// 读连接池保留的空闲连接数
+const READ_POOL_IDLE: usize = 4;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Job = Box<dyn FnOnce(&mut Connection) + Send>;
+
+enum WriteCommand {
+    Insert(Box<ProxyRequestLog>, LogCompression),
+    /// 在写连接上执行任意写操作 (清空 / 维护 / 重建)，执行前先落盘已排队的日志
+    Run(Job),
+}
+
+/// 写入队列与批处理指标
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct LogWriterStats {
+    /// 当前排队等待写入的日志数
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    /// 已写入的日志总数
+    pub written: u64,
+    /// 因队列已满而丢弃的日志数 (日志写入跟不上请求速度)
+    pub dropped: u64,
+    /// 写入失败的日志数
+    pub failed: u64,
+    pub batches: u64,
+    pub last_batch_size: u64,
+    pub max_batch_size: u64,
+    /// 最近一次批量写入耗时 (毫秒)
+    pub last_batch_ms: u64,
+}
+
+#[derive(Default)]
+struct WriterMetrics {
+    queue_depth: AtomicUsize,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+    batches: AtomicU64,
+    last_batch_size: AtomicU64,
+    max_batch_size: AtomicU64,
+    last_batch_ms: AtomicU64,
+}
+
+pub struct LogStore {
+    path: PathBuf,
+    sender: SyncSender<WriteCommand>,
+    metrics: Arc<WriterMetrics>,
+    idle_readers: Mutex<Vec<Connection>>,
+}
+
+impl LogStore {
+    /// 打开数据库、初始化表结构并启动写线程
+    pub fn open(path: &Path, init: impl FnOnce(&Connection) -> Result<(), String>) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .map_err(|e| e.to_string())?;
+        init(&conn)?;
+
+        let (sender, receiver) = mpsc::sync_channel(WRITE_QUEUE_CAPACITY);
+        let metrics = Arc::new(WriterMetrics::default());
+        let writer_metrics = metrics.clone();
+        std::thread::Builder::new()
+            .name("proxy-log-writer".to_string())
+            .spawn(move || run_writer(conn, receiver, writer_metrics))
+            .map_err(|e| format!("启动日志写线程失败: {}", e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender,
+            metrics,
+            idle_readers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 将日志放入写入队列；队列已满时丢弃并返回 false
+    pub fn enqueue(&self, log: ProxyRequestLog, compression: LogCompression) -> bool {
+        // 先计数再发送，避免写线程先行扣减导致计数下溢
+        self.metrics.queue_depth.fetch_add(1, Ordering::Relaxed);
+        let result = self.sender.try_send(WriteCommand::Insert(Box::new(log), compression));
+        if result.is_err() {
+            self.metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        }
+        match result {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.metrics.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped == 1 || dropped.is_multiple_of(1000) {
+                    tracing::warn!(
+                        "Proxy log writer is falling behind, {} logs dropped so far",
+                        dropped
+                    );
+                }
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.metrics.failed.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// 在写连接上执行写操作并等待结果
+    pub fn write<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        let job: Job = Box::new(move |conn| {
+            let _ = reply_tx.send(f(conn));
+        });
+        self.sender
+            .send(WriteCommand::Run(job))
+            .map_err(|_| "日志写线程已停止".to_string())?;
+        reply_rx
+            .recv()
+            .map_err(|_| "日志写线程已停止".to_string())?
+    }
+
+    /// 等待已排队的日志全部写入
+    pub fn flush(&self) -> Result<(), String> {
+        self.write(|_| Ok(()))
+    }
+
+    /// 从读连接池获取连接 (用完自动归还)
+    pub fn reader(&self) -> Result<PooledReader<'_>, String> {
+        let idle = self
+            .idle_readers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = Connection::open(&self.path).map_err(|e| e.to_string())?;
+                conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+                conn.execute_batch("PRAGMA query_only = ON;")
+                    .map_err(|e| e.to_string())?;
+                conn
+            }
+        };
+        Ok(PooledReader {
+            store: self,
+            conn: Some(conn),
+        })
+    }
+
+    pub fn stats(&self) -> LogWriterStats {
+        let m = &self.metrics;
+        LogWriterStats {
+            queue_depth: m.queue_depth.load(Ordering::Relaxed),
+            queue_capacity: WRITE_QUEUE_CAPACITY,
+            written: m.written.load(Ordering::Relaxed),
+            dropped: m.dropped.load(Ordering::Relaxed),
+            failed: m.failed.load(Ordering::Relaxed),
+            batches: m.batches.load(Ordering::Relaxed),
+            last_batch_size: m.last_batch_size.load(Ordering::Relaxed),
+            max_batch_size: m.max_batch_size.load(Ordering::Relaxed),
+            last_batch_ms: m.last_batch_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 读连接池中的连接
+pub struct PooledReader<'a> {
+    store: &'a LogStore,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledReader<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("pooled connection already returned")
+    }
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let mut idle = self
+                .store
+                .idle_readers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if idle.len() < READ_POOL_IDLE {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+fn run_writer(mut conn: Connection, receiver: Receiver<WriteCommand>, metrics: Arc<WriterMetrics>) {
+    let mut batch: Vec<(Box<ProxyRequestLog>, LogCompression)> = Vec::with_capacity(MAX_BATCH);
+
+    while let Ok(command) = receiver.recv() {
+        let mut pending_job = None;
+        match command {
+            WriteCommand::Insert(log, compression) => batch.push((log, compression)),
+            WriteCommand::Run(job) => pending_job = Some(job),
+        }
+
+        // 攒批: 在时间窗口内继续接收日志，遇到写操作时先落盘当前批次
+        let deadline = Instant::now() + BATCH_WINDOW;
+        while pending_job.is_none() && batch.len() < MAX_BATCH {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(timeout) {
+                Ok(WriteCommand::Insert(log, compression)) => batch.push((log, compression)),
+                Ok(WriteCommand::Run(job)) => pending_job = Some(job),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if !batch.is_empty() {
+            write_batch(&mut conn, &mut batch, &metrics);
+        }
+        if let Some(job) = pending_job {
+            job(&mut conn);
+        }
+    }
+    tracing::debug!("Proxy log writer stopped");
+}
+
+fn write_batch(
+    conn: &mut Connection,
+    batch: &mut Vec<(Box<ProxyRequestLog>, LogCompression)>,
+    metrics: &WriterMetrics,
+) {
+    let started = Instant::now();
+    let size = batch.len() as u64;
+    metrics.queue_depth.fetch_sub(batch.len(), Ordering::Relaxed);
+
+    let result = conn.transaction().map_err(|e| e.to_string()).and_then(|mut tx| {
+        let mut failed = 0;
+        for (log, compression) in batch.iter() {
+            // 每条日志使用独立的保存点，单条失败不影响同批次其他日志
+            let inserted = tx.savepoint().map_err(|e| e.to_string()).and_then(|sp| {
+                crate::modules::proxy_db::insert_log(&sp, log, *compression)?;
+                sp.commit().map_err(|e| e.to_string())
+            });
+            if let Err(e) = inserted {
+                tracing::error!("Failed to save proxy log to DB: {}", e);
+                failed += 1;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(failed)
+    });
+    batch.clear();
+
+    match result {
+        Ok(failed) => {
+            metrics.written.fetch_add(size - failed, Ordering::Relaxed);
+            metrics.failed.fetch_add(failed, Ordering::Relaxed);
+        }
+        Err(e) => {
+            tracing::error!("Failed to commit proxy log batch: {}", e);
+            metrics.failed.fetch_add(size, Ordering::Relaxed);
+        }
+    }
+    metrics.batches.fetch_add(1, Ordering::Relaxed);
+    metrics.last_batch_size.store(size, Ordering::Relaxed);
+    metrics.max_batch_size.fetch_max(size, Ordering::Relaxed);
+    metrics
+        .last_batch_ms
+        .store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_batches_and_reader_pool() {
+        let dir = std::env::temp_dir().join(format!("proxy-log-store-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = LogStore::open(&dir.join("logs.db"), crate::modules::proxy_db::init_schema).unwrap();
+
+        for i in 0..20 {
+            let log = ProxyRequestLog {
+                id: format!("id-{}", i),
+                timestamp: i,
+                ..Default::default()
+            };
+            assert!(store.enqueue(log, LogCompression::None));
+        }
+        store.flush().unwrap();
+
+        let stats = store.stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.written, 20);
+        assert!(stats.batches >= 1 && stats.max_batch_size <= MAX_BATCH as u64);
+
+        // 写操作在写线程上执行
+        let deleted = store
+            .write(|conn| {
+                conn.execute("DELETE FROM request_logs WHERE timestamp >= 1", [])
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap();
+        assert_eq!(deleted, 19);
+
+        {
+            let reader = store.reader().unwrap();
+            let count: i64 = reader
+                .query_row("SELECT COUNT(*) FROM request_logs", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(count, 1);
+            assert!(reader.execute("DELETE FROM request_logs", []).is_err());
+        }
+        assert_eq!(store.idle_readers.lock().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
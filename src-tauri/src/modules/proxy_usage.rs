@@ -0,0 +1,481 @@
+// 反代用量统计汇总表
+// 每条请求日志写入时同步累加到 小时/天 × 模型 × 账号 × API Key 的汇总行，
+// 延迟分位数通过固定分桶的直方图近似计算，查询无需扫描 request_logs
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::proxy::monitor::ProxyRequestLog;
+
+const HOUR_MS: i64 = 3_600_000;
+const DAY_MS: i64 = 86_400_000;
+
+/// 延迟直方图分桶上界 (毫秒)，最后一个桶收纳所有更慢的请求
+const LATENCY_BOUNDS_MS: [u64; 14] = [
+    100, 250, 500, 1_000, 2_000, 3_000, 5_000, 10_000, 20_000, 30_000, 60_000, 120_000, 300_000,
+    u64::MAX,
+];
+
+/// 汇总的时间粒度 (按 UTC 对齐)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGranularity {
+    #[default]
+    Hour,
+    Day,
+}
+
+impl UsageGranularity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    fn bucket_start(self, timestamp_ms: i64) -> i64 {
+        let size = match self {
+            Self::Hour => HOUR_MS,
+            Self::Day => DAY_MS,
+        };
+        timestamp_ms - timestamp_ms.rem_euclid(size)
+    }
+}
+
+/// 可分组的维度
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageDimension {
+    Model,
+    Account,
+    ApiKey,
+}
+
+impl UsageDimension {
+    fn column(self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::Account => "account",
+            Self::ApiKey => "api_key",
+        }
+    }
+}
+
+/// 用量查询条件
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct UsageQuery {
+    pub granularity: UsageGranularity,
+    /// 起始时间 (毫秒, 含)
+    pub start_ms: Option<i64>,
+    /// 结束时间 (毫秒, 不含)
+    pub end_ms: Option<i64>,
+    /// 分组维度，未列出的维度会被合并
+    pub group_by: Vec<UsageDimension>,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub api_key: Option<String>,
+}
+
+/// 一行汇总结果
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct UsageRow {
+    /// 时间桶起点 (毫秒)，按维度汇总时为 None
+    pub bucket: Option<i64>,
+    pub model: Option<String>,
+    pub account: Option<String>,
+    pub api_key: Option<String>,
+    pub request_count: u64,
+    pub error_count: u64,
+    pub error_rate: f64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub avg_latency_ms: u64,
+    /// 直方图近似值 (所在分桶的上界)
+    pub p50_latency_ms: u64,
+    pub p95_latency_ms: u64,
+}
+
+pub fn init_tables(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS usage_rollups (
+            granularity TEXT NOT NULL,
+            bucket INTEGER NOT NULL,
+            model TEXT NOT NULL,
+            account TEXT NOT NULL,
+            api_key TEXT NOT NULL,
+            request_count INTEGER NOT NULL DEFAULT 0,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            input_tokens INTEGER NOT NULL DEFAULT 0,
+            output_tokens INTEGER NOT NULL DEFAULT 0,
+            total_duration INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (granularity, bucket, model, account, api_key)
+        );
+        CREATE TABLE IF NOT EXISTS usage_latency_histogram (
+            granularity TEXT NOT NULL,
+            bucket INTEGER NOT NULL,
+            model TEXT NOT NULL,
+            account TEXT NOT NULL,
+            api_key TEXT NOT NULL,
+            latency_bucket INTEGER NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (granularity, bucket, model, account, api_key, latency_bucket)
+        );",
+    )
+    .map_err(|e| e.to_string())?;
+
+    // 首次创建汇总表时从已有日志回填
+    let rollup_rows: i64 = conn
+        .query_row("SELECT COUNT(*) FROM usage_rollups", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if rollup_rows == 0 {
+        rebuild(conn)?;
+    }
+    Ok(())
+}
+
+/// 将一条日志累加到所有粒度的汇总行
+pub fn record(conn: &Connection, log: &ProxyRequestLog) -> Result<(), String> {
+    let model = log.model.as_deref().unwrap_or("");
+    let account = log.account_email.as_deref().unwrap_or("");
+    let api_key = log.api_key.as_deref().unwrap_or("");
+    let is_error = !(200..400).contains(&log.status);
+    let latency_bucket = LATENCY_BOUNDS_MS
+        .iter()
+        .position(|bound| log.duration <= *bound)
+        .unwrap_or(LATENCY_BOUNDS_MS.len() - 1);
+
+    for granularity in [UsageGranularity::Hour, UsageGranularity::Day] {
+        let bucket = granularity.bucket_start(log.timestamp);
+        conn.execute(
+            "INSERT INTO usage_rollups (granularity, bucket, model, account, api_key,
+                                        request_count, error_count, input_tokens, output_tokens, total_duration)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, ?7, ?8, ?9)
+             ON CONFLICT (granularity, bucket, model, account, api_key) DO UPDATE SET
+                request_count = request_count + 1,
+                error_count = error_count + excluded.error_count,
+                input_tokens = input_tokens + excluded.input_tokens,
+                output_tokens = output_tokens + excluded.output_tokens,
+                total_duration = total_duration + excluded.total_duration",
+            params![
+                granularity.as_str(),
+                bucket,
+                model,
+                account,
+                api_key,
+                is_error as i64,
+                log.input_tokens.unwrap_or(0),
+                log.output_tokens.unwrap_or(0),
+                log.duration as i64,
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO usage_latency_histogram (granularity, bucket, model, account, api_key, latency_bucket, count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1)
+             ON CONFLICT (granularity, bucket, model, account, api_key, latency_bucket) DO UPDATE SET
+                count = count + 1",
+            params![granularity.as_str(), bucket, model, account, api_key, latency_bucket as i64],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 清空并根据 request_logs 重新生成汇总表
+pub fn rebuild(conn: &Connection) -> Result<u64, String> {
+    clear(conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT timestamp, status, duration, model, input_tokens, output_tokens, account_email, api_key
+             FROM request_logs",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ProxyRequestLog {
+                timestamp: row.get(0)?,
+                status: row.get(1)?,
+                duration: row.get(2)?,
+                model: row.get(3)?,
+                input_tokens: row.get(4).unwrap_or(None),
+                output_tokens: row.get(5).unwrap_or(None),
+                account_email: row.get(6).unwrap_or(None),
+                api_key: row.get(7).unwrap_or(None),
+                ..Default::default()
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut count = 0;
+    for log in rows {
+        record(conn, &log.map_err(|e| e.to_string())?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn clear(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("DELETE FROM usage_rollups; DELETE FROM usage_latency_histogram;")
+        .map_err(|e| e.to_string())
+}
+
+/// 查询汇总数据
+/// `bucketed` 为 true 时按时间桶输出时间序列，否则在整个时间范围内按维度汇总
+pub fn query(conn: &Connection, query: &UsageQuery, bucketed: bool) -> Result<Vec<UsageRow>, String> {
+    let mut group_columns: Vec<&str> = Vec::new();
+    if bucketed {
+        group_columns.push("bucket");
+    }
+    for dimension in [UsageDimension::Model, UsageDimension::Account, UsageDimension::ApiKey] {
+        if query.group_by.contains(&dimension) {
+            group_columns.push(dimension.column());
+        }
+    }
+
+    let mut conditions = vec!["granularity = ?".to_string()];
+    let mut args: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(query.granularity.as_str())];
+    if let Some(start) = query.start_ms {
+        conditions.push("bucket >= ?".to_string());
+        args.push(Box::new(query.granularity.bucket_start(start)));
+    }
+    if let Some(end) = query.end_ms {
+        conditions.push("bucket < ?".to_string());
+        args.push(Box::new(end));
+    }
+    for (column, value) in [
+        ("model", &query.model),
+        ("account", &query.account),
+        ("api_key", &query.api_key),
+    ] {
+        if let Some(v) = value {
+            conditions.push(format!("{} = ?", column));
+            args.push(Box::new(v.clone()));
+        }
+    }
+
+    let where_clause = conditions.join(" AND ");
+    let (select_prefix, group_clause) = if group_columns.is_empty() {
+        (String::new(), String::new())
+    } else {
+        let joined = group_columns.join(", ");
+        (format!("{}, ", joined), format!("GROUP BY {}", joined))
+    };
+    let key_len = group_columns.len();
+
+    let mut rows: Vec<(Vec<Option<String>>, UsageRow)> = Vec::new();
+    {
+        let sql = format!(
+            "SELECT {}SUM(request_count), SUM(error_count), SUM(input_tokens), SUM(output_tokens), SUM(total_duration)
+             FROM usage_rollups WHERE {} {} ORDER BY {}",
+            select_prefix,
+            where_clause,
+            group_clause,
+            if group_columns.is_empty() { "1".to_string() } else { group_columns.join(", ") }
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let mapped = stmt
+            .query_map(rusqlite::params_from_iter(args.iter()), |row| {
+                let key = read_key(row, &group_columns)?;
+                let request_count: Option<i64> = row.get(key_len)?;
+                let error_count: Option<i64> = row.get(key_len + 1)?;
+                let input_tokens: Option<i64> = row.get(key_len + 2)?;
+                let output_tokens: Option<i64> = row.get(key_len + 3)?;
+                let total_duration: Option<i64> = row.get(key_len + 4)?;
+                let request_count = request_count.unwrap_or(0).max(0) as u64;
+                let error_count = error_count.unwrap_or(0).max(0) as u64;
+                let mut usage = UsageRow {
+                    request_count,
+                    error_count,
+                    input_tokens: input_tokens.unwrap_or(0).max(0) as u64,
+                    output_tokens: output_tokens.unwrap_or(0).max(0) as u64,
+                    ..Default::default()
+                };
+                if request_count > 0 {
+                    usage.error_rate = error_count as f64 / request_count as f64;
+                    usage.avg_latency_ms = total_duration.unwrap_or(0).max(0) as u64 / request_count;
+                }
+                apply_key(&mut usage, &group_columns, &key);
+                Ok((key, usage))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in mapped {
+            let (key, usage) = row.map_err(|e| e.to_string())?;
+            if usage.request_count > 0 {
+                rows.push((key, usage));
+            }
+        }
+    }
+
+    // 合并后的直方图用于计算分位数
+    let mut histograms: HashMap<Vec<Option<String>>, Vec<u64>> = HashMap::new();
+    {
+        let sql = format!(
+            "SELECT {}latency_bucket, SUM(count) FROM usage_latency_histogram WHERE {} GROUP BY {}latency_bucket",
+            select_prefix, where_clause, select_prefix
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let mapped = stmt
+            .query_map(rusqlite::params_from_iter(args.iter()), |row| {
+                let key = read_key(row, &group_columns)?;
+                let latency_bucket: i64 = row.get(key_len)?;
+                let count: i64 = row.get(key_len + 1)?;
+                Ok((key, latency_bucket as usize, count.max(0) as u64))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in mapped {
+            let (key, latency_bucket, count) = row.map_err(|e| e.to_string())?;
+            let histogram = histograms
+                .entry(key)
+                .or_insert_with(|| vec![0; LATENCY_BOUNDS_MS.len()]);
+            if let Some(slot) = histogram.get_mut(latency_bucket) {
+                *slot += count;
+            }
+        }
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|(key, mut usage)| {
+            if let Some(histogram) = histograms.get(&key) {
+                usage.p50_latency_ms = percentile(histogram, 0.50, usage.avg_latency_ms);
+                usage.p95_latency_ms = percentile(histogram, 0.95, usage.avg_latency_ms);
+            }
+            usage
+        })
+        .collect())
+}
+
+/// 分组键统一读取为字符串 (bucket 转为十进制字符串)
+fn read_key(row: &rusqlite::Row<'_>, columns: &[&str]) -> rusqlite::Result<Vec<Option<String>>> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            if *column == "bucket" {
+                row.get::<_, i64>(i).map(|v| Some(v.to_string()))
+            } else {
+                row.get::<_, String>(i).map(Some)
+            }
+        })
+        .collect()
+}
+
+fn apply_key(usage: &mut UsageRow, columns: &[&str], key: &[Option<String>]) {
+    for (column, value) in columns.iter().zip(key) {
+        // 空字符串表示该维度缺失 (如 z.ai 请求没有账号)
+        let value = value.clone().filter(|v| !v.is_empty());
+        match *column {
+            "bucket" => usage.bucket = value.and_then(|v| v.parse().ok()),
+            "model" => usage.model = value,
+            "account" => usage.account = value,
+            "api_key" => usage.api_key = value,
+            _ => {}
+        }
+    }
+}
+
+/// 从直方图估算分位数；落在最后一个开放分桶时用平均值兜底
+fn percentile(histogram: &[u64], p: f64, fallback: u64) -> u64 {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    let target = ((total as f64) * p).ceil().max(1.0) as u64;
+    let mut cumulative = 0;
+    for (i, count) in histogram.iter().enumerate() {
+        cumulative += count;
+        if cumulative >= target {
+            let bound = LATENCY_BOUNDS_MS[i];
+            return if bound == u64::MAX { fallback } else { bound };
+        }
+    }
+    fallback
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE request_logs (
+                id TEXT PRIMARY KEY, timestamp INTEGER, status INTEGER, duration INTEGER, model TEXT,
+                input_tokens INTEGER, output_tokens INTEGER, account_email TEXT, api_key TEXT
+            )",
+        )
+        .unwrap();
+        init_tables(&conn).unwrap();
+        conn
+    }
+
+    fn log(timestamp: i64, model: &str, account: &str, status: u16, duration: u64) -> ProxyRequestLog {
+        ProxyRequestLog {
+            timestamp,
+            status,
+            duration,
+            model: Some(model.to_string()),
+            account_email: Some(account.to_string()),
+            input_tokens: Some(10),
+            output_tokens: Some(5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rollups_group_by_model_and_hour() {
+        let conn = setup();
+        let base = 1_700_000_000_000 - 1_700_000_000_000 % HOUR_MS;
+        for i in 0..10 {
+            record(&conn, &log(base + i, "claude", "a@x.com", 200, 400)).unwrap();
+        }
+        record(&conn, &log(base + 1, "claude", "b@x.com", 500, 8_000)).unwrap();
+        record(&conn, &log(base + HOUR_MS, "gemini", "a@x.com", 200, 50)).unwrap();
+
+        let rows = query(
+            &conn,
+            &UsageQuery {
+                group_by: vec![UsageDimension::Model],
+                ..Default::default()
+            },
+            true,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        let claude = &rows[0];
+        assert_eq!(claude.bucket, Some(base));
+        assert_eq!(claude.model.as_deref(), Some("claude"));
+        assert_eq!(claude.account, None);
+        assert_eq!(claude.request_count, 11);
+        assert_eq!(claude.error_count, 1);
+        assert_eq!(claude.input_tokens, 110);
+        assert_eq!(claude.p50_latency_ms, 500);
+        assert_eq!(claude.p95_latency_ms, 10_000);
+        assert_eq!(rows[1].bucket, Some(base + HOUR_MS));
+
+        // 按账号汇总整个时间范围 (日粒度)
+        let rows = query(
+            &conn,
+            &UsageQuery {
+                granularity: UsageGranularity::Day,
+                group_by: vec![UsageDimension::Account],
+                ..Default::default()
+            },
+            false,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].account.as_deref(), Some("a@x.com"));
+        assert_eq!(rows[0].request_count, 11);
+        assert_eq!(rows[0].bucket, None);
+    }
+
+    #[test]
+    fn test_percentile_open_bucket_falls_back() {
+        let mut histogram = vec![0; LATENCY_BOUNDS_MS.len()];
+        histogram[LATENCY_BOUNDS_MS.len() - 1] = 3;
+        assert_eq!(percentile(&histogram, 0.5, 400_000), 400_000);
+    }
+}
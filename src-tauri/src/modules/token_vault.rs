@@ -0,0 +1,535 @@
+// 账号令牌静态加密 (可选)
+// - 账号文件中的 token 字段加密为 {"encrypted": <Envelope>}，其余字段 (邮箱/配额/禁用状态) 保持明文
+// - 密钥来源: 用户口令 (PBKDF2-HMAC-SHA256) 或本地密钥文件 (SHA-256(salt || 文件内容))
+// - 算法: AES-256-GCM，附加数据 (AAD) 为账号 ID，防止密文在账号文件之间互换
+// - 令牌库元数据保存在数据目录的 token_vault.json，存在即表示已启用加密
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use once_cell::sync::Lazy;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::modules::account::{get_accounts_dir, get_data_dir};
+
+pub const VAULT_FILE: &str = "token_vault.json";
+/// 启动时自动解锁使用的口令环境变量
+pub const PASSPHRASE_ENV: &str = "ANTIGRAVITY_VAULT_PASSPHRASE";
+const PBKDF2_ITERATIONS: u32 = 600_000;
+/// 从文件读取的迭代次数允许范围：过低削弱口令保护，过高会让解锁长时间占满 CPU
+const MIN_PBKDF2_ITERATIONS: u32 = 100_000;
+const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const ALGORITHM: &str = "AES-256-GCM";
+/// 用于校验密钥是否正确的固定明文
+const CHECK_PLAINTEXT: &[u8] = b"antigravity-token-vault";
+const BUNDLE_FORMAT: &str = "antigravity-accounts-bundle";
+
+type Key = [u8; KEY_LEN];
+
+/// 密钥来源
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Passphrase,
+    KeyFile,
+}
+
+/// 密文信封
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Envelope {
+    pub alg: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// 令牌库元数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultMeta {
+    version: u32,
+    source: KeySource,
+    salt: String,
+    iterations: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_file: Option<String>,
+    check: Envelope,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+    pub source: Option<KeySource>,
+    pub key_file: Option<String>,
+}
+
+#[derive(Default)]
+struct VaultState {
+    meta: Option<VaultMeta>,
+    key: Option<Key>,
+}
+
+static VAULT: Lazy<RwLock<VaultState>> = Lazy::new(|| {
+    let meta = get_data_dir()
+        .ok()
+        .and_then(|dir| std::fs::read_to_string(dir.join(VAULT_FILE)).ok())
+        .and_then(|content| match serde_json::from_str::<VaultMeta>(&content) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                tracing::error!("[TokenVault] 解析令牌库元数据失败: {}", e);
+                None
+            }
+        });
+    RwLock::new(VaultState { meta, key: None })
+});
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut buf = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut buf)
+        .map_err(|_| "生成随机数失败".to_string())?;
+    Ok(buf)
+}
+
+fn decode_b64(value: &str) -> Result<Vec<u8>, String> {
+    BASE64.decode(value).map_err(|e| format!("密文格式错误: {}", e))
+}
+
+fn check_iterations(iterations: u32) -> Result<(), String> {
+    if (MIN_PBKDF2_ITERATIONS..=MAX_PBKDF2_ITERATIONS).contains(&iterations) {
+        Ok(())
+    } else {
+        Err(format!(
+            "不支持的 PBKDF2 迭代次数 {} (允许 {}-{})",
+            iterations, MIN_PBKDF2_ITERATIONS, MAX_PBKDF2_ITERATIONS
+        ))
+    }
+}
+
+/// 由口令派生密钥
+pub fn derive_passphrase_key(passphrase: &str, salt: &[u8], iterations: u32) -> Key {
+    let mut key = [0u8; KEY_LEN];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(iterations.max(1)).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    key
+}
+
+/// 由密钥文件派生密钥 (文件至少包含 32 字节)
+pub fn derive_file_key(path: &Path, salt: &[u8]) -> Result<Key, String> {
+    let content = std::fs::read(path).map_err(|e| format!("读取密钥文件失败 {:?}: {}", path, e))?;
+    if content.len() < KEY_LEN {
+        return Err(format!("密钥文件过短 (至少 {} 字节): {:?}", KEY_LEN, path));
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(&content);
+    Ok(hasher.finalize().into())
+}
+
+/// 生成随机密钥文件 (仅当前用户可读)
+fn create_key_file(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建密钥目录失败: {}", e))?;
+    }
+    let key = random_bytes::<KEY_LEN>()?;
+    crate::utils::fs::write_private_file(path, BASE64.encode(key).as_bytes())
+        .map_err(|e| format!("写入密钥文件失败: {}", e))
+}
+
+pub fn seal(key: &Key, aad: &[u8], plaintext: &[u8]) -> Result<Envelope, String> {
+    let sealing = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "无效的密钥")?);
+    let nonce = random_bytes::<NONCE_LEN>()?;
+    let mut buffer = plaintext.to_vec();
+    sealing
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut buffer)
+        .map_err(|_| "加密失败".to_string())?;
+    Ok(Envelope {
+        alg: ALGORITHM.to_string(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(buffer),
+    })
+}
+
+pub fn open(key: &Key, aad: &[u8], envelope: &Envelope) -> Result<Vec<u8>, String> {
+    if envelope.alg != ALGORITHM {
+        return Err(format!("不支持的加密算法: {}", envelope.alg));
+    }
+    let opening = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "无效的密钥")?);
+    let nonce: [u8; NONCE_LEN] = decode_b64(&envelope.nonce)?
+        .try_into()
+        .map_err(|_| "密文格式错误: nonce 长度无效".to_string())?;
+    let mut buffer = decode_b64(&envelope.ciphertext)?;
+    let plaintext = opening
+        .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut buffer)
+        .map_err(|_| "解密失败 (密钥错误或数据已损坏)".to_string())?;
+    Ok(plaintext.to_vec())
+}
+
+/// 账号 JSON 中的 token 字段是否为密文
+pub fn is_encrypted(account: &Value) -> bool {
+    account["token"].get("encrypted").is_some()
+}
+
+fn account_aad(account: &Value) -> Vec<u8> {
+    account["id"].as_str().unwrap_or_default().as_bytes().to_vec()
+}
+
+/// 使用指定密钥解密账号 JSON 的 token 字段 (明文时不做处理)
+pub fn decrypt_account_with(account: &mut Value, key: Option<&Key>) -> Result<(), String> {
+    if !is_encrypted(account) {
+        return Ok(());
+    }
+    let key = key.ok_or("令牌库已锁定，请先解锁")?;
+    let envelope: Envelope = serde_json::from_value(account["token"]["encrypted"].clone())
+        .map_err(|e| format!("密文格式错误: {}", e))?;
+    let plaintext = open(key, &account_aad(account), &envelope)?;
+    account["token"] = serde_json::from_slice(&plaintext).map_err(|e| format!("解析令牌失败: {}", e))?;
+    Ok(())
+}
+
+/// 使用指定密钥加密账号 JSON 的 token 字段 (已加密时不做处理)
+pub fn encrypt_account_with(account: &mut Value, key: &Key) -> Result<(), String> {
+    if is_encrypted(account) || account.get("token").is_none() {
+        return Ok(());
+    }
+    let plaintext = serde_json::to_vec(&account["token"]).map_err(|e| e.to_string())?;
+    let envelope = seal(key, &account_aad(account), &plaintext)?;
+    account["token"] = serde_json::json!({ "encrypted": envelope });
+    Ok(())
+}
+
+/// 读取账号文件时调用: 解密 token 字段
+pub fn decrypt_account(account: &mut Value) -> Result<(), String> {
+    let key = VAULT.read().unwrap().key;
+    decrypt_account_with(account, key.as_ref())
+}
+
+/// 写入账号文件前调用: 启用加密时加密 token 字段，令牌库锁定时拒绝写入明文
+pub fn encrypt_account(account: &mut Value) -> Result<(), String> {
+    let state = VAULT.read().unwrap();
+    if state.meta.is_none() {
+        return Ok(());
+    }
+    let key = state.key.ok_or("令牌库已锁定，无法保存账号令牌")?;
+    drop(state);
+    encrypt_account_with(account, &key)
+}
+
+pub fn status() -> VaultStatus {
+    let state = VAULT.read().unwrap();
+    VaultStatus {
+        enabled: state.meta.is_some(),
+        unlocked: state.key.is_some(),
+        source: state.meta.as_ref().map(|m| m.source),
+        key_file: state.meta.as_ref().and_then(|m| m.key_file.clone()),
+    }
+}
+
+fn derive_key(meta: &VaultMeta, passphrase: Option<&str>) -> Result<Key, String> {
+    let salt = decode_b64(&meta.salt)?;
+    let key = match meta.source {
+        KeySource::Passphrase => {
+            check_iterations(meta.iterations)?;
+            derive_passphrase_key(passphrase.ok_or("请输入口令")?, &salt, meta.iterations)
+        }
+        KeySource::KeyFile => {
+            let path = meta.key_file.as_deref().ok_or("令牌库未配置密钥文件")?;
+            derive_file_key(Path::new(path), &salt)?
+        }
+    };
+    open(&key, b"check", &meta.check).map_err(|_| match meta.source {
+        KeySource::Passphrase => "口令错误".to_string(),
+        KeySource::KeyFile => "密钥文件与令牌库不匹配".to_string(),
+    })?;
+    Ok(key)
+}
+
+/// 解锁令牌库 (口令模式需提供口令，密钥文件模式读取配置的文件)
+pub fn unlock(passphrase: Option<&str>) -> Result<VaultStatus, String> {
+    let meta = VAULT.read().unwrap().meta.clone().ok_or("未启用令牌加密")?;
+    let key = derive_key(&meta, passphrase)?;
+    VAULT.write().unwrap().key = Some(key);
+    tracing::info!("[TokenVault] 令牌库已解锁");
+    Ok(status())
+}
+
+pub fn lock() {
+    VAULT.write().unwrap().key = None;
+    tracing::info!("[TokenVault] 令牌库已锁定");
+}
+
+/// 启动时自动解锁: 密钥文件模式直接解锁，口令模式读取 ANTIGRAVITY_VAULT_PASSPHRASE
+pub fn try_auto_unlock() -> VaultStatus {
+    let current = status();
+    if !current.enabled || current.unlocked {
+        return current;
+    }
+    let passphrase = std::env::var(PASSPHRASE_ENV).ok();
+    if current.source == Some(KeySource::Passphrase) && passphrase.is_none() {
+        tracing::warn!("[TokenVault] 令牌库已加密且处于锁定状态，请在界面中输入口令解锁");
+        return current;
+    }
+    match unlock(passphrase.as_deref()) {
+        Ok(status) => {
+            if let Err(e) = migrate_accounts() {
+                tracing::warn!("[TokenVault] 迁移明文账号失败: {}", e);
+            }
+            status
+        }
+        Err(e) => {
+            tracing::error!("[TokenVault] 自动解锁失败: {}", e);
+            current
+        }
+    }
+}
+
+/// 启用令牌加密并迁移现有账号文件
+/// 密钥文件模式下 key_file 不存在时自动生成 (默认位于数据目录)
+pub fn enable(
+    source: KeySource,
+    passphrase: Option<String>,
+    key_file: Option<String>,
+) -> Result<VaultStatus, String> {
+    if VAULT.read().unwrap().meta.is_some() {
+        return Err("令牌加密已启用".to_string());
+    }
+    let data_dir = get_data_dir()?;
+    let salt = random_bytes::<SALT_LEN>()?;
+    let (key, key_file) = match source {
+        KeySource::Passphrase => {
+            let passphrase = passphrase.filter(|p| !p.is_empty()).ok_or("请输入口令")?;
+            (derive_passphrase_key(&passphrase, &salt, PBKDF2_ITERATIONS), None)
+        }
+        KeySource::KeyFile => {
+            let path = key_file
+                .filter(|p| !p.trim().is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| data_dir.join("token_vault.key"));
+            if !path.exists() {
+                create_key_file(&path)?;
+                tracing::info!("[TokenVault] 已生成密钥文件: {:?}", path);
+            }
+            (derive_file_key(&path, &salt)?, Some(path.to_string_lossy().to_string()))
+        }
+    };
+    let meta = VaultMeta {
+        version: 1,
+        source,
+        salt: BASE64.encode(salt),
+        iterations: PBKDF2_ITERATIONS,
+        key_file,
+        check: seal(&key, b"check", CHECK_PLAINTEXT)?,
+    };
+    let content = serde_json::to_string_pretty(&meta).map_err(|e| e.to_string())?;
+    std::fs::write(data_dir.join(VAULT_FILE), content).map_err(|e| format!("写入令牌库元数据失败: {}", e))?;
+
+    {
+        let mut state = VAULT.write().unwrap();
+        state.meta = Some(meta);
+        state.key = Some(key);
+    }
+    let migrated = migrate_accounts()?;
+    tracing::info!("[TokenVault] 已启用令牌加密，迁移 {} 个账号", migrated);
+    Ok(status())
+}
+
+/// 关闭令牌加密: 解密全部账号文件并删除令牌库元数据 (需已解锁)
+pub fn disable() -> Result<VaultStatus, String> {
+    let key = {
+        let state = VAULT.read().unwrap();
+        if state.meta.is_none() {
+            return Err("未启用令牌加密".to_string());
+        }
+        state.key.ok_or("令牌库已锁定，请先解锁")?
+    };
+    rewrite_accounts(|account| {
+        if !is_encrypted(account) {
+            return Ok(false);
+        }
+        decrypt_account_with(account, Some(&key))?;
+        Ok(true)
+    })?;
+    std::fs::remove_file(get_data_dir()?.join(VAULT_FILE)).map_err(|e| format!("删除令牌库元数据失败: {}", e))?;
+    *VAULT.write().unwrap() = VaultState::default();
+    tracing::info!("[TokenVault] 已关闭令牌加密");
+    Ok(status())
+}
+
+/// 加密仍为明文的账号文件 (令牌库已启用且已解锁时)，返回迁移数量
+pub fn migrate_accounts() -> Result<usize, String> {
+    let key = {
+        let state = VAULT.read().unwrap();
+        match (&state.meta, state.key) {
+            (Some(_), Some(key)) => key,
+            _ => return Ok(0),
+        }
+    };
+    rewrite_accounts(|account| {
+        if is_encrypted(account) {
+            return Ok(false);
+        }
+        encrypt_account_with(account, &key)?;
+        Ok(true)
+    })
+}
+
+/// 逐个改写账号文件 (临时文件 + 原子重命名)，返回改写数量
+fn rewrite_accounts(mut transform: impl FnMut(&mut Value) -> Result<bool, String>) -> Result<usize, String> {
+    let dir = get_accounts_dir()?;
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("读取账号目录失败: {}", e))?;
+    let mut count = 0;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|s| s.to_str()) != Some("json") {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("读取账号文件失败: {}", e))?;
+        let mut account: Value = match serde_json::from_str(&content) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!("[TokenVault] 跳过无法解析的账号文件 {:?}: {}", path, e);
+                continue;
+            }
+        };
+        if !transform(&mut account)? {
+            continue;
+        }
+        let temp = path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(&account).map_err(|e| e.to_string())?;
+        std::fs::write(&temp, content).map_err(|e| format!("写入账号文件失败: {}", e))?;
+        std::fs::rename(&temp, &path).map_err(|e| format!("替换账号文件失败: {}", e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// 加密导出包
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountBundle {
+    pub format: String,
+    pub version: u32,
+    pub created_at: i64,
+    pub count: usize,
+    pub salt: String,
+    pub iterations: u32,
+    pub payload: Envelope,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleAccount {
+    pub email: String,
+    pub refresh_token: String,
+}
+
+/// 使用导出口令加密账号列表 (与令牌库密钥无关)
+pub fn seal_bundle(accounts: &[BundleAccount], passphrase: &str) -> Result<AccountBundle, String> {
+    seal_bundle_with(accounts, passphrase, PBKDF2_ITERATIONS)
+}
+
+fn seal_bundle_with(accounts: &[BundleAccount], passphrase: &str, iterations: u32) -> Result<AccountBundle, String> {
+    if passphrase.is_empty() {
+        return Err("请输入导出口令".to_string());
+    }
+    let salt = random_bytes::<SALT_LEN>()?;
+    let key = derive_passphrase_key(passphrase, &salt, iterations);
+    let plaintext = serde_json::to_vec(accounts).map_err(|e| e.to_string())?;
+    Ok(AccountBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: 1,
+        created_at: chrono::Utc::now().timestamp(),
+        count: accounts.len(),
+        salt: BASE64.encode(salt),
+        iterations,
+        payload: seal(&key, BUNDLE_FORMAT.as_bytes(), &plaintext)?,
+    })
+}
+
+pub fn open_bundle(bundle: &AccountBundle, passphrase: &str) -> Result<Vec<BundleAccount>, String> {
+    if bundle.format != BUNDLE_FORMAT {
+        return Err("不是有效的账号导出包".to_string());
+    }
+    check_iterations(bundle.iterations)?;
+    let key = derive_passphrase_key(passphrase, &decode_b64(&bundle.salt)?, bundle.iterations);
+    let plaintext = open(&key, BUNDLE_FORMAT.as_bytes(), &bundle.payload).map_err(|_| "导出口令错误或文件已损坏".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("解析导出包失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> Value {
+        serde_json::json!({
+            "id": "acc-1",
+            "email": "a@example.com",
+            "token": { "access_token": "at", "refresh_token": "rt", "expires_in": 3600 }
+        })
+    }
+
+    #[test]
+    fn test_account_token_round_trip() {
+        let key = derive_passphrase_key("correct horse", b"0123456789abcdef", 1000);
+        let mut value = account();
+        encrypt_account_with(&mut value, &key).unwrap();
+        assert!(is_encrypted(&value));
+        assert!(!value.to_string().contains("\"rt\""));
+        assert_eq!(value["email"], "a@example.com");
+
+        // 锁定 / 错误密钥 / 密文移到其他账号时均无法解密
+        assert!(decrypt_account_with(&mut value.clone(), None).is_err());
+        let wrong = derive_passphrase_key("wrong", b"0123456789abcdef", 1000);
+        assert!(decrypt_account_with(&mut value.clone(), Some(&wrong)).is_err());
+        let mut moved = value.clone();
+        moved["id"] = Value::String("acc-2".to_string());
+        assert!(decrypt_account_with(&mut moved, Some(&key)).is_err());
+
+        decrypt_account_with(&mut value, Some(&key)).unwrap();
+        assert_eq!(value, account());
+    }
+
+    #[test]
+    fn test_key_file_derivation() {
+        let path = std::env::temp_dir().join(format!("vault-{}.key", uuid::Uuid::new_v4()));
+        create_key_file(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let a = derive_file_key(&path, b"salt-a").unwrap();
+        assert_eq!(a, derive_file_key(&path, b"salt-a").unwrap());
+        assert_ne!(a, derive_file_key(&path, b"salt-b").unwrap());
+        std::fs::write(&path, "short").unwrap();
+        assert!(derive_file_key(&path, b"salt-a").is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let accounts = vec![BundleAccount {
+            email: "a@example.com".to_string(),
+            refresh_token: "1//secret".to_string(),
+        }];
+        let bundle = seal_bundle_with(&accounts, "export-pass", MIN_PBKDF2_ITERATIONS).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(!json.contains("1//secret"));
+        let parsed: AccountBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(open_bundle(&parsed, "export-pass").unwrap()[0].refresh_token, "1//secret");
+        assert!(open_bundle(&parsed, "nope").is_err());
+
+        // 迭代次数来自文件，超出范围时直接拒绝而不是执行派生
+        for iterations in [1, MAX_PBKDF2_ITERATIONS + 1, u32::MAX] {
+            let tampered = AccountBundle { iterations, ..parsed.clone() };
+            assert!(open_bundle(&tampered, "export-pass").unwrap_err().contains("迭代次数"));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 代表整个账号池的特殊分组名
+pub const ALL_ACCOUNTS_GROUP: &str = "*";
+
+/// 账号分组路由配置
+/// 根据客户端 Key / 模型 / 请求类型将请求路由到带有对应标签的账号分组
+/// (客户端 Key 的分组在 `client_keys` 中随 Key 配置，仅对认证识别出的 Key 生效)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AccountGroupConfig {
+    /// 是否启用分组路由 (关闭时所有账号组成一个共享池)
+    #[serde(default)]
+    pub enabled: bool,
+    /// 模型名 -> 分组，支持 `*` 通配符 (如 `gemini-3-pro-image*`)
+    #[serde(default)]
+    pub model_groups: HashMap<String, String>,
+    /// 请求类型 (agent / web_search / image_gen) -> 分组
+    #[serde(default)]
+    pub request_type_groups: HashMap<String, String>,
+    /// 未命中任何规则时使用的分组，None 表示使用整个账号池
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_group: Option<String>,
+    /// 目标分组没有可用账号时回退的共享分组，None 表示不回退，`*` 表示整个账号池
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_group: Option<String>,
+}
+
+impl AccountGroupConfig {
+    /// 解析请求应路由到的分组
+    /// 优先级：客户端 Key 指定的分组 > 模型 > 请求类型 > 默认分组
+    pub fn resolve_group(&self, key_group: Option<&str>, model: &str, request_type: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+
+        if let Some(group) = key_group {
+            return Some(group.to_string());
+        }
+
+        if let Some(group) = self.match_model_group(model) {
+            return Some(group);
+        }
+
+        if let Some(group) = self.request_type_groups.get(request_type) {
+            return Some(group.clone());
+        }
+
+        self.default_group.clone()
+    }
+
+    /// 精确匹配优先；通配符规则中取最长 (最具体) 的模式，保证结果稳定
+    fn match_model_group(&self, model: &str) -> Option<String> {
+        if let Some(group) = self.model_groups.get(model) {
+            return Some(group.clone());
+        }
+
+        self.model_groups
+            .iter()
+            .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, group)| group.clone())
+    }
+}
+
+/// 简单的 `*` 通配符匹配 (不区分大小写)
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.to_lowercase();
+    let text = text.to_lowercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let mut rest = text.as_str();
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            match rest.strip_prefix(part) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+/// 判断账号标签是否属于指定分组
+pub fn account_in_group(account_groups: &[String], group: &str) -> bool {
+    group == ALL_ACCOUNTS_GROUP || account_groups.iter().any(|g| g == group)
+}
+
+/// 规范化用户输入的分组列表：去除空白、去重并保持原有顺序
+pub fn normalize_groups(groups: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for g in groups {
+        let g = g.trim().to_string();
+        if !g.is_empty() && g != ALL_ACCOUNTS_GROUP && !out.contains(&g) {
+            out.push(g);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AccountGroupConfig {
+        let mut c = AccountGroupConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        c.model_groups.insert("gemini-3-pro-image*".to_string(), "image-gen".to_string());
+        c.model_groups.insert("claude-opus-*".to_string(), "ultra-only".to_string());
+        c.model_groups.insert("claude-*".to_string(), "claude".to_string());
+        c.request_type_groups.insert("web_search".to_string(), "search".to_string());
+        c
+    }
+
+    #[test]
+    fn test_disabled_config_routes_to_whole_pool() {
+        let mut c = config();
+        c.enabled = false;
+        assert_eq!(c.resolve_group(Some("team-a"), "claude-opus-4-5", "agent"), None);
+    }
+
+    #[test]
+    fn test_key_group_takes_priority() {
+        let c = config();
+        assert_eq!(
+            c.resolve_group(Some("team-a"), "claude-opus-4-5", "web_search"),
+            Some("team-a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_model_takes_priority_over_request_type() {
+        let c = config();
+        assert_eq!(
+            c.resolve_group(None, "gemini-3-pro-image", "web_search"),
+            Some("image-gen".to_string())
+        );
+    }
+
+    #[test]
+    fn test_most_specific_model_pattern_wins() {
+        let c = config();
+        assert_eq!(
+            c.resolve_group(None, "claude-opus-4-5-thinking", "agent"),
+            Some("ultra-only".to_string())
+        );
+        assert_eq!(
+            c.resolve_group(None, "claude-sonnet-4-5", "agent"),
+            Some("claude".to_string())
+        );
+    }
+
+    #[test]
+    fn test_request_type_and_default_group() {
+        let mut c = config();
+        assert_eq!(
+            c.resolve_group(None, "gemini-2.5-flash", "web_search"),
+            Some("search".to_string())
+        );
+        assert_eq!(c.resolve_group(None, "gemini-2.5-flash", "agent"), None);
+        c.default_group = Some("shared".to_string());
+        assert_eq!(
+            c.resolve_group(None, "gemini-2.5-flash", "agent"),
+            Some("shared".to_string())
+        );
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("gemini-*-flash", "gemini-2.5-flash"));
+        assert!(wildcard_match("*-thinking", "claude-opus-4-5-thinking"));
+        assert!(wildcard_match("*", "anything"));
+        assert!(!wildcard_match("gemini-*-flash", "gemini-2.5-pro"));
+        assert!(wildcard_match("GPT-4*", "gpt-4o"));
+    }
+
+    #[test]
+    fn test_normalize_groups() {
+        let groups = vec![
+            " team-a ".to_string(),
+            "team-a".to_string(),
+            "".to_string(),
+            "*".to_string(),
+            "image-gen".to_string(),
+        ];
+        assert_eq!(normalize_groups(groups), vec!["team-a", "image-gen"]);
+    }
+}
@@ -0,0 +1,128 @@
+// 账号目录热加载
+// 监听 accounts/ 目录的文件变化，增量同步到 TokenManager，
+// 无需手动调用 reload_proxy_accounts，也不会打断进行中的请求、会话绑定与限流状态
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::proxy::TokenManager;
+
+/// 防抖时间：保存账号时可能连续触发多次写入事件，合并后再处理
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 账号目录监听器，drop 时自动停止监听
+pub struct AccountWatcher {
+    _watcher: RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl AccountWatcher {
+    /// 开始监听账号目录
+    pub fn start(token_manager: Arc<TokenManager>) -> Result<Self, String> {
+        let accounts_dir = token_manager.accounts_dir();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<PathBuf>();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) => {
+                    if matches!(event.kind, EventKind::Access(_)) {
+                        return;
+                    }
+                    for path in event.paths.into_iter().filter(|p| is_account_file(p)) {
+                        let _ = tx.send(path);
+                    }
+                }
+                Err(e) => tracing::warn!("Account watcher error: {}", e),
+            }
+        })
+        .map_err(|e| format!("创建账号目录监听失败: {}", e))?;
+
+        watcher
+            .watch(&accounts_dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("监听账号目录失败: {}", e))?;
+
+        let task = tokio::spawn(async move {
+            while let Some(first) = rx.recv().await {
+                let mut pending = HashSet::from([first]);
+                tokio::time::sleep(DEBOUNCE).await;
+                while let Ok(path) = rx.try_recv() {
+                    pending.insert(path);
+                }
+                for path in pending {
+                    token_manager.sync_account_file(&path).await;
+                }
+            }
+        });
+
+        tracing::info!("Watching account directory for changes: {:?}", accounts_dir);
+        Ok(Self {
+            _watcher: watcher,
+            task,
+        })
+    }
+}
+
+impl Drop for AccountWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 只关心账号 JSON 文件 (忽略编辑器临时文件等)
+fn is_account_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_account(dir: &Path, id: &str, proxy_disabled: bool) -> PathBuf {
+        let path = dir.join(format!("{}.json", id));
+        let account = serde_json::json!({
+            "id": id,
+            "email": format!("{}@example.com", id),
+            "proxy_disabled": proxy_disabled,
+            "token": {
+                "access_token": "at",
+                "refresh_token": "rt",
+                "expires_in": 3600,
+                "expiry_timestamp": chrono::Utc::now().timestamp() + 3600,
+                "project_id": "p"
+            }
+        });
+        std::fs::write(&path, account.to_string()).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_sync_account_file_add_update_remove() {
+        let data_dir = std::env::temp_dir().join(format!("account-watcher-{}", uuid::Uuid::new_v4()));
+        let accounts_dir = data_dir.join("accounts");
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let manager = TokenManager::new(data_dir.clone());
+        write_account(&accounts_dir, "a", false);
+        assert_eq!(manager.load_accounts().await.unwrap(), 1);
+
+        // 新增
+        let b = write_account(&accounts_dir, "b", false);
+        manager.sync_account_file(&b).await;
+        assert_eq!(manager.len(), 2);
+
+        // 禁用反代后移除
+        write_account(&accounts_dir, "b", true);
+        manager.sync_account_file(&b).await;
+        assert_eq!(manager.len(), 1);
+
+        // 文件删除后移除
+        let a = accounts_dir.join("a.json");
+        std::fs::remove_file(&a).unwrap();
+        manager.sync_account_file(&a).await;
+        assert_eq!(manager.len(), 0);
+
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+}
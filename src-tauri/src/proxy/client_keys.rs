@@ -0,0 +1,114 @@
+// 命名的客户端 API Key
+// 主 API Key (`api_key`) 之外可为每位成员 / 每个用途分配独立的 Key；
+// 认证时识别出请求所用的 Key，之后按 Key 名称路由账号分组、计量限流与预算、统计用量
+use serde::{Deserialize, Serialize};
+
+/// 主 API Key 的名称 (限额配置与用量统计中使用)
+pub const PRIMARY_KEY_NAME: &str = "default";
+
+/// 客户端 API Key
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct ClientApiKey {
+    /// 名称 (唯一)，用于限额配置与用量统计，不含敏感信息
+    pub name: String,
+    pub key: String,
+    /// 使用该 Key 的请求路由到的账号分组 (优先于模型 / 请求类型规则)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// 识别出的客户端 Key，由认证中间件写入请求扩展 (未携带已配置的 Key 时不存在)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedKey {
+    pub name: String,
+    pub group: Option<String>,
+}
+
+/// 按请求携带的 Key 查找对应的客户端 (主 API Key 优先)
+pub fn identify(primary_key: &str, keys: &[ClientApiKey], presented: &str) -> Option<AuthenticatedKey> {
+    if presented.is_empty() {
+        return None;
+    }
+    if presented == primary_key {
+        return Some(AuthenticatedKey {
+            name: PRIMARY_KEY_NAME.to_string(),
+            group: None,
+        });
+    }
+    keys.iter().find(|k| k.key == presented).map(|k| AuthenticatedKey {
+        name: k.name.clone(),
+        group: k.group.clone().filter(|g| !g.trim().is_empty()),
+    })
+}
+
+/// 保存配置前校验: 名称与 Key 均不能为空或重复，也不能与主 API Key 冲突
+pub fn validate(primary_key: &str, keys: &[ClientApiKey]) -> Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    let mut secrets = std::collections::HashSet::new();
+    for k in keys {
+        let name = k.name.trim();
+        if name.is_empty() {
+            return Err("客户端 Key 名称不能为空".to_string());
+        }
+        if name != k.name {
+            return Err(format!("客户端 Key 名称首尾不能包含空白: {:?}", k.name));
+        }
+        if name == PRIMARY_KEY_NAME {
+            return Err(format!("客户端 Key 名称 `{}` 保留给主 API Key", PRIMARY_KEY_NAME));
+        }
+        if !names.insert(name) {
+            return Err(format!("客户端 Key 名称重复: {}", name));
+        }
+        if k.key.is_empty() {
+            return Err(format!("客户端 Key `{}` 的密钥为空", name));
+        }
+        if k.key == primary_key || !secrets.insert(k.key.as_str()) {
+            return Err(format!("客户端 Key `{}` 与其它 Key 重复", name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, key: &str, group: Option<&str>) -> ClientApiKey {
+        ClientApiKey {
+            name: name.to_string(),
+            key: key.to_string(),
+            group: group.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_identify() {
+        let keys = vec![key("alice", "sk-alice", Some("team-a")), key("bob", "sk-bob", Some(" "))];
+        assert_eq!(identify("sk-main", &keys, "sk-main").unwrap().name, PRIMARY_KEY_NAME);
+        assert_eq!(
+            identify("sk-main", &keys, "sk-alice"),
+            Some(AuthenticatedKey { name: "alice".to_string(), group: Some("team-a".to_string()) })
+        );
+        assert_eq!(identify("sk-main", &keys, "sk-bob").unwrap().group, None);
+        assert_eq!(identify("sk-main", &keys, "sk-unknown"), None);
+        // 主 API Key 为空时空字符串不能匹配
+        assert_eq!(identify("", &keys, ""), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("sk-main", &[key("alice", "sk-alice", None), key("bob", "sk-bob", None)]).is_ok());
+        for keys in [
+            vec![key("", "sk-alice", None)],
+            vec![key(" alice", "sk-alice", None)],
+            vec![key(PRIMARY_KEY_NAME, "sk-alice", None)],
+            vec![key("alice", "", None)],
+            vec![key("alice", "sk-main", None)],
+            vec![key("alice", "sk-alice", None), key("alice", "sk-bob", None)],
+            vec![key("alice", "sk-alice", None), key("bob", "sk-alice", None)],
+        ] {
+            assert!(validate("sk-main", &keys).is_err(), "{:?}", keys);
+        }
+    }
+}
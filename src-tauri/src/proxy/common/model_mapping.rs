@@ -74,10 +74,10 @@ pub fn get_supported_models() -> Vec<String> {
 }
 
 /// 动态获取所有可用模型列表 (包含内置与用户自定义)
-pub async fn get_all_dynamic_models(
-    openai_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
-    custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
-    anthropic_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
+pub fn get_all_dynamic_models(
+    openai_mapping: &std::collections::HashMap<String, String>,
+    custom_mapping: &std::collections::HashMap<String, String>,
+    anthropic_mapping: &std::collections::HashMap<String, String>,
 ) -> Vec<String> {
     use std::collections::HashSet;
     let mut model_ids = HashSet::new();
@@ -88,30 +88,21 @@ pub async fn get_all_dynamic_models(
     }
 
     // 2. 获取所有自定义映射模型 (OpenAI)
-    {
-        let mapping = openai_mapping.read().await;
-        for key in mapping.keys() {
-            if !key.ends_with("-series") {
-                 model_ids.insert(key.clone());
-            }
+    for key in openai_mapping.keys() {
+        if !key.ends_with("-series") {
+             model_ids.insert(key.clone());
         }
     }
 
     // 3. 获取所有自定义映射模型 (Custom)
-    {
-        let mapping = custom_mapping.read().await;
-        for key in mapping.keys() {
-            model_ids.insert(key.clone());
-        }
+    for key in custom_mapping.keys() {
+        model_ids.insert(key.clone());
     }
 
     // 4. 获取所有 Anthropic 映射模型
-    {
-        let mapping = anthropic_mapping.read().await;
-        for key in mapping.keys() {
-            if !key.ends_with("-series") && key != "claude-default" {
-                model_ids.insert(key.clone());
-            }
+    for key in anthropic_mapping.keys() {
+        if !key.ends_with("-series") && key != "claude-default" {
+            model_ids.insert(key.clone());
         }
     }
 
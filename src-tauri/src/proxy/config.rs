@@ -128,11 +128,28 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub allow_lan_access: bool,
 
+    /// 局域网访问时绑定的网卡地址 (如 "192.168.1.10")，为空时绑定 0.0.0.0
+    #[serde(default)]
+    pub bind_address: String,
+
+    /// 客户端 IP 允许 / 拒绝列表 (CIDR)，在认证之前检查
+    #[serde(default)]
+    pub ip_filter: crate::proxy::ip_filter::IpFilterConfig,
+
+    /// 监听 TLS (HTTPS / HTTP2)
+    #[serde(default)]
+    pub tls: crate::proxy::tls::TlsConfig,
+
+    /// Unix domain socket 监听 (与 TCP 同时或独占)
+    #[serde(default)]
+    pub unix_socket: crate::proxy::unix_socket::UnixSocketConfig,
+
     /// Authorization policy for the proxy.
     /// - off: no auth required
     /// - strict: auth required for all routes
     /// - all_except_health: auth required for all routes except `/healthz`
-    /// - auto: recommended defaults (currently: allow_lan_access => all_except_health, else off)
+    /// - auto: recommended defaults (off when only loopback can connect, i.e. the proxy listens on a
+    ///   loopback address or the IP allowlist admits nothing but loopback; otherwise all_except_health)
     #[serde(default)]
     pub auth_mode: ProxyAuthMode,
     
@@ -141,6 +158,10 @@ pub struct ProxyConfig {
     
     /// API 密钥
     pub api_key: String,
+
+    /// 主 API Key 之外的命名客户端 Key (按成员 / 用途区分，可指定账号分组)
+    #[serde(default)]
+    pub client_keys: Vec<crate::proxy::client_keys::ClientApiKey>,
     
 
     /// 是否自动启动
@@ -158,14 +179,40 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub custom_mapping: std::collections::HashMap<String, String>,
 
-    /// API 请求超时时间(秒)
+    /// API 请求超时时间(秒)，单次上游尝试的总时长 (含流式输出)，0 表示不限制
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
 
+    /// 上游连接 / 首字节 / 流式空闲超时 (支持按模型覆盖)
+    #[serde(default)]
+    pub timeouts: crate::proxy::upstream::timeouts::TimeoutConfig,
+
+    /// 流式响应的 SSE 保活间隔(秒)，0 表示关闭 (默认)。
+    /// 上游超过该间隔仍无首个分块时先返回响应头并发送心跳，此后的首字节超时不再轮换账号；
+    /// 需要保留轮换时应大于 timeouts.first_byte_secs
+    #[serde(default = "default_sse_keepalive")]
+    pub sse_keepalive_secs: u64,
+
+    /// 停止服务时等待进行中请求完成的最长时间(秒)，超时后取消剩余连接
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+
     /// 是否开启请求日志记录 (监控)
     #[serde(default)]
     pub enable_logging: bool,
 
+    /// 请求日志存储策略 (保留期限 / 报文截断 / 压缩)
+    #[serde(default)]
+    pub log_storage: LogStorageConfig,
+
+    /// 敏感信息脱敏 (作用于运行日志、请求日志与导出)
+    #[serde(default)]
+    pub redaction: crate::modules::redaction::RedactionConfig,
+
+    /// 是否开放 HTTP 管理接口 (/admin/*，始终要求 API Key 认证)
+    #[serde(default)]
+    pub enable_admin_api: bool,
+
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
@@ -177,10 +224,68 @@ pub struct ProxyConfig {
     /// 账号调度配置 (粘性会话/限流重试)
     #[serde(default)]
     pub scheduling: crate::proxy::sticky_config::StickySessionConfig,
+
+    /// 账号分组路由配置 (按客户端 Key / 模型 / 请求类型路由到账号分组)
+    #[serde(default)]
+    pub account_groups: crate::proxy::account_groups::AccountGroupConfig,
+
+    /// 事件通知配置 (Webhook / 桌面通知 / 本地事件日志)
+    #[serde(default)]
+    pub events: crate::proxy::events::EventsConfig,
+
+    /// 按 API Key / 客户端 IP 的限流与 token 预算
+    #[serde(default)]
+    pub key_limits: crate::proxy::key_limits::KeyLimitsConfig,
+}
+
+/// 请求日志报文压缩算法
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogCompression {
+    None,
+    Gzip,
+    #[default]
+    Zstd,
+}
+
+/// 请求日志存储策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogStorageConfig {
+    /// 日志最长保留天数 (0 表示不限制)
+    pub max_age_days: u32,
+    /// 最多保留的日志条数 (0 表示不限制)
+    pub max_rows: u64,
+    /// 数据库文件大小上限 (MB, 0 表示不限制)
+    pub max_db_size_mb: u64,
+    /// 请求报文最大保存长度 (KB, 0 表示不保存)
+    pub request_body_limit_kb: usize,
+    /// 响应报文最大保存长度 (KB, 0 表示不保存)
+    pub response_body_limit_kb: usize,
+    /// 将报文中的 base64 数据 (图片等) 替换为占位符
+    pub redact_base64: bool,
+    pub compression: LogCompression,
+    /// 清理与增量 VACUUM 的执行间隔 (分钟)
+    pub maintenance_interval_minutes: u64,
+}
+
+impl Default for LogStorageConfig {
+    fn default() -> Self {
+        Self {
+            max_age_days: 30,
+            max_rows: 100_000,
+            max_db_size_mb: 1024,
+            request_body_limit_kb: 256,
+            response_body_limit_kb: 256,
+            redact_base64: true,
+            compression: LogCompression::Zstd,
+            maintenance_interval_minutes: 60,
+        }
+    }
 }
 
 /// 上游代理配置
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct UpstreamProxyConfig {
     /// 是否启用
     pub enabled: bool,
@@ -193,18 +298,32 @@ impl Default for ProxyConfig {
         Self {
             enabled: false,
             allow_lan_access: false, // 默认仅本机访问，隐私优先
+            bind_address: String::new(),
+            ip_filter: crate::proxy::ip_filter::IpFilterConfig::default(),
+            tls: crate::proxy::tls::TlsConfig::default(),
+            unix_socket: crate::proxy::unix_socket::UnixSocketConfig::default(),
             auth_mode: ProxyAuthMode::default(),
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
+            client_keys: Vec::new(),
             auto_start: false,
             anthropic_mapping: std::collections::HashMap::new(),
             openai_mapping: std::collections::HashMap::new(),
             custom_mapping: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
+            timeouts: crate::proxy::upstream::timeouts::TimeoutConfig::default(),
+            sse_keepalive_secs: default_sse_keepalive(),
+            drain_timeout_secs: default_drain_timeout(),
             enable_logging: false, // 默认关闭，节省性能
+            log_storage: LogStorageConfig::default(),
+            redaction: crate::modules::redaction::RedactionConfig::default(),
+            enable_admin_api: false,
             upstream_proxy: UpstreamProxyConfig::default(),
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
+            account_groups: crate::proxy::account_groups::AccountGroupConfig::default(),
+            events: crate::proxy::events::EventsConfig::default(),
+            key_limits: crate::proxy::key_limits::KeyLimitsConfig::default(),
         }
     }
 }
@@ -213,6 +332,14 @@ fn default_request_timeout() -> u64 {
     120  // 默认 120 秒,原来 60 秒太短
 }
 
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+fn default_sse_keepalive() -> u64 {
+    0
+}
+
 fn default_zai_base_url() -> String {
     "https://api.z.ai/api/anthropic".to_string()
 }
@@ -232,12 +359,34 @@ fn default_zai_haiku_model() -> String {
 impl ProxyConfig {
     /// 获取实际的监听地址
     /// - allow_lan_access = false: 返回 "127.0.0.1"（默认，隐私优先）
-    /// - allow_lan_access = true: 返回 "0.0.0.0"（允许局域网访问）
+    /// - allow_lan_access = true: 返回 bind_address，未配置时返回 "0.0.0.0"（允许局域网访问）
     pub fn get_bind_address(&self) -> &str {
         if self.allow_lan_access {
-            "0.0.0.0"
+            match self.bind_address.trim() {
+                "" => "0.0.0.0",
+                addr => addr,
+            }
         } else {
             "127.0.0.1"
         }
     }
+
+    /// 是否监听 TCP 端口 (独占 Unix socket 时不监听)
+    pub fn tcp_enabled(&self) -> bool {
+        !(cfg!(unix) && self.unix_socket.enabled && self.unix_socket.exclusive)
+    }
+
+    pub fn sse_keepalive(&self) -> Option<std::time::Duration> {
+        (self.sse_keepalive_secs > 0).then(|| std::time::Duration::from_secs(self.sse_keepalive_secs))
+    }
+
+    /// 本机访问反代服务的地址 (绑定到指定网卡时使用该地址，启用 TLS 时为 https)
+    pub fn local_base_url(&self) -> String {
+        let scheme = if self.tls.enabled { "https" } else { "http" };
+        let host = match self.get_bind_address().parse::<std::net::IpAddr>() {
+            Ok(ip) if !ip.is_unspecified() && !ip.is_loopback() => ip,
+            _ => std::net::IpAddr::from([127, 0, 0, 1]),
+        };
+        format!("{}://{}", scheme, std::net::SocketAddr::new(host, self.port))
+    }
 }
@@ -0,0 +1,373 @@
+// 事件总线
+// 账号池健康相关的重要事件 (账号因 invalid_grant 被禁用 / 所有账号限流 / 配额低于阈值 / 上游端点切换)
+// 经节流后分发给可插拔的事件输出 (HTTP Webhook / Tauri 事件 / 本地 JSONL)
+mod sinks;
+
+pub use sinks::{EventSink, JsonlSink, TauriSink, WebhookSink};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// 本地事件日志文件名 (位于数据目录)
+const EVENTS_LOG_FILE: &str = "proxy_events.jsonl";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyEventKind {
+    /// 账号因 invalid_grant 等原因被禁用
+    AccountDisabled,
+    /// 所有账号均处于限流 / 不可用状态
+    AllAccountsLimited,
+    /// 账号某模型的剩余配额低于阈值
+    QuotaLow,
+    /// 上游端点失败，切换到下一个端点
+    UpstreamFailover,
+    /// 手动触发的测试事件
+    Test,
+}
+
+impl ProxyEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProxyEventKind::AccountDisabled => "account_disabled",
+            ProxyEventKind::AllAccountsLimited => "all_accounts_limited",
+            ProxyEventKind::QuotaLow => "quota_low",
+            ProxyEventKind::UpstreamFailover => "upstream_failover",
+            ProxyEventKind::Test => "test",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSeverity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyEvent {
+    pub id: String,
+    pub kind: ProxyEventKind,
+    pub severity: EventSeverity,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+    pub message: String,
+    pub data: Value,
+    /// 节流主体 (如账号 / 端点)，同一类型不同主体分别节流
+    #[serde(skip)]
+    subject: String,
+}
+
+impl ProxyEvent {
+    pub fn new(
+        kind: ProxyEventKind,
+        severity: EventSeverity,
+        subject: impl Into<String>,
+        message: impl Into<String>,
+        data: Value,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            severity,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            message: message.into(),
+            data,
+            subject: subject.into(),
+        }
+    }
+
+    fn throttle_key(&self) -> String {
+        format!("{}:{}", self.kind.as_str(), self.subject)
+    }
+}
+
+/// Webhook 输出配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub enabled: bool,
+    pub url: String,
+    /// HMAC-SHA256 签名密钥 (为空时不签名)
+    pub secret: String,
+    /// 订阅的事件类型 (为空表示全部)
+    pub events: Vec<ProxyEventKind>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            url: String::new(),
+            secret: String::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+/// 事件通知配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventsConfig {
+    pub enabled: bool,
+    /// 同一事件 (类型 + 主体) 的最小触发间隔 (秒)
+    pub throttle_seconds: u64,
+    /// 按事件类型覆盖节流间隔 (键为事件类型，如 "quota_low")
+    pub throttle_overrides: HashMap<String, u64>,
+    /// 剩余配额低于该百分比时触发 quota_low
+    pub quota_low_threshold: i32,
+    /// 通过 Tauri 事件通知前端 (桌面提示)
+    pub tauri_events: bool,
+    /// 追加写入数据目录下的 proxy_events.jsonl
+    pub jsonl_log: bool,
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            throttle_seconds: 300,
+            throttle_overrides: HashMap::new(),
+            quota_low_threshold: 10,
+            tauri_events: true,
+            jsonl_log: false,
+            webhooks: Vec::new(),
+        }
+    }
+}
+
+/// 测试事件在单个输出上的投递结果
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkDelivery {
+    pub sink: String,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+pub struct EventBus {
+    config: RwLock<EventsConfig>,
+    sinks: RwLock<Vec<Arc<dyn EventSink>>>,
+    app_handle: RwLock<Option<tauri::AppHandle>>,
+    /// 节流键 -> 上次触发时间 (ms)
+    last_fired: DashMap<String, i64>,
+}
+
+static BUS: Lazy<EventBus> = Lazy::new(EventBus::new);
+
+/// 全局事件总线
+pub fn bus() -> &'static EventBus {
+    &BUS
+}
+
+/// 发布事件 (经节流后异步投递，不阻塞调用方)
+pub fn emit(event: ProxyEvent) {
+    bus().emit(event);
+}
+
+impl EventBus {
+    fn new() -> Self {
+        Self {
+            config: RwLock::new(EventsConfig::default()),
+            sinks: RwLock::new(Vec::new()),
+            app_handle: RwLock::new(None),
+            last_fired: DashMap::new(),
+        }
+    }
+
+    /// 更新配置并重建事件输出
+    pub fn configure(&self, config: EventsConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        self.rebuild_sinks();
+    }
+
+    pub fn set_app_handle(&self, handle: tauri::AppHandle) {
+        *self.app_handle.write().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+        self.rebuild_sinks();
+    }
+
+    pub fn config(&self) -> EventsConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn rebuild_sinks(&self) {
+        let config = self.config();
+        let mut sinks: Vec<Arc<dyn EventSink>> = Vec::new();
+        if config.tauri_events {
+            if let Some(handle) = self.app_handle.read().unwrap_or_else(|e| e.into_inner()).clone() {
+                sinks.push(Arc::new(TauriSink::new(handle)));
+            }
+        }
+        if config.jsonl_log {
+            match crate::modules::account::get_data_dir() {
+                Ok(dir) => sinks.push(Arc::new(JsonlSink::new(dir.join(EVENTS_LOG_FILE)))),
+                Err(e) => tracing::warn!("[Events] JSONL sink disabled: {}", e),
+            }
+        }
+        for webhook in config.webhooks.iter().filter(|w| w.enabled && !w.url.trim().is_empty()) {
+            sinks.push(Arc::new(WebhookSink::new(webhook.clone())));
+        }
+        *self.sinks.write().unwrap_or_else(|e| e.into_inner()) = sinks;
+    }
+
+    /// 节流检查: 窗口内已触发过则返回 false，否则记录本次触发
+    fn should_fire(&self, event: &ProxyEvent) -> bool {
+        let window_ms = {
+            let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+            config
+                .throttle_overrides
+                .get(event.kind.as_str())
+                .copied()
+                .unwrap_or(config.throttle_seconds) as i64
+                * 1000
+        };
+        let mut fired = true;
+        self.last_fired
+            .entry(event.throttle_key())
+            .and_modify(|last| {
+                if event.timestamp - *last < window_ms {
+                    fired = false;
+                } else {
+                    *last = event.timestamp;
+                }
+            })
+            .or_insert(event.timestamp);
+        fired
+    }
+
+    pub fn emit(&self, event: ProxyEvent) {
+        if !self.config.read().unwrap_or_else(|e| e.into_inner()).enabled {
+            return;
+        }
+        if !self.should_fire(&event) {
+            tracing::debug!("[Events] Throttled {} ({})", event.kind.as_str(), event.subject);
+            return;
+        }
+        tracing::info!("[Events] {}: {}", event.kind.as_str(), event.message);
+
+        let sinks = self.sinks.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if sinks.is_empty() {
+            return;
+        }
+        tauri::async_runtime::spawn(async move {
+            for sink in sinks.iter().filter(|s| s.accepts(event.kind)) {
+                if let Err(e) = sink.deliver(&event).await {
+                    tracing::warn!("[Events] Failed to deliver {} to {}: {}", event.kind.as_str(), sink.name(), e);
+                }
+            }
+        });
+    }
+
+    /// 向所有输出发送测试事件 (不经节流)，返回每个输出的投递结果
+    pub async fn test_fire(&self) -> Vec<SinkDelivery> {
+        let event = ProxyEvent::new(
+            ProxyEventKind::Test,
+            EventSeverity::Info,
+            "",
+            "Test event from Antigravity Tools",
+            json!({}),
+        );
+        let sinks = self.sinks.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut results = Vec::new();
+        for sink in sinks {
+            let result = sink.deliver(&event).await;
+            results.push(SinkDelivery {
+                sink: sink.name(),
+                ok: result.is_ok(),
+                error: result.err(),
+            });
+        }
+        results
+    }
+}
+
+/// 账号被禁用
+pub fn account_disabled(account: &str, reason: &str) {
+    emit(ProxyEvent::new(
+        ProxyEventKind::AccountDisabled,
+        EventSeverity::Error,
+        account,
+        format!("Account {} disabled: {}", account, reason),
+        json!({ "account": account, "reason": reason }),
+    ));
+}
+
+/// 所有账号均不可用
+pub fn all_accounts_limited(quota_group: &str, wait_seconds: u64) {
+    emit(ProxyEvent::new(
+        ProxyEventKind::AllAccountsLimited,
+        EventSeverity::Error,
+        quota_group,
+        format!("All accounts are limited for '{}', shortest wait {}s", quota_group, wait_seconds),
+        json!({ "quota_group": quota_group, "wait_seconds": wait_seconds }),
+    ));
+}
+
+/// 检查配额并对低于阈值的模型触发 quota_low
+pub fn check_quota(email: &str, quota: &crate::models::QuotaData) {
+    let threshold = bus().config().quota_low_threshold;
+    if quota.is_forbidden {
+        return;
+    }
+    for model in quota.models.iter().filter(|m| m.percentage < threshold) {
+        emit(ProxyEvent::new(
+            ProxyEventKind::QuotaLow,
+            EventSeverity::Warning,
+            format!("{}:{}", email, model.name),
+            format!("{} quota for {} is at {}%", email, model.name, model.percentage),
+            json!({
+                "account": email,
+                "model": model.name,
+                "percentage": model.percentage,
+                "threshold": threshold,
+                "reset_time": model.reset_time,
+            }),
+        ));
+    }
+}
+
+/// 上游端点失败并切换
+pub fn upstream_failover(from: &str, to: &str, reason: &str) {
+    emit(ProxyEvent::new(
+        ProxyEventKind::UpstreamFailover,
+        EventSeverity::Warning,
+        from,
+        format!("Upstream {} failed ({}), failing over to {}", from, reason, to),
+        json!({ "from": from, "to": to, "reason": reason }),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_per_kind_and_subject() {
+        let bus = EventBus::new();
+        let mut config = EventsConfig::default();
+        config.throttle_overrides.insert("quota_low".to_string(), 0);
+        bus.configure(config);
+
+        let event = |kind, subject: &str, timestamp| {
+            let mut e = ProxyEvent::new(kind, EventSeverity::Warning, subject, "", json!({}));
+            e.timestamp = timestamp;
+            e
+        };
+        let disabled = ProxyEventKind::AccountDisabled;
+        assert!(bus.should_fire(&event(disabled, "a@x.com", 1_000)));
+        assert!(!bus.should_fire(&event(disabled, "a@x.com", 2_000)));
+        // 不同主体分别节流
+        assert!(bus.should_fire(&event(disabled, "b@x.com", 2_000)));
+        // 窗口过后再次触发
+        assert!(bus.should_fire(&event(disabled, "a@x.com", 302_000)));
+        // 按类型覆盖为不节流
+        assert!(bus.should_fire(&event(ProxyEventKind::QuotaLow, "a", 1_000)));
+        assert!(bus.should_fire(&event(ProxyEventKind::QuotaLow, "a", 1_001)));
+    }
+}
@@ -0,0 +1,183 @@
+// 事件输出: HTTP Webhook (HMAC 签名) / Tauri 事件 / 本地 JSONL 文件
+use futures::future::BoxFuture;
+use ring::hmac;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::Emitter;
+
+use super::{ProxyEvent, ProxyEventKind, WebhookConfig};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// JSONL 事件日志超过该大小后轮转为 .1
+const JSONL_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// 事件输出
+pub trait EventSink: Send + Sync {
+    /// 输出名称 (用于日志与测试结果)
+    fn name(&self) -> String;
+
+    /// 是否订阅该类型的事件
+    fn accepts(&self, _kind: ProxyEventKind) -> bool {
+        true
+    }
+
+    fn deliver<'a>(&'a self, event: &'a ProxyEvent) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// HTTP Webhook: POST 事件 JSON
+/// 配置了密钥时附带签名头:
+///   X-Antigravity-Timestamp: <unix 秒>
+///   X-Antigravity-Signature: sha256=<hex(HMAC-SHA256(secret, "<timestamp>.<body>"))>
+pub struct WebhookSink {
+    config: WebhookConfig,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self { config, client }
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook:{}", self.config.url)
+    }
+
+    fn accepts(&self, kind: ProxyEventKind) -> bool {
+        kind == ProxyEventKind::Test || self.config.events.is_empty() || self.config.events.contains(&kind)
+    }
+
+    fn deliver<'a>(&'a self, event: &'a ProxyEvent) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let body = serde_json::to_string(event).map_err(|e| e.to_string())?;
+            let mut request = self
+                .client
+                .post(&self.config.url)
+                .header("Content-Type", "application/json")
+                .header("X-Antigravity-Event", event.kind.as_str());
+            if !self.config.secret.is_empty() {
+                let timestamp = chrono::Utc::now().timestamp().to_string();
+                let signature = hmac_sha256_hex(
+                    self.config.secret.as_bytes(),
+                    format!("{}.{}", timestamp, body).as_bytes(),
+                );
+                request = request
+                    .header("X-Antigravity-Timestamp", timestamp)
+                    .header("X-Antigravity-Signature", format!("sha256={}", signature));
+            }
+            let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("Webhook returned {}", response.status()));
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Tauri 事件: 前端监听 proxy://event 并弹出提示
+pub struct TauriSink {
+    app: tauri::AppHandle,
+}
+
+impl TauriSink {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self { app }
+    }
+}
+
+impl EventSink for TauriSink {
+    fn name(&self) -> String {
+        "tauri".to_string()
+    }
+
+    fn deliver<'a>(&'a self, event: &'a ProxyEvent) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move { self.app.emit("proxy://event", event).map_err(|e| e.to_string()) })
+    }
+}
+
+/// 本地 JSONL 事件日志 (每行一个事件)
+pub struct JsonlSink {
+    path: PathBuf,
+}
+
+impl JsonlSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn append(path: &PathBuf, line: &str) -> std::io::Result<()> {
+        if std::fs::metadata(path).is_ok_and(|m| m.len() > JSONL_ROTATE_BYTES) {
+            std::fs::rename(path, path.with_extension("jsonl.1"))?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", line)
+    }
+}
+
+impl EventSink for JsonlSink {
+    fn name(&self) -> String {
+        format!("jsonl:{}", self.path.display())
+    }
+
+    fn deliver<'a>(&'a self, event: &'a ProxyEvent) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let line = serde_json::to_string(event).map_err(|e| e.to_string())?;
+            let path = self.path.clone();
+            tokio::task::spawn_blocking(move || Self::append(&path, &line))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// HMAC-SHA256，返回小写十六进制
+pub fn hmac_sha256_hex(key: &[u8], message: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, message).as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // test case 6: 密钥长于块大小
+        assert_eq!(
+            hmac_sha256_hex(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First"),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_sink_appends_lines() {
+        let path = std::env::temp_dir().join(format!("proxy-events-{}.jsonl", uuid::Uuid::new_v4()));
+        let sink = JsonlSink::new(path.clone());
+        for _ in 0..2 {
+            let event = ProxyEvent::new(
+                ProxyEventKind::Test,
+                super::super::EventSeverity::Info,
+                "",
+                "hello",
+                serde_json::json!({}),
+            );
+            sink.deliver(&event).await.unwrap();
+        }
+        let content = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("\"kind\":\"test\""));
+    }
+}
@@ -0,0 +1,111 @@
+// 管理接口 (/admin/*)
+// 需在配置中开启 enable_admin_api，且无论 auth_mode 如何都要求提供反代 API Key
+use axum::{
+    body::Body,
+    extract::State,
+    extract::Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::modules::proxy_export::LogExportRequest;
+use crate::proxy::middleware::extract_api_key;
+use crate::proxy::monitor::LogSearchQuery;
+use crate::proxy::server::AppState;
+
+/// 校验管理接口是否开启以及 API Key 是否正确
+async fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), Response> {
+    let runtime = state.runtime.load();
+    let security = &runtime.security;
+    if !security.admin_api_enabled {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    let authorized = !security.api_key.is_empty()
+        && extract_api_key(headers).is_some_and(|k| k == security.api_key);
+    if !authorized {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({ "error": "invalid or missing API key" })),
+        )
+            .into_response());
+    }
+    Ok(())
+}
+
+/// 检索请求日志
+/// POST /admin/logs/search  (请求体为 LogSearchQuery)
+pub async fn handle_search_logs(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(query): Json<LogSearchQuery>,
+) -> Response {
+    if let Err(response) = authorize_admin(&state, &headers).await {
+        return response;
+    }
+
+    match tokio::task::spawn_blocking(move || crate::modules::proxy_db::search_logs(&query)).await {
+        Ok(Ok(page)) => Json(page).into_response(),
+        Ok(Err(e)) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// 将同步写出的数据转发到响应流 (客户端断开后写入失败，导出随之停止)
+struct ChannelWriter(mpsc::Sender<Result<Bytes, std::io::Error>>);
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 流式导出请求日志
+/// POST /admin/logs/export  (请求体为 LogSearchQuery 的字段 + format: jsonl | har | otlp)
+pub async fn handle_export_logs(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<LogExportRequest>,
+) -> Response {
+    if let Err(response) = authorize_admin(&state, &headers).await {
+        return response;
+    }
+
+    let base_url = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(|host| format!("http://{}", host))
+        .unwrap_or_else(|| "http://localhost".to_string());
+    let format = request.format;
+
+    let (tx, rx) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = std::io::BufWriter::with_capacity(64 * 1024, ChannelWriter(tx.clone()));
+        if let Err(e) = crate::modules::proxy_db::export_logs(&request, &base_url, &mut writer) {
+            tracing::warn!("[Admin] Log export failed: {}", e);
+            let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"proxy-logs.{}\"", format.file_extension()),
+        )
+        .body(Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx)))
+        .unwrap_or_else(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())
+}
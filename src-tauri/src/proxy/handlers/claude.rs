@@ -5,6 +5,7 @@ use axum::{
     extract::{Json, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
+    Extension,
 };
 use bytes::Bytes;
 use futures::StreamExt;
@@ -12,10 +13,13 @@ use serde_json::{json, Value};
 use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info};
 
+use crate::proxy::client_keys::AuthenticatedKey;
 use crate::proxy::mappers::claude::{
     transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
 };
+use crate::proxy::monitor::{RequestAttribution, PROVIDER_GOOGLE, PROVIDER_ZAI};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::timeouts::UpstreamWaitError;
 use axum::http::HeaderMap;
 use std::sync::atomic::Ordering;
 
@@ -26,15 +30,33 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 /// 处理 Chat 消息请求流程
 pub async fn handle_messages(
     State(state): State<AppState>,
+    key: Option<Extension<AuthenticatedKey>>,
     headers: HeaderMap,
     Json(body): Json<Value>,
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let response = messages(state, key_group, headers, body, &mut attribution).await;
+    attribution.attach(response)
+}
+
+async fn messages(
+    state: AppState,
+    key_group: Option<String>,
+    headers: HeaderMap,
+    body: Value,
+    attribution: &mut RequestAttribution,
 ) -> Response {
     // Decide whether this request should be handled by z.ai (Anthropic passthrough) or the existing Google flow.
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     let zai_enabled = zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
     let google_accounts = state.token_manager.len();
 
-    let use_zai = if !zai_enabled {
+    let use_zai = if let Some(provider) = crate::proxy::replay::forced_provider() {
+        // 请求重放可强制指定提供方
+        provider == PROVIDER_ZAI
+    } else if !zai_enabled {
         false
     } else {
         match zai.dispatch_mode {
@@ -52,8 +74,14 @@ pub async fn handle_messages(
     };
 
     if use_zai {
+        let zai_model = body
+            .get("model")
+            .and_then(|v| v.as_str())
+            .map(|m| crate::proxy::providers::zai_anthropic::map_model_for_zai(m, &zai))
+            .unwrap_or_default();
+        attribution.record_attempt(PROVIDER_ZAI, None, &zai_model);
         return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
-            &state,
+            &runtime,
             axum::http::Method::POST,
             "/v1/messages",
             &headers,
@@ -173,11 +201,14 @@ pub async fn handle_messages(
     debug!("[{}] Full Claude Request JSON: {}", trace_id, serde_json::to_string_pretty(&request).unwrap_or_default());
     debug!("========== [{}] CLAUDE REQUEST DEBUG END ==========", trace_id);
 
-    // 1. 获取 会话 ID (已废弃基于内容的哈希，改用 TokenManager 内部的时间窗口锁定)
-    let _session_id: Option<&str> = None;
+    // 1. 获取会话 ID 用于粘性调度：优先使用客户端显式指定的会话头，否则使用稳定的内容指纹
+    // (在重试循环前计算一次，避免重试时请求体被修改导致指纹变化)
+    let session_id_str = crate::proxy::session_manager::SessionManager::session_id_from_headers(&headers)
+        .unwrap_or_else(|| crate::proxy::session_manager::SessionManager::extract_session_id(&request));
+    let session_id = Some(session_id_str.as_str());
 
-    // 2. 获取 UpstreamClient
-    let upstream = state.upstream.clone();
+    // 2. 获取 UpstreamClient (沿用请求开始时的配置快照)
+    let upstream = runtime.upstream.clone();
     
     // 3. 准备闭包
     let mut request_for_body = request.clone();
@@ -191,12 +222,16 @@ pub async fn handle_messages(
     
     for attempt in 0..max_attempts {
         // 3. 模型路由与配置解析 (提前解析以确定请求类型)
-        let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &request_for_body.model,
-            &*state.custom_mapping.read().await,
-            &*state.openai_mapping.read().await,
-            &*state.anthropic_mapping.read().await,
-        );
+        // 请求重放可强制指定上游模型
+        let mut mapped_model = match crate::proxy::replay::forced_mapped_model() {
+            Some(model) => model,
+            None => crate::proxy::common::model_mapping::resolve_model_route(
+                &request_for_body.model,
+                &runtime.config.custom_mapping,
+                &runtime.config.openai_mapping,
+                &runtime.config.anthropic_mapping,
+            ),
+        };
         // 将 Claude 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = request_for_body.tools.as_ref().map(|list| {
             list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
@@ -204,13 +239,13 @@ pub async fn handle_messages(
 
         let config = crate::proxy::mappers::common_utils::resolve_request_config(&request_for_body.model, &mapped_model, &tools_val);
 
-        // 0. 尝试提取 session_id 用于粘性调度 (Phase 2/3)
-        // 使用 SessionManager 生成稳定的会话指纹
-        let session_id_str = crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body);
-        let session_id = Some(session_id_str.as_str());
+        // 按客户端 Key / 模型 / 请求类型解析目标账号分组
+        let account_group = token_manager
+            .resolve_account_group(key_group.as_deref(), &request_for_body.model, &config.request_type)
+            .await;
 
         let force_rotate_token = attempt > 0;
-        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, force_rotate_token, session_id).await {
+        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, force_rotate_token, session_id, account_group.as_deref()).await {
             Ok(t) => t,
             Err(e) => {
                 let safe_message = if e.contains("invalid_grant") {
@@ -282,6 +317,7 @@ pub async fn handle_messages(
         }
 
         
+        attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), &mapped_model);
         request_with_mapped.model = mapped_model;
 
         // 生成 Trace ID (简单用时间戳后缀)
@@ -310,13 +346,19 @@ pub async fn handle_messages(
     let is_stream = request.stream;
     let method = if is_stream { "streamGenerateContent" } else { "generateContent" };
     let query = if is_stream { Some("alt=sse") } else { None };
-
-    let response = match upstream.call_v1_internal(
+    // 按模型解析超时，每次尝试重新计时
+    let timer = runtime
+        .config
+        .timeouts
+        .resolve(runtime.config.request_timeout, &[&request_with_mapped.model, &request_for_body.model])
+        .start();
+
+    let response = match timer.response(is_stream, upstream.call_v1_internal(
         method,
         &access_token,
         gemini_body,
         query
-    ).await {
+    )).await {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
@@ -331,9 +373,17 @@ pub async fn handle_messages(
         if status.is_success() {
             // 处理流式响应
             if request.stream {
-                let stream = response.bytes_stream();
-                let gemini_stream = Box::pin(stream);
-                let claude_stream = create_claude_sse_stream(gemini_stream, trace_id, email);
+                // 等待首个分块: 超时或失败时尚未向客户端输出内容，轮换账号重试
+                let keepalive = runtime.config.sse_keepalive();
+                let gemini_stream = match timer.stream(response, keepalive).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::warn!("[{}] {} (attempt {}/{}), rotating account", trace_id, e, attempt + 1, max_attempts);
+                        last_error = e;
+                        continue;
+                    }
+                };
+                let claude_stream = create_claude_sse_stream(gemini_stream, trace_id, email, keepalive);
 
                 // 转换为 Bytes stream
                 let sse_stream = claude_stream.map(|result| -> Result<Bytes, std::io::Error> {
@@ -352,9 +402,14 @@ pub async fn handle_messages(
                     .unwrap();
             } else {
                 // 处理非流式响应
-                let bytes = match response.bytes().await {
+                let bytes = match timer.body(response).await {
                     Ok(b) => b,
-                    Err(e) => return (StatusCode::BAD_GATEWAY, format!("Failed to read body: {}", e)).into_response(),
+                    Err(UpstreamWaitError::Timeout(e)) => {
+                        tracing::warn!("[{}] {} (attempt {}/{}), rotating account", trace_id, e, attempt + 1, max_attempts);
+                        last_error = e;
+                        continue;
+                    }
+                    Err(e) => return (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
                 };
                 
                 // Debug print
@@ -487,11 +542,12 @@ pub async fn handle_messages(
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
+    let runtime = state.runtime.load();
     let model_ids = get_all_dynamic_models(
-        &state.openai_mapping,
-        &state.custom_mapping,
-        &state.anthropic_mapping,
-    ).await;
+        &runtime.config.openai_mapping,
+        &runtime.config.custom_mapping,
+        &runtime.config.anthropic_mapping,
+    );
 
     let data: Vec<_> = model_ids.into_iter().map(|id| {
         json!({
@@ -514,12 +570,13 @@ pub async fn handle_count_tokens(
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     let zai_enabled = zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
 
     if zai_enabled {
         return crate::proxy::providers::zai_anthropic::forward_anthropic_json(
-            &state,
+            &runtime,
             axum::http::Method::POST,
             "/v1/messages/count_tokens",
             &headers,
@@ -15,11 +15,12 @@ pub async fn handle_detect_model(
     }
 
     // 1. Resolve mapping
+    let runtime = state.runtime.load();
     let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
         model_name,
-        &*state.custom_mapping.read().await,
-        &*state.openai_mapping.read().await,
-        &*state.anthropic_mapping.read().await,
+        &runtime.config.custom_mapping,
+        &runtime.config.openai_mapping,
+        &runtime.config.anthropic_mapping,
     );
 
     // 2. Resolve capabilities
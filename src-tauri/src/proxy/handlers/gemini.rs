@@ -1,11 +1,14 @@
 // Gemini Handler
-use axum::{extract::State, extract::{Json, Path}, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::{Json, Path}, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, Extension};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
+use crate::proxy::client_keys::AuthenticatedKey;
 use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
+use crate::proxy::monitor::{RequestAttribution, PROVIDER_GOOGLE};
 use crate::proxy::server::AppState;
 use crate::proxy::session_manager::SessionManager;
+use crate::proxy::upstream::timeouts::UpstreamWaitError;
  
 const MAX_RETRY_ATTEMPTS: usize = 3;
  
@@ -14,7 +17,23 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
+    key: Option<Extension<AuthenticatedKey>>,
+    headers: HeaderMap,
     Json(body): Json<Value>
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let result = generate(state, model_action, key_group, headers, body, &mut attribution).await;
+    attribution.attach(result)
+}
+
+async fn generate(
+    state: AppState,
+    model_action: String,
+    key_group: Option<String>,
+    headers: HeaderMap,
+    body: Value,
+    attribution: &mut RequestAttribution,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
     let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
@@ -32,7 +51,8 @@ pub async fn handle_generate(
     let is_stream = method == "streamGenerateContent";
 
     // 2. 获取 UpstreamClient 和 TokenManager
-    let upstream = state.upstream.clone();
+    let runtime = state.runtime.load();
+    let upstream = runtime.upstream.clone();
     let token_manager = state.token_manager;
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
@@ -41,12 +61,16 @@ pub async fn handle_generate(
 
     for attempt in 0..max_attempts {
         // 3. 模型路由与配置解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &model_name,
-            &*state.custom_mapping.read().await,
-            &*state.openai_mapping.read().await,
-            &*state.anthropic_mapping.read().await,
-        );
+        // 请求重放可强制指定上游模型
+        let mapped_model = match crate::proxy::replay::forced_mapped_model() {
+            Some(model) => model,
+            None => crate::proxy::common::model_mapping::resolve_model_route(
+                &model_name,
+                &runtime.config.custom_mapping,
+                &runtime.config.openai_mapping,
+                &runtime.config.anthropic_mapping,
+            ),
+        };
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> = body.get("tools").and_then(|t| t.as_array()).map(|arr| {
             let mut flattened = Vec::new();
@@ -63,11 +87,17 @@ pub async fn handle_generate(
         let config = crate::proxy::mappers::common_utils::resolve_request_config(&model_name, &mapped_model, &tools_val);
 
         // 4. 获取 Token (使用准确的 request_type)
-        // 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+        // 提取 SessionId (显式会话头优先，否则使用粘性指纹)
+        let session_id = SessionManager::session_id_from_headers(&headers)
+            .unwrap_or_else(|| SessionManager::extract_gemini_session_id(&body, &model_name));
+
+        // 按客户端 Key / 模型 / 请求类型解析目标账号分组
+        let account_group = token_manager
+            .resolve_account_group(key_group.as_deref(), &model_name, &config.request_type)
+            .await;
 
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id)).await {
+        let (access_token, project_id, email) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id), account_group.as_deref()).await {
             Ok(t) => t,
             Err(e) => {
                 return Err((StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)));
@@ -75,6 +105,7 @@ pub async fn handle_generate(
         };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), &mapped_model);
 
         // 5. 包装请求 (project injection)
         let wrapped_body = wrap_request(&body, &project_id, &mapped_model);
@@ -83,8 +114,15 @@ pub async fn handle_generate(
         let query_string = if is_stream { Some("alt=sse") } else { None };
         let upstream_method = if is_stream { "streamGenerateContent" } else { "generateContent" };
 
-        let response = match upstream
-            .call_v1_internal(upstream_method, &access_token, wrapped_body, query_string)
+        // 按模型解析超时，每次尝试重新计时
+        let timer = runtime
+            .config
+            .timeouts
+            .resolve(runtime.config.request_timeout, &[&mapped_model, &model_name])
+            .start();
+
+        let response = match timer
+            .response(is_stream, upstream.call_v1_internal(upstream_method, &access_token, wrapped_body, query_string))
             .await {
                 Ok(r) => r,
                 Err(e) => {
@@ -103,7 +141,16 @@ pub async fn handle_generate(
                 use bytes::{Bytes, BytesMut};
                 use futures::StreamExt;
                 
-                let mut response_stream = response.bytes_stream();
+                // 等待首个分块: 超时或失败时尚未向客户端输出内容，轮换账号重试
+                let keepalive = runtime.config.sse_keepalive();
+                let mut response_stream = match timer.stream(response, keepalive).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::warn!("Gemini {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                        last_error = e;
+                        continue;
+                    }
+                };
                 let mut buffer = BytesMut::new();
 
                 let stream = async_stream::stream! {
@@ -159,6 +206,11 @@ pub async fn handle_generate(
                     }
                 };
                 
+                let stream = crate::proxy::mappers::keepalive::with_keepalive(
+                    Box::pin(stream),
+                    keepalive,
+                    crate::proxy::mappers::keepalive::SSE_COMMENT,
+                );
                 let body = Body::from_stream(stream);
                 return Ok(Response::builder()
                     .header("Content-Type", "text/event-stream")
@@ -169,9 +221,16 @@ pub async fn handle_generate(
                     .into_response());
             }
 
-            let gemini_resp: Value = response
-                .json()
-                .await
+            let bytes = match timer.body(response).await {
+                Ok(b) => b,
+                Err(UpstreamWaitError::Timeout(e)) => {
+                    tracing::warn!("Gemini {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                    last_error = e;
+                    continue;
+                }
+                Err(e) => return Err((StatusCode::BAD_GATEWAY, e.to_string())),
+            };
+            let gemini_resp: Value = serde_json::from_slice(&bytes)
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let unwrapped = unwrap_response(&gemini_resp);
@@ -209,11 +268,11 @@ pub async fn handle_generate(
 
 pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
     let model_group = "gemini";
-    let (access_token, _, _) = state.token_manager.get_token(model_group, false, None).await
+    let (access_token, _, _) = state.token_manager.get_token(model_group, false, None, None).await
         .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)))?;
 
     // Fetch from upstream
-    let upstream_models = state.upstream.fetch_available_models(&access_token).await
+    let upstream_models = state.runtime.load().upstream.fetch_available_models(&access_token).await
         .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
 
     // Transform map to Gemini list format
@@ -260,7 +319,7 @@ pub async fn handle_get_model(Path(model_name): Path<String>) -> impl IntoRespon
 
 pub async fn handle_count_tokens(State(state): State<AppState>, Path(_model_name): Path<String>, Json(_body): Json<Value>) -> Result<impl IntoResponse, (StatusCode, String)> {
     let model_group = "gemini";
-    let (_access_token, _project_id, _) = state.token_manager.get_token(model_group, false, None).await
+    let (_access_token, _project_id, _) = state.token_manager.get_token(model_group, false, None, None).await
         .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)))?;
     
     Ok(Json(json!({"totalTokens": 0})))
@@ -49,7 +49,8 @@ async fn forward_mcp(
     upstream_url: &str,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     if !zai.enabled || zai.api_key.trim().is_empty() {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
     }
@@ -58,8 +59,8 @@ async fn forward_mcp(
         return StatusCode::NOT_FOUND.into_response();
     }
 
-    let upstream_proxy = state.upstream_proxy.read().await.clone();
-    let client = match build_client(upstream_proxy, state.request_timeout) {
+    let upstream_proxy = runtime.config.upstream_proxy.clone();
+    let client = match build_client(upstream_proxy, runtime.config.request_timeout) {
         Ok(c) => c,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     };
@@ -118,7 +119,8 @@ pub async fn handle_web_search_prime(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     if !zai.mcp.web_search_enabled {
         return StatusCode::NOT_FOUND.into_response();
     }
@@ -140,7 +142,8 @@ pub async fn handle_web_reader(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     if !zai.mcp.web_reader_enabled {
         return StatusCode::NOT_FOUND.into_response();
     }
@@ -332,9 +335,10 @@ async fn handle_vision_post(state: AppState, headers: HeaderMap, body: Body) ->
 
             let arguments = params.get("arguments").cloned().unwrap_or(Value::Object(Default::default()));
 
-            let zai = state.zai.read().await.clone();
-            let upstream_proxy = state.upstream_proxy.read().await.clone();
-            let timeout = state.request_timeout;
+            let runtime = state.runtime.load();
+            let zai = runtime.config.zai.clone();
+            let upstream_proxy = runtime.config.upstream_proxy.clone();
+            let timeout = runtime.config.request_timeout;
 
             match crate::proxy::zai_vision_tools::call_tool(
                 &zai,
@@ -379,7 +383,8 @@ pub async fn handle_zai_mcp_server(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let runtime = state.runtime.load();
+    let zai = runtime.config.zai.clone();
     if !zai.enabled || zai.api_key.trim().is_empty() {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
     }
@@ -1,21 +1,39 @@
 // OpenAI Handler
-use axum::{extract::Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{extract::Json, extract::State, http::{HeaderMap, StatusCode}, response::{IntoResponse, Response}, Extension};
 use base64::Engine as _;
 use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
+use crate::proxy::client_keys::AuthenticatedKey;
 use crate::proxy::mappers::openai::{
     transform_openai_request, transform_openai_response, OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
+use crate::proxy::monitor::{RequestAttribution, PROVIDER_GOOGLE};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::timeouts::UpstreamWaitError;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 use crate::proxy::session_manager::SessionManager;
 
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    key: Option<Extension<AuthenticatedKey>>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let result = chat_completions(state, key_group, headers, body, &mut attribution).await;
+    attribution.attach(result)
+}
+
+async fn chat_completions(
+    state: AppState,
+    key_group: Option<String>,
+    headers: HeaderMap,
+    body: Value,
+    attribution: &mut RequestAttribution,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
@@ -39,7 +57,8 @@ pub async fn handle_chat_completions(
     debug!("Received OpenAI request for model: {}", openai_req.model);
 
     // 1. 获取 UpstreamClient (Clone handle)
-    let upstream = state.upstream.clone();
+    let runtime = state.runtime.load();
+    let upstream = runtime.upstream.clone();
     let token_manager = state.token_manager;
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
@@ -48,12 +67,16 @@ pub async fn handle_chat_completions(
 
     for attempt in 0..max_attempts {
         // 2. 预解析模型路由与配置
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-            &*state.openai_mapping.read().await,
-            &*state.anthropic_mapping.read().await,
-        );
+        // 请求重放可强制指定上游模型
+        let mapped_model = match crate::proxy::replay::forced_mapped_model() {
+            Some(model) => model,
+            None => crate::proxy::common::model_mapping::resolve_model_route(
+                &openai_req.model,
+                &runtime.config.custom_mapping,
+                &runtime.config.openai_mapping,
+                &runtime.config.anthropic_mapping,
+            ),
+        };
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
@@ -65,13 +88,19 @@ pub async fn handle_chat_completions(
             &tools_val,
         );
 
-        // 3. 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_openai_session_id(&openai_req);
+        // 3. 提取 SessionId (显式会话头优先，否则使用粘性指纹)
+        let session_id = SessionManager::session_id_from_headers(&headers)
+            .unwrap_or_else(|| SessionManager::extract_openai_session_id(&openai_req));
+
+        // 按客户端 Key / 模型 / 请求类型解析目标账号分组
+        let account_group = token_manager
+            .resolve_account_group(key_group.as_deref(), &openai_req.model, &config.request_type)
+            .await;
 
         // 4. 获取 Token (使用准确的 request_type)
         // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
         let (access_token, project_id, email) = match token_manager
-            .get_token(&config.request_type, attempt > 0, Some(&session_id))
+            .get_token(&config.request_type, attempt > 0, Some(&session_id), account_group.as_deref())
             .await
         {
             Ok(t) => t,
@@ -84,6 +113,7 @@ pub async fn handle_chat_completions(
         };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), &mapped_model);
 
         // 4. 转换请求
         let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
@@ -102,8 +132,18 @@ pub async fn handle_chat_completions(
         };
         let query_string = if list_response { Some("alt=sse") } else { None };
 
-        let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+        // 按模型解析超时，每次尝试重新计时
+        let timer = runtime
+            .config
+            .timeouts
+            .resolve(runtime.config.request_timeout, &[&mapped_model, &openai_req.model])
+            .start();
+
+        let response = match timer
+            .response(
+                list_response,
+                upstream.call_v1_internal(method, &access_token, gemini_body, query_string),
+            )
             .await
         {
             Ok(r) => r,
@@ -128,9 +168,17 @@ pub async fn handle_chat_completions(
                 use axum::response::Response;
                 // Removed redundant StreamExt
 
-                let gemini_stream = response.bytes_stream();
-                let openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                // 等待首个分块: 超时或失败时尚未向客户端输出内容，轮换账号重试
+                let keepalive = runtime.config.sse_keepalive();
+                let gemini_stream = match timer.stream(response, keepalive).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::warn!("OpenAI {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                        last_error = e;
+                        continue;
+                    }
+                };
+                let openai_stream = create_openai_sse_stream(gemini_stream, openai_req.model.clone(), keepalive);
                 let body = Body::from_stream(openai_stream);
 
                 return Ok(Response::builder()
@@ -142,9 +190,16 @@ pub async fn handle_chat_completions(
                     .into_response());
             }
 
-            let gemini_resp: Value = response
-                .json()
-                .await
+            let bytes = match timer.body(response).await {
+                Ok(b) => b,
+                Err(UpstreamWaitError::Timeout(e)) => {
+                    tracing::warn!("OpenAI {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                    last_error = e;
+                    continue;
+                }
+                Err(e) => return Err((StatusCode::BAD_GATEWAY, e.to_string())),
+            };
+            let gemini_resp: Value = serde_json::from_slice(&bytes)
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let openai_response = transform_openai_response(&gemini_resp);
@@ -237,7 +292,22 @@ pub async fn handle_chat_completions(
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
     State(state): State<AppState>,
-    Json(mut body): Json<Value>,
+    key: Option<Extension<AuthenticatedKey>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let result = completions(state, key_group, headers, body, &mut attribution).await;
+    attribution.attach(result)
+}
+
+async fn completions(
+    state: AppState,
+    key_group: Option<String>,
+    headers: HeaderMap,
+    mut body: Value,
+    attribution: &mut RequestAttribution,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     info!(
         "Received /v1/completions or /v1/responses payload: {:?}",
@@ -511,20 +581,29 @@ pub async fn handle_completions(
             });
     }
 
-    let upstream = state.upstream.clone();
+    let runtime = state.runtime.load();
+    let upstream = runtime.upstream.clone();
     let token_manager = state.token_manager;
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
 
+    // 提取 SessionId (显式会话头优先，否则使用粘性指纹)
+    let session_id = SessionManager::session_id_from_headers(&headers)
+        .unwrap_or_else(|| SessionManager::extract_openai_session_id(&openai_req));
+
     let mut last_error = String::new();
 
-    for _attempt in 0..max_attempts {
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &openai_req.model,
-            &*state.custom_mapping.read().await,
-            &*state.openai_mapping.read().await,
-            &*state.anthropic_mapping.read().await,
-        );
+    for attempt in 0..max_attempts {
+        // 请求重放可强制指定上游模型
+        let mapped_model = match crate::proxy::replay::forced_mapped_model() {
+            Some(model) => model,
+            None => crate::proxy::common::model_mapping::resolve_model_route(
+                &openai_req.model,
+                &runtime.config.custom_mapping,
+                &runtime.config.openai_mapping,
+                &runtime.config.anthropic_mapping,
+            ),
+        };
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
@@ -536,18 +615,25 @@ pub async fn handle_completions(
             &tools_val,
         );
 
-        let (access_token, project_id, email) =
-            match token_manager.get_token(&config.request_type, false, None).await {
-                Ok(t) => t,
-                Err(e) => {
-                    return Err((
-                        StatusCode::SERVICE_UNAVAILABLE,
-                        format!("Token error: {}", e),
-                    ))
-                }
-            };
+        let account_group = token_manager
+            .resolve_account_group(key_group.as_deref(), &openai_req.model, &config.request_type)
+            .await;
+
+        let (access_token, project_id, email) = match token_manager
+            .get_token(&config.request_type, attempt > 0, Some(&session_id), account_group.as_deref())
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                ))
+            }
+        };
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
+        attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), &mapped_model);
 
         let gemini_body = transform_openai_request(&openai_req, &project_id, &mapped_model);
 
@@ -564,8 +650,18 @@ pub async fn handle_completions(
         };
         let query_string = if list_response { Some("alt=sse") } else { None };
 
-        let response = match upstream
-            .call_v1_internal(method, &access_token, gemini_body, query_string)
+        // 按模型解析超时，每次尝试重新计时
+        let timer = runtime
+            .config
+            .timeouts
+            .resolve(runtime.config.request_timeout, &[&mapped_model, &openai_req.model])
+            .start();
+
+        let response = match timer
+            .response(
+                list_response,
+                upstream.call_v1_internal(method, &access_token, gemini_body, query_string),
+            )
             .await
         {
             Ok(r) => r,
@@ -581,16 +677,22 @@ pub async fn handle_completions(
                 use axum::body::Body;
                 use axum::response::Response;
 
-                let gemini_stream = response.bytes_stream();
+                let keepalive = runtime.config.sse_keepalive();
+                let gemini_stream = match timer.stream(response, keepalive).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::warn!("Codex {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                        last_error = e;
+                        continue;
+                    }
+                };
                 let body = if is_codex_style {
                     use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                    let s =
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    let s = create_codex_sse_stream(gemini_stream, openai_req.model.clone(), keepalive);
                     Body::from_stream(s)
                 } else {
                     use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
-                    let s =
-                        create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                    let s = create_legacy_sse_stream(gemini_stream, openai_req.model.clone(), keepalive);
                     Body::from_stream(s)
                 };
 
@@ -603,9 +705,16 @@ pub async fn handle_completions(
                     .into_response());
             }
 
-            let gemini_resp: Value = response
-                .json()
-                .await
+            let bytes = match timer.body(response).await {
+                Ok(b) => b,
+                Err(UpstreamWaitError::Timeout(e)) => {
+                    tracing::warn!("Codex {} on attempt {}/{}, rotating account", e, attempt + 1, max_attempts);
+                    last_error = e;
+                    continue;
+                }
+                Err(e) => return Err((StatusCode::BAD_GATEWAY, e.to_string())),
+            };
+            let gemini_resp: Value = serde_json::from_slice(&bytes)
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let chat_resp = transform_openai_response(&gemini_resp);
@@ -654,11 +763,12 @@ pub async fn handle_completions(
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
+    let runtime = state.runtime.load();
     let model_ids = get_all_dynamic_models(
-        &state.openai_mapping,
-        &state.custom_mapping,
-        &state.anthropic_mapping,
-    ).await;
+        &runtime.config.openai_mapping,
+        &runtime.config.custom_mapping,
+        &runtime.config.anthropic_mapping,
+    );
 
     let data: Vec<_> = model_ids.into_iter().map(|id| {
         json!({
@@ -679,7 +789,20 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
 /// 处理图像生成请求，转换为 Gemini API 格式
 pub async fn handle_images_generations(
     State(state): State<AppState>,
+    key: Option<Extension<AuthenticatedKey>>,
     Json(body): Json<Value>,
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let result = images_generations(state, key_group, body, &mut attribution).await;
+    attribution.attach(result)
+}
+
+async fn images_generations(
+    state: AppState,
+    key_group: Option<String>,
+    body: Value,
+    attribution: &mut RequestAttribution,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 1. 解析请求参数
     let prompt = body.get("prompt").and_then(|v| v.as_str()).ok_or((
@@ -745,10 +868,16 @@ pub async fn handle_images_generations(
     }
 
     // 3. 获取 Token
-    let upstream = state.upstream.clone();
+    let runtime = state.runtime.load();
+    let upstream = runtime.upstream.clone();
     let token_manager = state.token_manager;
+    let account_group = token_manager
+        .resolve_account_group(key_group.as_deref(), model, "image_gen")
+        .await;
 
-    let (access_token, project_id, email) = match token_manager.get_token("image_gen", false, None).await
+    let (access_token, project_id, email) = match token_manager
+        .get_token("image_gen", false, None, account_group.as_deref())
+        .await
     {
         Ok(t) => t,
         Err(e) => {
@@ -760,8 +889,14 @@ pub async fn handle_images_generations(
     };
 
     info!("✓ Using account: {} for image generation", email);
+    attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), "gemini-3-pro-image");
 
     // 4. 并发发送请求 (解决 candidateCount > 1 不支持的问题)
+    let timer = runtime
+        .config
+        .timeouts
+        .resolve(runtime.config.request_timeout, &["gemini-3-pro-image", model])
+        .start();
     let mut tasks = Vec::new();
 
     for _ in 0..n {
@@ -800,8 +935,8 @@ pub async fn handle_images_generations(
                 }
             });
 
-            match upstream
-                .call_v1_internal("generateContent", &access_token, gemini_body, None)
+            match timer
+                .response(false, upstream.call_v1_internal("generateContent", &access_token, gemini_body, None))
                 .await
             {
                 Ok(response) => {
@@ -810,10 +945,8 @@ pub async fn handle_images_generations(
                         let err_text = response.text().await.unwrap_or_default();
                         return Err(format!("Upstream error {}: {}", status, err_text));
                     }
-                    match response.json::<Value>().await {
-                        Ok(json) => Ok(json),
-                        Err(e) => Err(format!("Parse error: {}", e)),
-                    }
+                    let bytes = timer.body(response).await.map_err(|e| e.to_string())?;
+                    serde_json::from_slice::<Value>(&bytes).map_err(|e| format!("Parse error: {}", e))
                 }
                 Err(e) => Err(format!("Network error: {}", e)),
             }
@@ -909,7 +1042,20 @@ pub async fn handle_images_generations(
 
 pub async fn handle_images_edits(
     State(state): State<AppState>,
+    key: Option<Extension<AuthenticatedKey>>,
+    multipart: axum::extract::Multipart,
+) -> Response {
+    let mut attribution = RequestAttribution::default();
+    let key_group = key.and_then(|Extension(k)| k.group);
+    let result = images_edits(state, key_group, multipart, &mut attribution).await;
+    attribution.attach(result)
+}
+
+async fn images_edits(
+    state: AppState,
+    key_group: Option<String>,
     mut multipart: axum::extract::Multipart,
+    attribution: &mut RequestAttribution,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     tracing::info!("[Images] Received edit request");
 
@@ -995,10 +1141,16 @@ pub async fn handle_images_edits(
     // Let's keep the log to confirm.
 
     // 1. 获取 Upstream
-    let upstream = state.upstream.clone();
+    let runtime = state.runtime.load();
+    let upstream = runtime.upstream.clone();
     let token_manager = state.token_manager;
+    let account_group = token_manager
+        .resolve_account_group(key_group.as_deref(), &model, "image_gen")
+        .await;
     // Fix: Proper get_token call with correct signature and unwrap (using image_gen quota)
-    let (access_token, project_id, _email) = match token_manager.get_token("image_gen", false, None).await
+    let (access_token, project_id, email) = match token_manager
+        .get_token("image_gen", false, None, account_group.as_deref())
+        .await
     {
         Ok(t) => t,
         Err(e) => {
@@ -1008,6 +1160,7 @@ pub async fn handle_images_edits(
             ))
         }
     };
+    attribution.record_attempt(PROVIDER_GOOGLE, Some(&email), &model);
 
     // 2. 映射配置
     let mut contents_parts = Vec::new();
@@ -1064,6 +1217,11 @@ pub async fn handle_images_edits(
         }
     });
 
+    let timer = runtime
+        .config
+        .timeouts
+        .resolve(runtime.config.request_timeout, &[&model])
+        .start();
     let mut tasks = Vec::new();
     for _ in 0..n {
         let upstream = upstream.clone();
@@ -1071,8 +1229,8 @@ pub async fn handle_images_edits(
         let body = gemini_body.clone();
 
         tasks.push(tokio::spawn(async move {
-            match upstream
-                .call_v1_internal("generateContent", &access_token, body, None)
+            match timer
+                .response(false, upstream.call_v1_internal("generateContent", &access_token, body, None))
                 .await
             {
                 Ok(response) => {
@@ -1081,10 +1239,8 @@ pub async fn handle_images_edits(
                         let err_text = response.text().await.unwrap_or_default();
                         return Err(format!("Upstream error {}: {}", status, err_text));
                     }
-                    match response.json::<Value>().await {
-                        Ok(json) => Ok(json),
-                        Err(e) => Err(format!("Parse error: {}", e)),
-                    }
+                    let bytes = timer.body(response).await.map_err(|e| e.to_string())?;
+                    serde_json::from_slice::<Value>(&bytes).map_err(|e| format!("Parse error: {}", e))
                 }
                 Err(e) => Err(format!("Network error: {}", e)),
             }
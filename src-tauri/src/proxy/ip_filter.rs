@@ -0,0 +1,145 @@
+// 客户端 IP 访问控制 (CIDR 允许 / 拒绝列表)
+// - 拒绝列表优先于允许列表
+// - 允许列表为空时不限制来源；非空时仅列表内地址可访问 (本机回环地址始终放行，除非被显式拒绝)
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// IP 访问控制配置
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct IpFilterConfig {
+    /// 允许访问的地址 / 网段 (如 "192.168.1.0/24"、"10.0.0.5"、"fd00::/8")
+    pub allowlist: Vec<String>,
+    /// 拒绝访问的地址 / 网段
+    pub denylist: Vec<String>,
+}
+
+/// 编译后的访问控制规则
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+/// 解析单条规则，单个 IP 视为 /32 或 /128
+pub fn parse_rule(rule: &str) -> Result<IpNet, String> {
+    let rule = rule.trim();
+    if let Ok(net) = rule.parse::<IpNet>() {
+        return Ok(net.trunc());
+    }
+    rule.parse::<IpAddr>()
+        .map(IpNet::from)
+        .map_err(|_| format!("无效的 IP / CIDR 规则: {}", rule))
+}
+
+impl IpFilter {
+    /// 严格解析，任一规则无效即返回错误 (用于保存配置前校验)
+    pub fn try_from_config(config: &IpFilterConfig) -> Result<Self, String> {
+        let parse_all = |rules: &[String]| -> Result<Vec<IpNet>, String> {
+            rules
+                .iter()
+                .filter(|r| !r.trim().is_empty())
+                .map(|r| parse_rule(r))
+                .collect()
+        };
+        Ok(Self {
+            allow: parse_all(&config.allowlist)?,
+            deny: parse_all(&config.denylist)?,
+        })
+    }
+
+    /// 宽松解析，跳过无效规则并记录警告
+    pub fn from_config(config: &IpFilterConfig) -> Self {
+        let parse_all = |rules: &[String]| -> Vec<IpNet> {
+            rules
+                .iter()
+                .filter(|r| !r.trim().is_empty())
+                .filter_map(|r| match parse_rule(r) {
+                    Ok(net) => Some(net),
+                    Err(e) => {
+                        tracing::warn!("[IpFilter] {}", e);
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            allow: parse_all(&config.allowlist),
+            deny: parse_all(&config.denylist),
+        }
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        // IPv4-mapped IPv6 (::ffff:a.b.c.d) 按 IPv4 匹配
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        if self.allow.is_empty() || ip.is_loopback() {
+            return true;
+        }
+        self.allow.iter().any(|net| net.contains(&ip))
+    }
+
+    /// 允许列表非空且只包含回环网段，即除本机外任何来源都无法连接
+    pub fn admits_only_loopback(&self) -> bool {
+        let loopback: [IpNet; 2] = ["127.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()];
+        !self.allow.is_empty() && self.allow.iter().all(|net| loopback.iter().any(|lo| lo.contains(net)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> IpFilter {
+        IpFilter::try_from_config(&IpFilterConfig {
+            allowlist: allow.iter().map(|s| s.to_string()).collect(),
+            denylist: deny.iter().map(|s| s.to_string()).collect(),
+        })
+        .unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_allow_and_deny_rules() {
+        let f = filter(&["192.168.1.0/24", "10.0.0.5"], &["192.168.1.13"]);
+        assert!(f.is_allowed(ip("192.168.1.20")));
+        assert!(f.is_allowed(ip("10.0.0.5")));
+        assert!(!f.is_allowed(ip("10.0.0.6")));
+        assert!(!f.is_allowed(ip("192.168.1.13")));
+        // 回环地址始终放行；IPv4-mapped IPv6 按 IPv4 匹配
+        assert!(f.is_allowed(ip("127.0.0.1")));
+        assert!(f.is_allowed(ip("::ffff:192.168.1.20")));
+        assert!(!f.is_allowed(ip("::ffff:192.168.1.13")));
+
+        let open = filter(&[], &["0.0.0.0/0"]);
+        assert!(!open.is_allowed(ip("127.0.0.1")));
+        assert!(open.is_allowed(ip("::1")));
+    }
+
+    #[test]
+    fn test_admits_only_loopback() {
+        assert!(filter(&["127.0.0.1", "::1"], &[]).admits_only_loopback());
+        assert!(filter(&["127.0.0.0/8"], &[]).admits_only_loopback());
+        assert!(!filter(&[], &[]).admits_only_loopback());
+        assert!(!filter(&["127.0.0.1", "192.168.1.20"], &[]).admits_only_loopback());
+        assert!(!filter(&["0.0.0.0/0"], &[]).admits_only_loopback());
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(parse_rule(" 192.168.1.77/24 ").unwrap().to_string(), "192.168.1.0/24");
+        assert_eq!(parse_rule("fd00::1").unwrap().to_string(), "fd00::1/128");
+        assert!(parse_rule("192.168.1.0/33").is_err());
+        assert!(IpFilter::try_from_config(&IpFilterConfig {
+            allowlist: vec!["lan".to_string()],
+            denylist: vec![],
+        })
+        .is_err());
+    }
+}
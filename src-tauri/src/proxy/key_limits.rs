@@ -0,0 +1,509 @@
+// 按客户端 Key (可选叠加客户端 IP) 的限流与用量预算
+// - 按认证识别出的 Key 名称计量 (主 API Key 为 `default`，其余见 client_keys)
+// - 每分钟请求数: 令牌桶 (容量为 burst，按 requests_per_minute 匀速补充)
+// - 每日 / 每月 token 预算: 按 UTC 自然日 / 自然月累计，持久化到数据目录，重启后继续生效
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::proxy::client_keys::{ClientApiKey, PRIMARY_KEY_NAME};
+
+/// 预算文件名 (位于数据目录)
+pub const BUDGETS_FILE: &str = "key_budgets.json";
+/// 预算变更后最短的落盘间隔
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// 未通过认证 (含认证关闭) 的请求共用的计量主体
+const ANONYMOUS: &str = "anonymous";
+/// 计量主体数上限，超出时清理空闲主体，仍超出则新主体不再按 IP 区分
+const MAX_SUBJECTS: usize = 4096;
+
+/// 单个客户端 Key 的限额 (0 表示不限制)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct KeyLimit {
+    pub requests_per_minute: u32,
+    /// 令牌桶容量 (允许的突发请求数，0 表示等于 requests_per_minute)
+    pub burst: u32,
+    pub tokens_per_day: u64,
+    pub tokens_per_month: u64,
+}
+
+/// 限流与预算配置
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct KeyLimitsConfig {
+    pub enabled: bool,
+    /// 同一 Key 下按客户端 IP 分别计量
+    pub per_ip: bool,
+    /// 未单独配置的 Key (及未认证请求) 使用的限额
+    pub default_limits: KeyLimit,
+    /// 按 Key 名称单独配置的限额 (主 API Key 为 `default`，其余为 client_keys 中的名称)
+    pub keys: HashMap<String, KeyLimit>,
+}
+
+impl KeyLimitsConfig {
+    /// 保存配置前校验: 单独配置的 Key 名称必须存在，否则该限额永远不会生效
+    pub fn validate(&self, client_keys: &[ClientApiKey]) -> Result<(), String> {
+        match self
+            .keys
+            .keys()
+            .find(|name| name.as_str() != PRIMARY_KEY_NAME && !client_keys.iter().any(|k| &k.name == *name))
+        {
+            Some(name) => Err(format!("限额配置中的 Key `{}` 不存在", name)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 触发的限制类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    Requests,
+    DailyTokens,
+    MonthlyTokens,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitExceeded {
+    pub kind: LimitKind,
+    /// 距离可以重试的秒数
+    pub retry_after: u64,
+    pub message: String,
+}
+
+/// 计量主体的持久化用量
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct BudgetUsage {
+    /// Key 名称 (及 IP)，仅用于展示
+    pub label: String,
+    /// 当前计量日 (UTC, YYYY-MM-DD)
+    pub day: String,
+    pub day_tokens: u64,
+    /// 当前计量月 (UTC, YYYY-MM)
+    pub month: String,
+    pub month_tokens: u64,
+}
+
+impl BudgetUsage {
+    /// 跨日 / 跨月时清零对应计数
+    fn roll_over(&mut self, now: DateTime<Utc>) {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+        if self.day != day {
+            self.day = day;
+            self.day_tokens = 0;
+        }
+        if self.month != month {
+            self.month = month;
+            self.month_tokens = 0;
+        }
+    }
+
+    /// 当日与当月均无用量 (可以丢弃)
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.day != now.format("%Y-%m-%d").to_string() && self.month != now.format("%Y-%m").to_string()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    /// 补满的时刻，之后与新建的令牌桶等价 (可以丢弃)
+    full_at: Instant,
+}
+
+pub struct KeyLimiter {
+    config: RwLock<KeyLimitsConfig>,
+    buckets: DashMap<String, Bucket>,
+    budgets: DashMap<String, BudgetUsage>,
+    path: Option<PathBuf>,
+    dirty: AtomicBool,
+    last_saved: Mutex<Instant>,
+}
+
+impl KeyLimiter {
+    /// `path` 为预算文件路径 (None 时不持久化)
+    pub fn new(config: KeyLimitsConfig, path: Option<PathBuf>) -> Self {
+        let mut budgets = DashMap::new();
+        if let Some(p) = &path {
+            match std::fs::read_to_string(p) {
+                Ok(content) => match serde_json::from_str::<HashMap<String, BudgetUsage>>(&content) {
+                    Ok(map) => budgets.extend(map),
+                    Err(e) => tracing::warn!("Failed to parse key budgets {:?}: {}", p, e),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to read key budgets {:?}: {}", p, e),
+            }
+        }
+        Self {
+            config: RwLock::new(config),
+            buckets: DashMap::new(),
+            budgets,
+            path,
+            dirty: AtomicBool::new(false),
+            last_saved: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn set_config(&self, config: KeyLimitsConfig) {
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        // 限额或计量方式变化后令牌桶按新容量重新开始，其它设置变化时保留
+        if current.per_ip != config.per_ip
+            || current.default_limits != config.default_limits
+            || current.keys != config.keys
+        {
+            self.buckets.clear();
+        }
+        *current = config;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).enabled
+    }
+
+    /// 计量主体: 识别出的 Key 名称 (可叠加 IP)；加前缀避免与匿名主体重名，更换 Key 的密钥后用量延续
+    fn subject(key_name: Option<&str>, ip: Option<IpAddr>, per_ip: bool) -> (String, String) {
+        let (mut id, mut label) = match key_name.filter(|k| !k.is_empty()) {
+            Some(name) => (format!("key:{}", name), name.to_string()),
+            None => (ANONYMOUS.to_string(), ANONYMOUS.to_string()),
+        };
+        if let (true, Some(ip)) = (per_ip, ip) {
+            id = format!("{}@{}", id, ip);
+            label = format!("{}@{}", label, ip);
+        }
+        (id, label)
+    }
+
+    /// 丢弃已补满的令牌桶与无当期用量的预算
+    fn prune(&self, now: Instant, wall: DateTime<Utc>) {
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+        let before = self.budgets.len();
+        self.budgets.retain(|_, usage| !usage.is_stale(wall));
+        if self.budgets.len() != before {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// 检查并占用一次请求配额，通过时返回计量主体 (用于之后记录 token 用量)。
+    /// `key_name` 为认证识别出的 Key 名称，未携带已配置 Key 的请求传 None
+    pub fn check(&self, key_name: Option<&str>, ip: Option<IpAddr>) -> Result<String, LimitExceeded> {
+        self.check_at(key_name, ip, Instant::now(), Utc::now())
+    }
+
+    fn check_at(
+        &self,
+        key_name: Option<&str>,
+        ip: Option<IpAddr>,
+        now: Instant,
+        wall: DateTime<Utc>,
+    ) -> Result<String, LimitExceeded> {
+        let (limit, per_ip) = {
+            let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+            let limit = key_name
+                .and_then(|k| config.keys.get(k))
+                .unwrap_or(&config.default_limits)
+                .clone();
+            (limit, config.per_ip)
+        };
+        let (mut id, mut label) = Self::subject(key_name, ip, per_ip);
+        let is_new = !self.budgets.contains_key(&id) && !self.buckets.contains_key(&id);
+        if is_new && self.budgets.len().max(self.buckets.len()) >= MAX_SUBJECTS {
+            self.prune(now, wall);
+            if self.budgets.len().max(self.buckets.len()) >= MAX_SUBJECTS {
+                (id, label) = Self::subject(key_name, None, false);
+            }
+        }
+
+        // 1. token 预算 (先于请求数检查，预算耗尽时不消耗令牌桶)
+        if limit.tokens_per_day > 0 || limit.tokens_per_month > 0 {
+            let mut usage = self.budgets.entry(id.clone()).or_default();
+            usage.label = label;
+            usage.roll_over(wall);
+            if limit.tokens_per_day > 0 && usage.day_tokens >= limit.tokens_per_day {
+                return Err(LimitExceeded {
+                    kind: LimitKind::DailyTokens,
+                    retry_after: seconds_until_next_day(wall),
+                    message: format!(
+                        "Daily token budget exhausted ({} / {} tokens)",
+                        usage.day_tokens, limit.tokens_per_day
+                    ),
+                });
+            }
+            if limit.tokens_per_month > 0 && usage.month_tokens >= limit.tokens_per_month {
+                return Err(LimitExceeded {
+                    kind: LimitKind::MonthlyTokens,
+                    retry_after: seconds_until_next_month(wall),
+                    message: format!(
+                        "Monthly token budget exhausted ({} / {} tokens)",
+                        usage.month_tokens, limit.tokens_per_month
+                    ),
+                });
+            }
+        }
+
+        // 2. 每分钟请求数 (令牌桶)
+        if limit.requests_per_minute > 0 {
+            let capacity = if limit.burst > 0 { limit.burst } else { limit.requests_per_minute } as f64;
+            let rate_per_sec = limit.requests_per_minute as f64 / 60.0;
+            let mut bucket = self
+                .buckets
+                .entry(id.clone())
+                .or_insert(Bucket { tokens: capacity, updated: now, full_at: now });
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate_per_sec).min(capacity);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                let retry_after = ((1.0 - bucket.tokens) / rate_per_sec).ceil().max(1.0) as u64;
+                return Err(LimitExceeded {
+                    kind: LimitKind::Requests,
+                    retry_after,
+                    message: format!(
+                        "Rate limit of {} requests per minute exceeded",
+                        limit.requests_per_minute
+                    ),
+                });
+            }
+            bucket.tokens -= 1.0;
+            bucket.full_at = now + Duration::from_secs_f64((capacity - bucket.tokens) / rate_per_sec);
+        }
+
+        Ok(id)
+    }
+
+    /// 记录一次请求消耗的 token (在响应结束后调用)
+    pub fn record_tokens(&self, subject: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        {
+            let mut usage = self.budgets.entry(subject.to_string()).or_default();
+            usage.roll_over(Utc::now());
+            usage.day_tokens += tokens;
+            usage.month_tokens += tokens;
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+
+        let due = {
+            let mut last = self.last_saved.lock().unwrap_or_else(|e| e.into_inner());
+            if last.elapsed() >= SAVE_INTERVAL {
+                *last = Instant::now();
+                true
+            } else {
+                false
+            }
+        };
+        if due {
+            if let Err(e) = self.save() {
+                tracing::warn!("Failed to save key budgets: {}", e);
+            }
+        }
+    }
+
+    /// 当前所有计量主体的用量
+    pub fn usage(&self) -> HashMap<String, BudgetUsage> {
+        let now = Utc::now();
+        self.budgets
+            .iter()
+            .map(|e| {
+                let mut usage = e.value().clone();
+                usage.roll_over(now);
+                (e.key().clone(), usage)
+            })
+            .collect()
+    }
+
+    /// 清零指定主体 (None 表示全部) 的预算用量
+    pub fn reset(&self, subject: Option<&str>) -> Result<(), String> {
+        match subject {
+            Some(s) => {
+                self.budgets.remove(s);
+                self.buckets.remove(s);
+            }
+            None => {
+                self.budgets.clear();
+                self.buckets.clear();
+            }
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+        self.save()
+    }
+
+    /// 将有变更的预算写入磁盘
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let snapshot: HashMap<String, BudgetUsage> =
+            self.budgets.iter().map(|e| (e.key().clone(), e.value().clone())).collect();
+        let content = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, content).map_err(|e| format!("写入预算文件失败: {}", e))?;
+        std::fs::rename(&temp, path).map_err(|e| format!("写入预算文件失败: {}", e))
+    }
+}
+
+impl Drop for KeyLimiter {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            tracing::warn!("Failed to save key budgets: {}", e);
+        }
+    }
+}
+
+fn seconds_until_next_day(now: DateTime<Utc>) -> u64 {
+    let next = (now.date_naive() + chrono::Days::new(1)).and_hms_opt(0, 0, 0).unwrap_or_default();
+    (Utc.from_utc_datetime(&next) - now).num_seconds().max(1) as u64
+}
+
+fn seconds_until_next_month(now: DateTime<Utc>) -> u64 {
+    let (year, month) = if now.month() == 12 { (now.year() + 1, 1) } else { (now.year(), now.month() + 1) };
+    Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .map(|next| (next - now).num_seconds().max(1) as u64)
+        .unwrap_or(86_400)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(limit: KeyLimit) -> KeyLimiter {
+        KeyLimiter::new(
+            KeyLimitsConfig { enabled: true, default_limits: limit, ..Default::default() },
+            None,
+        )
+    }
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let limiter = limiter(KeyLimit { requests_per_minute: 60, burst: 2, ..Default::default() });
+        let start = Instant::now();
+        let wall = Utc::now();
+        assert!(limiter.check_at(Some("alice"), None, start, wall).is_ok());
+        assert!(limiter.check_at(Some("alice"), None, start, wall).is_ok());
+        let err = limiter.check_at(Some("alice"), None, start, wall).unwrap_err();
+        assert_eq!(err.kind, LimitKind::Requests);
+        assert_eq!(err.retry_after, 1);
+        // 其它 Key 不受影响
+        assert!(limiter.check_at(Some("bob"), None, start, wall).is_ok());
+        // 1 秒后补充一个令牌
+        assert!(limiter.check_at(Some("alice"), None, start + Duration::from_secs(1), wall).is_ok());
+    }
+
+    #[test]
+    fn test_daily_budget_and_rollover() {
+        let limiter = limiter(KeyLimit { tokens_per_day: 100, ..Default::default() });
+        let now = Instant::now();
+        let day1 = Utc.with_ymd_and_hms(2026, 1, 31, 23, 0, 0).unwrap();
+        let subject = limiter.check_at(Some("alice"), None, now, day1).unwrap();
+        limiter.budgets.get_mut(&subject).unwrap().day_tokens = 100;
+
+        let err = limiter.check_at(Some("alice"), None, now, day1).unwrap_err();
+        assert_eq!(err.kind, LimitKind::DailyTokens);
+        assert_eq!(err.retry_after, 3600);
+
+        let day2 = Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 1).unwrap();
+        assert!(limiter.check_at(Some("alice"), None, now, day2).is_ok());
+    }
+
+    #[test]
+    fn test_per_key_override_and_per_ip() {
+        let mut config = KeyLimitsConfig {
+            enabled: true,
+            per_ip: true,
+            default_limits: KeyLimit { requests_per_minute: 1, ..Default::default() },
+            ..Default::default()
+        };
+        config.keys.insert("vip".to_string(), KeyLimit::default());
+        let limiter = KeyLimiter::new(config, None);
+        let (now, wall) = (Instant::now(), Utc::now());
+        let ip_a: IpAddr = "10.0.0.1".parse().unwrap();
+        let ip_b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        assert!(limiter.check_at(Some("alice"), Some(ip_a), now, wall).is_ok());
+        assert!(limiter.check_at(Some("alice"), Some(ip_a), now, wall).is_err());
+        assert!(limiter.check_at(Some("alice"), Some(ip_b), now, wall).is_ok());
+        for _ in 0..5 {
+            assert!(limiter.check_at(Some("vip"), Some(ip_a), now, wall).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_validate_key_names() {
+        let mut config = KeyLimitsConfig::default();
+        config.keys.insert(PRIMARY_KEY_NAME.to_string(), KeyLimit::default());
+        config.keys.insert("alice".to_string(), KeyLimit::default());
+        let alice = ClientApiKey { name: "alice".to_string(), key: "sk-alice".to_string(), group: None };
+        assert!(config.validate(&[alice]).is_ok());
+        assert!(config.validate(&[]).unwrap_err().contains("alice"));
+    }
+
+    #[test]
+    fn test_set_config_keeps_buckets_unless_limits_change() {
+        let limiter = limiter(KeyLimit { requests_per_minute: 1, ..Default::default() });
+        let (now, wall) = (Instant::now(), Utc::now());
+        assert!(limiter.check_at(None, None, now, wall).is_ok());
+
+        // 无关设置变化 (如保存其它配置) 不补满令牌桶
+        let mut config = limiter.config.read().unwrap().clone();
+        limiter.set_config(config.clone());
+        assert!(limiter.check_at(None, None, now, wall).is_err());
+
+        config.default_limits.requests_per_minute = 2;
+        limiter.set_config(config);
+        assert!(limiter.check_at(None, None, now, wall).is_ok());
+    }
+
+    #[test]
+    fn test_subjects_are_capped() {
+        let limiter = KeyLimiter::new(
+            KeyLimitsConfig {
+                enabled: true,
+                per_ip: true,
+                default_limits: KeyLimit { requests_per_minute: 60, ..Default::default() },
+                ..Default::default()
+            },
+            None,
+        );
+        let (now, wall) = (Instant::now(), Utc::now());
+        for i in 0..MAX_SUBJECTS as u32 + 10 {
+            let ip = IpAddr::from(std::net::Ipv4Addr::from(0x0a00_0000 + i));
+            limiter.check_at(None, Some(ip), now, wall).unwrap();
+        }
+        // 超出上限的 IP 合并到不区分 IP 的主体
+        assert_eq!(limiter.buckets.len(), MAX_SUBJECTS + 1);
+        assert!(limiter.buckets.contains_key(ANONYMOUS));
+
+        // 令牌桶补满后回收
+        let later = now + Duration::from_secs(60);
+        limiter.check_at(None, Some("10.1.0.0".parse().unwrap()), later, wall).unwrap();
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_budgets_persist() {
+        let path = std::env::temp_dir().join(format!("key-budgets-{}.json", uuid::Uuid::new_v4()));
+        let limit = KeyLimit { tokens_per_month: 1000, ..Default::default() };
+        let config = KeyLimitsConfig { enabled: true, default_limits: limit, ..Default::default() };
+        {
+            let limiter = KeyLimiter::new(config.clone(), Some(path.clone()));
+            let subject = limiter.check(Some("alice"), None).unwrap();
+            limiter.record_tokens(&subject, 1200);
+        }
+        let limiter = KeyLimiter::new(config, Some(path.clone()));
+        let _ = std::fs::remove_file(&path);
+        let err = limiter.check(Some("alice"), None).unwrap_err();
+        assert_eq!(err.kind, LimitKind::MonthlyTokens);
+    }
+}
@@ -18,15 +18,16 @@ use std::pin::Pin;
 
 /// 创建从 Gemini SSE 流到 Claude SSE 流的转换
 pub fn create_claude_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
     trace_id: String,
     email: String,
+    keepalive: Option<std::time::Duration>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
     use futures::StreamExt;
 
-    Box::pin(stream! {
+    let stream = Box::pin(stream! {
         let mut state = StreamingState::new();
         let mut buffer = BytesMut::new();
 
@@ -61,7 +62,9 @@ pub fn create_claude_sse_stream(
         for chunk in emit_force_stop(&mut state) {
             yield Ok(chunk);
         }
-    })
+    });
+
+    crate::proxy::mappers::keepalive::with_keepalive(stream, keepalive, crate::proxy::mappers::keepalive::CLAUDE_PING)
 }
 
 /// 处理单行 SSE 数据
@@ -0,0 +1,61 @@
+// SSE 保活
+// 上游长时间无输出 (如思考模型的首个分块之前) 时按间隔插入心跳，
+// 避免中间代理 / 客户端的空闲超时断开连接
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Anthropic 协议的心跳事件
+pub const CLAUDE_PING: &str = "event: ping\ndata: {\"type\": \"ping\"}\n\n";
+
+/// 通用 SSE 注释行 (OpenAI / Gemini 客户端会忽略)
+pub const SSE_COMMENT: &str = ": keep-alive\n\n";
+
+/// 在输出流空闲超过 interval 时插入心跳，interval 为 None 时原样返回
+pub fn with_keepalive(
+    mut stream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
+    interval: Option<Duration>,
+    ping: &'static str,
+) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+    let Some(interval) = interval else {
+        return stream;
+    };
+
+    Box::pin(async_stream::stream! {
+        loop {
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(item)) => yield item,
+                Ok(None) => break,
+                Err(_) => yield Ok(Bytes::from_static(ping.as_bytes())),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pings_only_while_idle() {
+        let upstream = Box::pin(async_stream::stream! {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            yield Ok::<_, String>(Bytes::from("data: 1\n\n"));
+            yield Ok(Bytes::from("data: 2\n\n"));
+        });
+        let items: Vec<_> = with_keepalive(upstream, Some(Duration::from_millis(120)), CLAUDE_PING)
+            .map(|item| String::from_utf8(item.unwrap().to_vec()).unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(items, vec![CLAUDE_PING, CLAUDE_PING, "data: 1\n\n", "data: 2\n\n"]);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_passes_through() {
+        let upstream = Box::pin(futures::stream::iter(vec![Ok::<_, String>(Bytes::from("a")), Err("boom".to_string())]));
+        let items: Vec<_> = with_keepalive(upstream, None, SSE_COMMENT).collect().await;
+        assert_eq!(items, vec![Ok(Bytes::from("a")), Err("boom".to_string())]);
+    }
+}
@@ -4,5 +4,6 @@
 pub mod claude;
 pub mod common_utils;
 pub mod gemini;
+pub mod keepalive;
 pub mod openai;
 pub mod signature_store;
@@ -3,12 +3,15 @@ use bytes::{Bytes, BytesMut};
 use futures::{Stream, StreamExt};
 use serde_json::{json, Value};
 use std::pin::Pin;
+use std::time::Duration;
 use std::sync::{Mutex, OnceLock};
 use chrono::Utc;
 use uuid::Uuid;
 use tracing::debug;
 use rand::Rng;
 
+use crate::proxy::mappers::keepalive::{with_keepalive, SSE_COMMENT};
+
 // === 全局 ThoughtSignature 存储 ===
 // 用于在流式响应和后续请求之间传递签名，避免嵌入到用户可见的文本中
 static GLOBAL_THOUGHT_SIG: OnceLock<Mutex<Option<String>>> = OnceLock::new();
@@ -51,8 +54,9 @@ pub fn get_thought_signature() -> Option<String> {
 }
 
 pub fn create_openai_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
     model: String,
+    keepalive: Option<Duration>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
     
@@ -199,12 +203,13 @@ pub fn create_openai_sse_stream(
         yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
     };
 
-    Box::pin(stream)
+    with_keepalive(Box::pin(stream), keepalive, SSE_COMMENT)
 }
 
 pub fn create_legacy_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
     model: String,
+    keepalive: Option<Duration>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
     
@@ -305,12 +310,13 @@ pub fn create_legacy_sse_stream(
         tokio::time::sleep(std::time::Duration::from_millis(50)).await;
     };
 
-    Box::pin(stream)
+    with_keepalive(Box::pin(stream), keepalive, SSE_COMMENT)
 }
 
 pub fn create_codex_sse_stream(
-    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+    mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
     _model: String,
+    keepalive: Option<Duration>,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
     
@@ -771,5 +777,5 @@ pub fn create_codex_sse_stream(
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&completed_ev).unwrap())));
     };
 
-    Box::pin(stream)
+    with_keepalive(Box::pin(stream), keepalive, SSE_COMMENT)
 }
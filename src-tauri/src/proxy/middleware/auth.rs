@@ -2,19 +2,28 @@
 use axum::{
     extract::State,
     extract::Request,
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+use crate::proxy::runtime::RuntimeConfig;
+use crate::proxy::ProxyAuthMode;
+
+/// 从请求头中提取客户端 API Key (Authorization: Bearer 或 x-api-key)
+pub fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+}
 
 /// API Key 认证中间件
 pub async fn auth_middleware(
-    State(security): State<Arc<RwLock<ProxySecurityConfig>>>,
-    request: Request,
+    State(runtime): State<Arc<RuntimeConfig>>,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let method = request.method().clone();
@@ -32,9 +41,19 @@ pub async fn auth_middleware(
         return Ok(next.run(request).await);
     }
 
-    let security = security.read().await.clone();
+    let runtime = runtime.load();
+    let security = &runtime.security;
     let effective_mode = security.effective_auth_mode();
 
+    // 识别请求所用的 Key (主 API Key 或命名客户端 Key)；认证关闭时携带已配置 Key 的请求同样按 Key 路由与计量，
+    // 未匹配的 Key 由客户端任意填写，不作为身份
+    // Constant-time compare is unnecessary here, but keep strict equality and avoid leaking values.
+    let identity = extract_api_key(request.headers()).and_then(|k| security.identify(k));
+    let authorized = identity.is_some();
+    if let Some(identity) = identity {
+        request.extensions_mut().insert(identity);
+    }
+
     if matches!(effective_mode, ProxyAuthMode::Off) {
         return Ok(next.run(request).await);
     }
@@ -42,28 +61,12 @@ pub async fn auth_middleware(
     if matches!(effective_mode, ProxyAuthMode::AllExceptHealth) && path == "/healthz" {
         return Ok(next.run(request).await);
     }
-    
-    // 从 header 中提取 API key
-    let api_key = request
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer ").or(Some(s)))
-        .or_else(|| {
-            request
-                .headers()
-                .get("x-api-key")
-                .and_then(|h| h.to_str().ok())
-        });
 
-    if security.api_key.is_empty() {
-        tracing::error!("Proxy auth is enabled but api_key is empty; denying request");
+    if !security.has_keys() {
+        tracing::error!("Proxy auth is enabled but no api_key is configured; denying request");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    // Constant-time compare is unnecessary here, but keep strict equality and avoid leaking values.
-    let authorized = api_key.map(|k| k == security.api_key).unwrap_or(false);
-
     if authorized {
         Ok(next.run(request).await)
     } else {
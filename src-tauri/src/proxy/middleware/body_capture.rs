@@ -0,0 +1,221 @@
+// 报文采集 (tee)
+// 报文原样转发给下游，同时最多记录前 N 字节用于日志，不再因报文过大而替换为空 body
+// 响应体由连接任务直接拉取，不经独立任务转发：客户端断开或停止服务取消连接时上游流随之释放
+use axum::body::Body;
+use bytes::Bytes;
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+
+use crate::modules::proxy_log_storage::{redact_base64, truncate_captured_body};
+
+/// 开启 base64 脱敏时，原始报文按存储上限的该倍数采集 (脱敏后通常会大幅缩短)
+const REDACT_CAPTURE_FACTOR: usize = 8;
+
+#[derive(Default)]
+struct CaptureBuf {
+    bytes: Vec<u8>,
+    /// 已经过的总字节数 (含未记录部分)
+    total: usize,
+}
+
+/// 报文采集缓冲区，可在多个任务间共享
+#[derive(Clone)]
+pub struct BodyCapture {
+    inner: Arc<Mutex<CaptureBuf>>,
+    /// 原始字节的采集上限
+    raw_cap: usize,
+    /// 写入日志的上限 (字节)
+    limit: usize,
+    redact: bool,
+}
+
+impl BodyCapture {
+    pub fn new(limit: usize, redact: bool) -> Self {
+        let raw_cap = if redact {
+            limit.saturating_mul(REDACT_CAPTURE_FACTOR)
+        } else {
+            limit
+        };
+        Self {
+            inner: Arc::new(Mutex::new(CaptureBuf::default())),
+            raw_cap,
+            limit,
+            redact,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CaptureBuf> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 记录一段经过的数据 (超出上限的部分只计数)
+    pub fn record(&self, chunk: &[u8]) {
+        let mut buf = self.lock();
+        buf.total += chunk.len();
+        let room = self.raw_cap.saturating_sub(buf.bytes.len());
+        if room > 0 {
+            buf.bytes.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        }
+    }
+
+    /// 包装 body: 数据原样转发，同时记录到缓冲区
+    pub fn tee(&self, body: Body) -> Body {
+        let capture = self.clone();
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                capture.record(bytes);
+            }
+            chunk
+        }))
+    }
+
+    /// 报文是否被完整记录
+    pub fn is_complete(&self) -> bool {
+        let buf = self.lock();
+        buf.total == buf.bytes.len()
+    }
+
+    /// 已记录的原始字节
+    pub fn bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(&self.lock().bytes)
+    }
+
+    /// 生成写入日志的文本 (脱敏并截断到上限)；未经过任何数据时返回 None
+    pub fn text(&self, binary_placeholder: &str) -> Option<String> {
+        let buf = self.lock();
+        if buf.total == 0 {
+            return None;
+        }
+        let truncated = buf.total > buf.bytes.len();
+        let text = match std::str::from_utf8(&buf.bytes) {
+            Ok(s) => s,
+            // 截断位置恰好落在多字节字符中间
+            Err(e) if truncated && e.error_len().is_none() => {
+                std::str::from_utf8(&buf.bytes[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return Some(binary_placeholder.to_string()),
+        };
+        let uncaptured = buf.total - text.len();
+        let text = if self.redact {
+            redact_base64(text).into_owned()
+        } else {
+            text.to_string()
+        };
+        truncate_captured_body(text, self.limit, uncaptured)
+    }
+}
+
+/// 响应体结束或被丢弃时执行一次收尾
+struct OnEnd<S, F: FnOnce(S, bool)> {
+    state: Option<(S, F)>,
+    completed: bool,
+}
+
+impl<S, F: FnOnce(S, bool)> Drop for OnEnd<S, F> {
+    fn drop(&mut self) {
+        if let Some((state, on_end)) = self.state.take() {
+            on_end(state, self.completed);
+        }
+    }
+}
+
+/// 包装响应 body: 每个分块经 on_chunk 观察后原样转发；
+/// 读完或被丢弃 (客户端断开 / 停止服务取消连接) 时调用 on_end，第二个参数表示是否读完
+pub fn observe_body<S, C, E>(body: Body, state: S, mut on_chunk: C, on_end: E) -> Body
+where
+    S: Send + 'static,
+    C: FnMut(&mut S, &Result<Bytes, axum::Error>) + Send + 'static,
+    E: FnOnce(S, bool) + Send + 'static,
+{
+    let mut stream = body.into_data_stream();
+    Body::from_stream(async_stream::stream! {
+        let mut guard = OnEnd { state: Some((state, on_end)), completed: false };
+        while let Some(chunk) = stream.next().await {
+            if let Some((state, _)) = guard.state.as_mut() {
+                on_chunk(state, &chunk);
+            }
+            yield chunk;
+        }
+        guard.completed = true;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tee_forwards_full_body_and_caps_capture() {
+        let payload = "x".repeat(10_000);
+        let capture = BodyCapture::new(1024, false);
+        let forwarded = axum::body::to_bytes(capture.tee(Body::from(payload.clone())), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(forwarded.len(), payload.len());
+        assert!(!capture.is_complete());
+
+        let text = capture.text("[Binary]").unwrap();
+        assert!(text.len() <= 1024);
+        assert!(text.ends_with("bytes]"));
+    }
+
+    #[test]
+    fn test_redacted_capture_keeps_text_after_image() {
+        let body = format!(
+            r#"{{"image":"data:image/png;base64,{}","prompt":"describe"}}"#,
+            "A".repeat(4000)
+        );
+        let capture = BodyCapture::new(1024, true);
+        capture.record(body.as_bytes());
+        assert!(capture.is_complete());
+        assert_eq!(
+            capture.text("[Binary]").unwrap(),
+            r#"{"image":"[base64 image/png, 3000 bytes]","prompt":"describe"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_observe_body_finishes_once() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let chunks = || futures::stream::iter(vec![Ok::<_, std::io::Error>(Bytes::from("a")), Ok(Bytes::from("b"))]);
+        let (tx, rx) = std::sync::mpsc::channel();
+        let body = observe_body(Body::from_stream(chunks()), 0usize, |n, _| *n += 1, move |n, done| {
+            tx.send((n, done)).unwrap()
+        });
+        assert_eq!(axum::body::to_bytes(body, usize::MAX).await.unwrap(), "ab");
+        assert_eq!(rx.try_recv().unwrap(), (2, true));
+
+        // 读取一个分块后丢弃 (客户端断开)：收尾只执行一次，上游流随之释放
+        struct SetOnDrop(Arc<AtomicBool>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+        let released = Arc::new(AtomicBool::new(false));
+        let guard = SetOnDrop(released.clone());
+        let upstream = chunks().chain(futures::stream::pending()).map(move |chunk| {
+            let _ = &guard;
+            chunk
+        });
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut stream = observe_body(Body::from_stream(upstream), 0usize, |n, _| *n += 1, move |n, done| {
+            tx.send((n, done)).unwrap()
+        })
+        .into_data_stream();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "a");
+        drop(stream);
+        assert!(released.load(Ordering::SeqCst));
+        assert_eq!(rx.try_recv().unwrap(), (1, false));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_binary_and_empty_bodies() {
+        let capture = BodyCapture::new(1024, false);
+        assert!(capture.text("[Binary]").is_none());
+        capture.record(&[0xff, 0xfe, 0x00]);
+        assert_eq!(capture.text("[Binary]").as_deref(), Some("[Binary]"));
+    }
+}
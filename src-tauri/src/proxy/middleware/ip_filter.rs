@@ -0,0 +1,35 @@
+// 客户端 IP 访问控制中间件 (在认证之前执行)
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::proxy::runtime::RuntimeConfig;
+
+pub async fn ip_filter_middleware(
+    State(runtime): State<Arc<RuntimeConfig>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    // 进程内调用 (如请求重放) 没有连接信息，直接放行
+    let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        return Ok(next.run(request).await);
+    };
+
+    let allowed = runtime.load().security.ip_filter.is_allowed(peer.ip());
+    if allowed {
+        Ok(next.run(request).await)
+    } else {
+        tracing::warn!(
+            "[IpFilter] Blocked {} {} {}",
+            peer.ip(),
+            request.method(),
+            request.uri().path()
+        );
+        Err(StatusCode::FORBIDDEN)
+    }
+}
@@ -0,0 +1,167 @@
+// 按 API Key / 客户端 IP 的限流与预算中间件
+// 请求前检查令牌桶与 token 预算，超限时按请求协议返回 429 + Retry-After；
+// 响应结束后从 usage 中统计 token 用量计入预算
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::proxy::key_limits::{KeyLimiter, LimitExceeded, LimitKind};
+use crate::proxy::client_keys::AuthenticatedKey;
+use crate::proxy::middleware::body_capture::{observe_body, BodyCapture};
+use crate::proxy::middleware::stream_capture::{usage_from_json, StreamCapture};
+use crate::proxy::server::AppState;
+
+/// 统计非流式响应用量时最多缓存的字节数
+const USAGE_CAPTURE_LIMIT: usize = 1024 * 1024;
+
+pub async fn key_limits_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limiter = state.key_limiter.clone();
+    let path = request.uri().path().to_string();
+    if !limiter.is_enabled()
+        || request.method() == Method::OPTIONS
+        || path == "/healthz"
+        || path.starts_with("/admin/")
+        || path.contains("event_logging")
+    {
+        return next.run(request).await;
+    }
+
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0.ip());
+    // 只按认证识别出的 Key 名称计量；未匹配已配置 Key 的请求由客户端任意填写，不能作为独立计量主体
+    let key_name = request.extensions().get::<AuthenticatedKey>().map(|k| k.name.as_str());
+    let subject = match limiter.check(key_name, ip) {
+        Ok(subject) => subject,
+        Err(exceeded) => {
+            tracing::warn!("[KeyLimits] {} {} rejected: {}", request.method(), path, exceeded.message);
+            return limit_response(&path, &exceeded);
+        }
+    };
+
+    let response = next.run(request).await;
+    track_usage(response, limiter, subject)
+}
+
+/// 包装响应体，在响应结束后记录 token 用量
+fn track_usage(response: Response, limiter: Arc<KeyLimiter>, subject: String) -> Response {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let is_stream = content_type.contains("text/event-stream");
+    if !is_stream && !content_type.contains("application/json") {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let sse = is_stream.then(StreamCapture::new);
+    let json = (!is_stream).then(|| BodyCapture::new(USAGE_CAPTURE_LIMIT, false));
+
+    // 客户端提前断开时按已输出部分中的 usage 计入
+    let body = observe_body(
+        body,
+        (sse, json),
+        |(sse, json), chunk| {
+            if let Ok(bytes) = chunk {
+                match (sse, json) {
+                    (Some(capture), _) => {
+                        capture.feed(bytes);
+                    }
+                    (None, Some(capture)) => capture.record(bytes),
+                    _ => {}
+                }
+            }
+        },
+        move |(sse, json), _completed| {
+            let (input, output) = match (sse, json) {
+                (Some(capture), _) => {
+                    let summary = capture.finish();
+                    (summary.input_tokens, summary.output_tokens)
+                }
+                (None, Some(capture)) if capture.is_complete() => serde_json::from_slice::<Value>(&capture.bytes())
+                    .map(|v| usage_from_json(&v))
+                    .unwrap_or_default(),
+                _ => (None, None),
+            };
+            let tokens = input.unwrap_or(0) as u64 + output.unwrap_or(0) as u64;
+            limiter.record_tokens(&subject, tokens);
+        },
+    );
+
+    Response::from_parts(parts, body)
+}
+
+/// 按请求路径对应的协议构造 429 错误体
+fn limit_response(path: &str, exceeded: &LimitExceeded) -> Response {
+    let body = if path.starts_with("/v1/messages") {
+        // Anthropic
+        json!({
+            "type": "error",
+            "error": { "type": "rate_limit_error", "message": exceeded.message }
+        })
+    } else if path.starts_with("/v1beta/") {
+        // Gemini (Google API)
+        json!({
+            "error": { "code": 429, "message": exceeded.message, "status": "RESOURCE_EXHAUSTED" }
+        })
+    } else {
+        // OpenAI
+        json!({
+            "error": {
+                "message": exceeded.message,
+                "type": match exceeded.kind {
+                    LimitKind::Requests => "requests",
+                    LimitKind::DailyTokens | LimitKind::MonthlyTokens => "tokens",
+                },
+                "param": null,
+                "code": "rate_limit_exceeded"
+            }
+        })
+    };
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(exceeded.retry_after));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limit_response_shapes() {
+        let exceeded = LimitExceeded {
+            kind: LimitKind::Requests,
+            retry_after: 7,
+            message: "slow down".to_string(),
+        };
+        for (path, pointer, expected) in [
+            ("/v1/messages", "/error/type", "rate_limit_error"),
+            ("/v1beta/models/gemini-pro:generateContent", "/error/status", "RESOURCE_EXHAUSTED"),
+            ("/v1/chat/completions", "/error/code", "rate_limit_exceeded"),
+        ] {
+            let response = limit_response(path, &exceeded);
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let json: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json.pointer(pointer).and_then(|v| v.as_str()), Some(expected), "{}", path);
+        }
+    }
+}
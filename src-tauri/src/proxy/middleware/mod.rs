@@ -1,9 +1,14 @@
 // Middleware 模块 - Axum 中间件
 
 pub mod auth;
+pub mod body_capture;
 pub mod cors;
+pub mod ip_filter;
+pub mod key_limits;
 pub mod logging;
 pub mod monitor;
+pub mod stream_capture;
 
-pub use auth::auth_middleware;
+pub use auth::{auth_middleware, extract_api_key};
 pub use cors::cors_layer;
+pub use ip_filter::ip_filter_middleware;
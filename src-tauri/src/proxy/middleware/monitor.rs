@@ -2,13 +2,19 @@ use axum::{
     extract::{Request, State},
     middleware::Next,
     response::Response,
-    body::Body,
 };
 use std::time::Instant;
 use crate::proxy::server::AppState;
-use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::client_keys::AuthenticatedKey;
+use crate::proxy::middleware::auth::extract_api_key;
+use crate::proxy::middleware::body_capture::{observe_body, BodyCapture};
+use crate::proxy::middleware::stream_capture::{usage_from_json, StreamCapture};
+use crate::proxy::monitor::{mask_api_key, ProxyMonitor, ProxyRequestLog, RequestAttribution};
 use serde_json::Value;
-use futures::StreamExt;
+use std::sync::Arc;
+
+/// 客户端在响应结束前断开 (或停止服务时连接被取消)
+const CLIENT_DISCONNECTED: &str = "Client disconnected before the response completed";
 
 pub async fn monitor_middleware(
     State(state): State<AppState>,
@@ -21,9 +27,16 @@ pub async fn monitor_middleware(
 
     let start = Instant::now();
     let method = request.method().to_string();
+    // 识别出的 Key 记录名称，便于按 Key 统计用量；其余 (认证关闭时的未知 Key) 脱敏记录
+    let api_key = request
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .map(|k| k.name.clone())
+        .or_else(|| extract_api_key(request.headers()).filter(|k| !k.is_empty()).map(mask_api_key));
     let uri = request.uri().to_string();
     
-    if uri.contains("event_logging") {
+    // 管理接口的请求不记录，避免日志检索结果再次写入日志
+    if uri.contains("event_logging") || uri.starts_with("/admin/") {
         return next.run(request).await;
     }
     
@@ -36,35 +49,28 @@ pub async fn monitor_middleware(
         None
     };
 
-    let request_body_str;
-    let request = if method == "POST" {
-        let (parts, body) = request.into_parts();
-        match axum::body::to_bytes(body, 1024 * 1024).await {
-            Ok(bytes) => {
-                if model.is_none() {
-                    model = serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
-                        v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string())
-                    );
-                }
-                request_body_str = if let Ok(s) = std::str::from_utf8(&bytes) {
-                    Some(s.to_string())
-                } else {
-                    Some("[Binary Request Data]".to_string())
-                };
-                Request::from_parts(parts, Body::from(bytes))
-            }
-            Err(_) => {
-                request_body_str = None;
-                Request::from_parts(parts, Body::empty())
-            }
+    // 请求与响应报文均以 tee 方式采集：完整转发，仅记录不超过上限的前缀
+    let storage = state.monitor.storage_config();
+    let request_capture = (method == "POST").then(|| {
+        BodyCapture::new(storage.request_body_limit_kb * 1024, storage.redact_base64)
+    });
+    let request = match &request_capture {
+        Some(capture) => {
+            let (parts, body) = request.into_parts();
+            Request::from_parts(parts, capture.tee(body))
         }
-    } else {
-        request_body_str = None;
-        request
+        None => request,
     };
-    
+
     let response = next.run(request).await;
-    
+
+    let request_body_str = request_capture.as_ref().and_then(|capture| {
+        if model.is_none() {
+            model = extract_model(capture);
+        }
+        capture.text("[Binary Request Data]")
+    });
+
     let duration = start.elapsed().as_millis() as u64;
     let status = response.status().as_u16();
     
@@ -74,8 +80,13 @@ pub async fn monitor_middleware(
         .to_string();
 
     let monitor = state.monitor.clone();
+    // 重放请求使用预分配的日志 ID，并关联原始日志
+    let replay = crate::proxy::replay::current();
     let mut log = ProxyRequestLog {
-        id: uuid::Uuid::new_v4().to_string(),
+        id: replay
+            .as_ref()
+            .map(|r| r.log_id.clone())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
         timestamp: chrono::Utc::now().timestamp_millis(),
         method,
         url: uri,
@@ -87,91 +98,108 @@ pub async fn monitor_middleware(
         response_body: None,
         input_tokens: None,
         output_tokens: None,
+        api_key,
+        replay_of: replay.map(|r| r.source_id),
+        ..Default::default()
     };
 
+    // 合并处理器通过响应扩展传递的归因信息 (账号 / 映射模型 / 提供方 / 重试)
+    if let Some(attribution) = response.extensions().get::<RequestAttribution>() {
+        attribution.apply_to(&mut log);
+    }
+
     if content_type.contains("text/event-stream") {
-        log.response_body = Some("[Stream Data]".to_string());
         let (parts, body) = response.into_parts();
-        let mut stream = body.into_data_stream();
-        let (tx, rx) = tokio::sync::mpsc::channel(64);
-        
-        tokio::spawn(async move {
-            let mut last_few_bytes = Vec::new();
-            while let Some(chunk_res) = stream.next().await {
-                if let Ok(chunk) = chunk_res {
-                    if chunk.len() > 8192 {
-                        last_few_bytes = chunk.slice(chunk.len()-8192..).to_vec();
-                    } else {
-                        last_few_bytes.extend_from_slice(&chunk);
-                        if last_few_bytes.len() > 8192 {
-                            last_few_bytes.drain(0..last_few_bytes.len()-8192);
-                        }
+        // 边转发边解析 SSE 事件，还原最终的助手消息
+        let body = observe_body(
+            body,
+            (StreamCapture::new(), None::<String>, log),
+            move |(capture, stream_error, log), chunk| match chunk {
+                Ok(chunk) => {
+                    if capture.feed(chunk) {
+                        log.ttft_ms = Some(start.elapsed().as_millis() as u64);
                     }
-                    let _ = tx.send(Ok::<_, axum::Error>(chunk)).await;
-                } else if let Err(e) = chunk_res {
-                    let _ = tx.send(Err(axum::Error::new(e))).await;
                 }
-            }
-            
-            if let Ok(full_tail) = std::str::from_utf8(&last_few_bytes) {
-                for line in full_tail.lines().rev() {
-                    if line.starts_with("data: ") && line.contains("\"usage\"") {
-                        let json_str = line.trim_start_matches("data: ").trim();
-                        if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                            if let Some(usage) = json.get("usage") {
-                                log.input_tokens = usage.get("prompt_tokens").or(usage.get("input_tokens")).and_then(|v| v.as_u64()).map(|v| v as u32);
-                                log.output_tokens = usage.get("completion_tokens").or(usage.get("output_tokens")).and_then(|v| v.as_u64()).map(|v| v as u32);
-                                if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                    log.output_tokens = usage.get("total_tokens").and_then(|v| v.as_u64()).map(|v| v as u32);
-                                }
-                                break;
-                            }
-                        }
-                    }
+                Err(e) => *stream_error = Some(e.to_string()),
+            },
+            move |(capture, stream_error, mut log), completed| {
+                log.stream_duration_ms = Some(start.elapsed().as_millis() as u64);
+
+                let summary = capture.finish();
+                log.input_tokens = summary.input_tokens;
+                log.output_tokens = summary.output_tokens;
+                if let Some(e) = stream_error.or_else(|| summary.error.clone()) {
+                    log.error = Some(e);
+                } else if !completed {
+                    log.error = Some(CLIENT_DISCONNECTED.to_string());
+                } else if log.status >= 400 {
+                    log.error = Some("Stream Error or Failed".to_string());
                 }
-            }
-            
-            if log.status >= 400 {
-                log.error = Some("Stream Error or Failed".to_string());
-            }
-            monitor.log_request(log).await;
-        });
-
-        Response::from_parts(parts, Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx)))
+                log.response_body = Some(
+                    serde_json::to_string(&summary).unwrap_or_else(|_| "[Stream Data]".to_string()),
+                );
+                spawn_log(monitor, log);
+            },
+        );
+
+        Response::from_parts(parts, body)
     } else if content_type.contains("application/json") || content_type.contains("text/") {
         let (parts, body) = response.into_parts();
-        match axum::body::to_bytes(body, 512 * 1024).await {
-            Ok(bytes) => {
-                if let Ok(s) = std::str::from_utf8(&bytes) {
-                    if let Ok(json) = serde_json::from_str::<Value>(&s) {
-                        if let Some(usage) = json.get("usage") {
-                            log.input_tokens = usage.get("prompt_tokens").or(usage.get("input_tokens")).and_then(|v| v.as_u64()).map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens").or(usage.get("output_tokens")).and_then(|v| v.as_u64()).map(|v| v as u32);
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens").and_then(|v| v.as_u64()).map(|v| v as u32);
-                            }
-                        }
+        let capture = BodyCapture::new(storage.response_body_limit_kb * 1024, storage.redact_base64);
+
+        // 响应原样转发，结束后再写日志，超出上限的部分只计数不缓存
+        let body = observe_body(
+            body,
+            (capture, log),
+            |(capture, log), chunk| match chunk {
+                Ok(chunk) => capture.record(chunk),
+                Err(e) => log.error = Some(e.to_string()),
+            },
+            move |(capture, mut log), completed| {
+                // 仅在完整采集时解析 usage，截断的 JSON 无法解析
+                if capture.is_complete() {
+                    if let Ok(json) = serde_json::from_slice::<Value>(&capture.bytes()) {
+                        (log.input_tokens, log.output_tokens) = usage_from_json(&json);
                     }
-                    log.response_body = Some(s.to_string());
-                } else {
-                    log.response_body = Some("[Binary Response Data]".to_string());
                 }
-                
-                if log.status >= 400 {
+                log.response_body = capture.text("[Binary Response Data]");
+
+                if !completed && log.error.is_none() {
+                    log.error = Some(CLIENT_DISCONNECTED.to_string());
+                }
+                if log.status >= 400 && log.error.is_none() {
                     log.error = log.response_body.clone();
                 }
-                monitor.log_request(log).await;
-                Response::from_parts(parts, Body::from(bytes))
-            }
-            Err(_) => {
-                log.response_body = Some("[Response too large]".to_string());
-                monitor.log_request(log).await;
-                Response::from_parts(parts, Body::empty())
-            }
-        }
+                spawn_log(monitor, log);
+            },
+        );
+
+        Response::from_parts(parts, body)
     } else {
         log.response_body = Some(format!("[{}]", content_type));
         monitor.log_request(log).await;
         response
     }
 }
+
+/// 收尾可能发生在响应体被丢弃时 (非 async 上下文)，日志写入交给后台任务
+fn spawn_log(monitor: Arc<ProxyMonitor>, log: ProxyRequestLog) {
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move { monitor.log_request(log).await });
+    }
+}
+
+/// 从请求报文中提取模型名：完整采集时按 JSON 解析，否则在已采集的前缀中查找
+fn extract_model(capture: &BodyCapture) -> Option<String> {
+    let bytes = capture.bytes();
+    if capture.is_complete() {
+        return serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
+            v.get("model").and_then(|m| m.as_str()).map(|s| s.to_string())
+        );
+    }
+    static MODEL_RE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+        regex::Regex::new(r#""model"\s*:\s*"([^"]+)""#).unwrap()
+    });
+    let text = String::from_utf8_lossy(&bytes);
+    MODEL_RE.captures(&text).map(|c| c[1].to_string())
+}
@@ -0,0 +1,486 @@
+// 流式响应还原
+// 逐块解析 SSE 事件，按 Claude / OpenAI Chat / Codex (Responses) / Gemini 格式
+// 拼接出最终的助手消息 (正文、思考、工具调用、停止原因) 以及 token 用量
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// 单字段最大累积长度，防止超长流式响应占用过多内存
+const MAX_FIELD_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamFormat {
+    Claude,
+    OpenAiChat,
+    Codex,
+    Gemini,
+}
+
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct CapturedToolCall {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// 还原后的助手消息
+#[derive(Debug, Clone, Serialize, Default, PartialEq)]
+pub struct StreamSummary {
+    /// 固定为 "reconstructed_stream"，便于前端识别
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub format: Option<StreamFormat>,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub thinking: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<CapturedToolCall>,
+    pub stop_reason: Option<String>,
+    #[serde(skip)]
+    pub input_tokens: Option<u32>,
+    #[serde(skip)]
+    pub output_tokens: Option<u32>,
+    /// 流中出现的错误事件
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// SSE 增量解析器
+#[derive(Default)]
+pub struct StreamCapture {
+    buffer: Vec<u8>,
+    summary: StreamSummary,
+    /// 按索引累积的工具调用 (Claude content block / OpenAI tool_calls index)
+    tools: BTreeMap<u64, CapturedToolCall>,
+    /// 已收到首个内容 (正文 / 思考 / 工具调用)
+    has_content: bool,
+}
+
+impl StreamCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 输入一段原始字节，返回本段是否包含首个内容 token (用于计算 TTFT)
+    pub fn feed(&mut self, chunk: &[u8]) -> bool {
+        let had_content = self.has_content;
+        self.buffer.extend_from_slice(chunk);
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            if let Ok(line) = std::str::from_utf8(&line) {
+                self.handle_line(line.trim_end_matches(['\r', '\n']));
+            }
+        }
+        !had_content && self.has_content
+    }
+
+    /// 结束解析，返回还原结果
+    pub fn finish(mut self) -> StreamSummary {
+        let rest = std::mem::take(&mut self.buffer);
+        if let Ok(line) = std::str::from_utf8(&rest) {
+            self.handle_line(line.trim());
+        }
+        let mut summary = self.summary;
+        summary.kind = "reconstructed_stream";
+        summary.tool_calls.extend(self.tools.into_values());
+        summary
+    }
+
+    fn handle_line(&mut self, line: &str) {
+        let Some(data) = line.strip_prefix("data:") else {
+            return;
+        };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            return;
+        }
+        let Ok(json) = serde_json::from_str::<Value>(data) else {
+            return;
+        };
+        // v1internal 包装格式 ({"response": {...}})
+        let json = match json.get("response") {
+            Some(inner) if inner.get("candidates").is_some() => inner.clone(),
+            _ => json,
+        };
+
+        if let Some(event_type) = json.get("type").and_then(|t| t.as_str()) {
+            if event_type.starts_with("response.") {
+                self.summary.format = Some(StreamFormat::Codex);
+                self.handle_codex(event_type, &json);
+            } else {
+                self.summary.format = Some(StreamFormat::Claude);
+                self.handle_claude(event_type, &json);
+            }
+        } else if json.get("candidates").is_some() || json.get("usageMetadata").is_some() {
+            self.summary.format = Some(StreamFormat::Gemini);
+            self.handle_gemini(&json);
+        } else if json.get("choices").is_some() || json.get("usage").is_some() {
+            self.summary.format = Some(StreamFormat::OpenAiChat);
+            self.handle_openai(&json);
+        } else if let Some(error) = json.get("error") {
+            self.summary.error = Some(error_message(error));
+        }
+    }
+
+    fn handle_claude(&mut self, event_type: &str, json: &Value) {
+        match event_type {
+            "message_start" => {
+                if let Some(usage) = json.pointer("/message/usage") {
+                    self.read_usage(usage, "input_tokens", "output_tokens");
+                }
+            }
+            "content_block_start" => {
+                let index = json.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                let block = json.get("content_block").unwrap_or(&Value::Null);
+                match block.get("type").and_then(|t| t.as_str()) {
+                    Some("tool_use") | Some("server_tool_use") => {
+                        self.has_content = true;
+                        self.tools.insert(
+                            index,
+                            CapturedToolCall {
+                                id: str_field(block, "id"),
+                                name: str_field(block, "name").unwrap_or_default(),
+                                arguments: String::new(),
+                            },
+                        );
+                    }
+                    Some("text") => {
+                        if let Some(text) = block.get("text").and_then(|t| t.as_str()) {
+                            self.push_text(text);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "content_block_delta" => {
+                let index = json.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                let delta = json.get("delta").unwrap_or(&Value::Null);
+                match delta.get("type").and_then(|t| t.as_str()) {
+                    Some("text_delta") => {
+                        if let Some(text) = delta.get("text").and_then(|t| t.as_str()) {
+                            self.push_text(text);
+                        }
+                    }
+                    Some("thinking_delta") => {
+                        if let Some(thinking) = delta.get("thinking").and_then(|t| t.as_str()) {
+                            self.push_thinking(thinking);
+                        }
+                    }
+                    Some("input_json_delta") => {
+                        if let (Some(tool), Some(partial)) = (
+                            self.tools.get_mut(&index),
+                            delta.get("partial_json").and_then(|t| t.as_str()),
+                        ) {
+                            push_capped(&mut tool.arguments, partial);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = json.pointer("/delta/stop_reason").and_then(|v| v.as_str()) {
+                    self.summary.stop_reason = Some(reason.to_string());
+                }
+                if let Some(usage) = json.get("usage") {
+                    self.read_usage(usage, "input_tokens", "output_tokens");
+                }
+            }
+            "error" => {
+                self.summary.error = json.get("error").map(error_message);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_openai(&mut self, json: &Value) {
+        if let Some(choice) = json.pointer("/choices/0") {
+            let delta = choice.get("delta").unwrap_or(&Value::Null);
+            if let Some(text) = delta.get("content").and_then(|t| t.as_str()) {
+                self.push_text(text);
+            }
+            if let Some(thinking) = delta.get("reasoning_content").and_then(|t| t.as_str()) {
+                self.push_thinking(thinking);
+            }
+            if let Some(calls) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+                self.has_content = true;
+                for call in calls {
+                    let index = call.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                    let tool = self.tools.entry(index).or_default();
+                    if let Some(id) = str_field(call, "id") {
+                        tool.id = Some(id);
+                    }
+                    if let Some(name) = call.pointer("/function/name").and_then(|v| v.as_str()) {
+                        tool.name.push_str(name);
+                    }
+                    if let Some(args) = call.pointer("/function/arguments").and_then(|v| v.as_str()) {
+                        push_capped(&mut tool.arguments, args);
+                    }
+                }
+            }
+            if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+                self.summary.stop_reason = Some(reason.to_string());
+            }
+        }
+        if let Some(usage) = json.get("usage").filter(|u| !u.is_null()) {
+            self.read_usage(usage, "prompt_tokens", "completion_tokens");
+        }
+    }
+
+    fn handle_codex(&mut self, event_type: &str, json: &Value) {
+        match event_type {
+            "response.output_text.delta" => {
+                if let Some(text) = json.get("delta").and_then(|t| t.as_str()) {
+                    self.push_text(text);
+                }
+            }
+            "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+                if let Some(thinking) = json.get("delta").and_then(|t| t.as_str()) {
+                    self.push_thinking(thinking);
+                }
+            }
+            "response.output_item.done" => {
+                let item = json.get("item").unwrap_or(&Value::Null);
+                let item_type = item.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                if item_type == "message" {
+                    return;
+                }
+                let arguments = match item_type {
+                    "function_call" => str_field(item, "arguments").unwrap_or_default(),
+                    _ => item.get("action").map(|a| a.to_string()).unwrap_or_default(),
+                };
+                self.has_content = true;
+                self.summary.tool_calls.push(CapturedToolCall {
+                    id: str_field(item, "call_id"),
+                    name: str_field(item, "name").unwrap_or_else(|| item_type.to_string()),
+                    arguments,
+                });
+            }
+            "response.completed" | "response.incomplete" | "response.failed" => {
+                let response = json.get("response").unwrap_or(&Value::Null);
+                self.summary.stop_reason = str_field(response, "finish_reason")
+                    .or_else(|| str_field(response, "status"));
+                if let Some(usage) = response.get("usage") {
+                    self.read_usage(usage, "input_tokens", "output_tokens");
+                }
+                if let Some(error) = response.get("error").filter(|e| !e.is_null()) {
+                    self.summary.error = Some(error_message(error));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_gemini(&mut self, json: &Value) {
+        if let Some(candidate) = json.pointer("/candidates/0") {
+            let parts = candidate
+                .pointer("/content/parts")
+                .and_then(|p| p.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for part in &parts {
+                if let Some(call) = part.get("functionCall") {
+                    self.has_content = true;
+                    self.summary.tool_calls.push(CapturedToolCall {
+                        id: str_field(call, "id"),
+                        name: str_field(call, "name").unwrap_or_default(),
+                        arguments: call.get("args").map(|a| a.to_string()).unwrap_or_default(),
+                    });
+                } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                    if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+                        self.push_thinking(text);
+                    } else {
+                        self.push_text(text);
+                    }
+                }
+            }
+            if let Some(reason) = candidate.get("finishReason").and_then(|v| v.as_str()) {
+                self.summary.stop_reason = Some(reason.to_string());
+            }
+        }
+        if let Some(usage) = json.get("usageMetadata") {
+            self.read_usage(usage, "promptTokenCount", "candidatesTokenCount");
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.has_content = true;
+            push_capped(&mut self.summary.text, text);
+        }
+    }
+
+    fn push_thinking(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.has_content = true;
+            push_capped(&mut self.summary.thinking, text);
+        }
+    }
+
+    /// 读取用量；0 视为未提供 (部分格式会先发送占位的 0)
+    fn read_usage(&mut self, usage: &Value, input_key: &str, output_key: &str) {
+        let read = |key: &str| {
+            usage
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .filter(|v| *v > 0)
+                .map(|v| v as u32)
+        };
+        if let Some(input) = read(input_key) {
+            self.summary.input_tokens = Some(input);
+        }
+        if let Some(output) = read(output_key) {
+            self.summary.output_tokens = Some(output);
+        }
+    }
+}
+
+/// 从非流式 JSON 响应中读取 (输入, 输出) token 用量
+/// 支持 OpenAI / Claude 的 usage 与 Gemini 的 usageMetadata；只有 total 时计为输出
+pub fn usage_from_json(json: &Value) -> (Option<u32>, Option<u32>) {
+    let read = |usage: &Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| usage.get(*k).and_then(|v| v.as_u64()))
+            .map(|v| v as u32)
+    };
+    let (usage, input_keys, output_keys, total_key): (_, &[&str], &[&str], _) =
+        if let Some(usage) = json.get("usage") {
+            (usage, &["prompt_tokens", "input_tokens"], &["completion_tokens", "output_tokens"], "total_tokens")
+        } else if let Some(usage) = json.get("usageMetadata") {
+            (usage, &["promptTokenCount"], &["candidatesTokenCount"], "totalTokenCount")
+        } else {
+            return (None, None);
+        };
+    let input = read(usage, input_keys);
+    let output = read(usage, output_keys);
+    if input.is_none() && output.is_none() {
+        return (None, read(usage, &[total_key]));
+    }
+    (input, output)
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+fn error_message(error: &Value) -> String {
+    error
+        .get("message")
+        .and_then(|m| m.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| error.to_string())
+}
+
+fn push_capped(target: &mut String, text: &str) {
+    if target.len() < MAX_FIELD_BYTES {
+        target.push_str(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(events: &[&str]) -> StreamSummary {
+        let mut capture = StreamCapture::new();
+        let raw: String = events.iter().map(|e| format!("data: {}\n\n", e)).collect();
+        // 按小块输入，验证跨块拼接
+        for chunk in raw.as_bytes().chunks(7) {
+            capture.feed(chunk);
+        }
+        capture.finish()
+    }
+
+    #[test]
+    fn test_claude_stream() {
+        let summary = capture(&[
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":12,"output_tokens":0}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"hmm"}}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"Hello "}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"world"}}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_1","name":"read"}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"{\"path\":"}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":34}}"#,
+            r#"{"type":"message_stop"}"#,
+        ]);
+        assert_eq!(summary.format, Some(StreamFormat::Claude));
+        assert_eq!(summary.text, "Hello world");
+        assert_eq!(summary.thinking, "hmm");
+        assert_eq!(
+            summary.tool_calls,
+            vec![CapturedToolCall {
+                id: Some("toolu_1".to_string()),
+                name: "read".to_string(),
+                arguments: r#"{"path":"a.rs"}"#.to_string(),
+            }]
+        );
+        assert_eq!(summary.stop_reason.as_deref(), Some("tool_use"));
+        assert_eq!((summary.input_tokens, summary.output_tokens), (Some(12), Some(34)));
+    }
+
+    #[test]
+    fn test_openai_chat_stream() {
+        let summary = capture(&[
+            r#"{"object":"chat.completion.chunk","choices":[{"index":0,"delta":{"role":"assistant","reasoning_content":"think"}}]}"#,
+            r#"{"object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":"Hi"}}]}"#,
+            r#"{"object":"chat.completion.chunk","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_1","function":{"name":"ls","arguments":"{\"d\""}}]}}]}"#,
+            r#"{"object":"chat.completion.chunk","choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":":1}"}}]},"finish_reason":"tool_calls"}]}"#,
+            r#"{"object":"chat.completion.chunk","choices":[],"usage":{"prompt_tokens":5,"completion_tokens":7}}"#,
+            "[DONE]",
+        ]);
+        assert_eq!(summary.format, Some(StreamFormat::OpenAiChat));
+        assert_eq!(summary.text, "Hi");
+        assert_eq!(summary.thinking, "think");
+        assert_eq!(summary.tool_calls[0].arguments, r#"{"d":1}"#);
+        assert_eq!(summary.stop_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(summary.output_tokens, Some(7));
+    }
+
+    #[test]
+    fn test_codex_and_gemini_streams() {
+        let summary = capture(&[
+            r#"{"type":"response.created","response":{"id":"r1"}}"#,
+            r#"{"type":"response.output_text.delta","delta":"ok"}"#,
+            r#"{"type":"response.output_item.done","item":{"type":"function_call","name":"f","arguments":"{}","call_id":"c1"}}"#,
+            r#"{"type":"response.completed","response":{"status":"completed","finish_reason":"STOP","usage":{"input_tokens":0,"output_tokens":0}}}"#,
+        ]);
+        assert_eq!(summary.format, Some(StreamFormat::Codex));
+        assert_eq!(summary.text, "ok");
+        assert_eq!(summary.tool_calls.len(), 1);
+        assert_eq!(summary.stop_reason.as_deref(), Some("STOP"));
+        assert_eq!(summary.output_tokens, None);
+
+        let summary = capture(&[
+            r#"{"response":{"candidates":[{"content":{"parts":[{"text":"plan","thought":true}]}}]}}"#,
+            r#"{"candidates":[{"content":{"parts":[{"text":"Answer"},{"functionCall":{"name":"g","args":{"q":1}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":3,"candidatesTokenCount":4}}"#,
+        ]);
+        assert_eq!(summary.format, Some(StreamFormat::Gemini));
+        assert_eq!(summary.thinking, "plan");
+        assert_eq!(summary.text, "Answer");
+        assert_eq!(summary.tool_calls[0].arguments, r#"{"q":1}"#);
+        assert_eq!((summary.input_tokens, summary.output_tokens), (Some(3), Some(4)));
+    }
+
+    #[test]
+    fn test_first_content_detection() {
+        let mut capture = StreamCapture::new();
+        assert!(!capture.feed(b"data: {\"type\":\"message_start\",\"message\":{}}\n\n"));
+        assert!(capture.feed(b"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"a\"}}\n"));
+        assert!(!capture.feed(b"data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"b\"}}\n"));
+    }
+
+    #[test]
+    fn test_usage_from_json() {
+        let openai = serde_json::json!({"usage": {"prompt_tokens": 3, "completion_tokens": 5}});
+        assert_eq!(usage_from_json(&openai), (Some(3), Some(5)));
+        let gemini = serde_json::json!({"usageMetadata": {"promptTokenCount": 2, "candidatesTokenCount": 7}});
+        assert_eq!(usage_from_json(&gemini), (Some(2), Some(7)));
+        let total_only = serde_json::json!({"usage": {"total_tokens": 9}});
+        assert_eq!(usage_from_json(&total_only), (None, Some(9)));
+        assert_eq!(usage_from_json(&serde_json::json!({})), (None, None));
+    }
+}
@@ -20,6 +20,17 @@ pub mod monitor;           // 监控
 pub mod rate_limit;        // 限流跟踪
 pub mod sticky_config;     // 粘性调度配置
 pub mod session_manager;   // 会话指纹管理
+pub mod account_groups;    // 账号分组路由
+pub mod scheduling;        // 可插拔调度策略与离线模拟器
+pub mod account_watcher;   // 账号目录热加载
+pub mod replay;            // 请求日志重放
+pub mod events;            // 事件总线与通知输出
+pub mod key_limits;        // API Key 限流与用量预算
+pub mod client_keys;       // 命名的客户端 API Key
+pub mod ip_filter;         // 客户端 IP 访问控制
+pub mod tls;               // 监听 TLS 终止与证书热加载
+pub mod runtime;           // 运行时配置快照 (热更新)
+pub mod unix_socket;       // Unix domain socket 监听
 
 
 pub use config::ProxyConfig;
@@ -3,8 +3,10 @@ use std::collections::VecDeque;
 use tokio::sync::RwLock;
 use tauri::Emitter;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use crate::proxy::config::LogStorageConfig;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ProxyRequestLog {
     pub id: String,
     pub timestamp: i64,
@@ -18,6 +20,180 @@ pub struct ProxyRequestLog {
     pub response_body: Option<String>,
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    /// 实际服务该请求的账号 (z.ai 等无账号的上游为 None)
+    #[serde(default)]
+    pub account_email: Option<String>,
+    /// resolve_model_route 映射后的上游模型
+    #[serde(default)]
+    pub mapped_model: Option<String>,
+    /// 上游提供方: "google" | "zai"
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// 上游尝试次数
+    #[serde(default)]
+    pub attempts: Option<u32>,
+    /// 尝试过程中切换账号的次数
+    #[serde(default)]
+    pub rotations: Option<u32>,
+    /// 客户端使用的 API Key: 已配置的 Key 记录名称 (主 API Key 为 `default`)，其余脱敏
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// 流式响应: 从收到请求到首个内容 token 的耗时 (ms)
+    #[serde(default)]
+    pub ttft_ms: Option<u64>,
+    /// 流式响应: 从收到请求到流结束的总耗时 (ms)
+    #[serde(default)]
+    pub stream_duration_ms: Option<u64>,
+    /// 重放请求: 被重放的原始日志 ID
+    #[serde(default)]
+    pub replay_of: Option<String>,
+}
+
+/// 请求日志过滤条件 (字段均为精确匹配，None 表示不过滤)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ProxyLogFilter {
+    pub account_email: Option<String>,
+    pub model: Option<String>,
+    pub mapped_model: Option<String>,
+    pub provider: Option<String>,
+    pub api_key: Option<String>,
+    /// 只返回尝试次数不少于该值的请求 (如 2 表示发生过重试)
+    pub min_attempts: Option<u32>,
+    /// 只返回指定日志的重放结果
+    pub replay_of: Option<String>,
+}
+
+impl ProxyLogFilter {
+    /// 内存日志的过滤 (数据库不可用时的兜底)
+    pub fn matches(&self, log: &ProxyRequestLog) -> bool {
+        fn eq(expected: &Option<String>, actual: &Option<String>) -> bool {
+            expected.is_none() || expected == actual
+        }
+        eq(&self.account_email, &log.account_email)
+            && eq(&self.model, &log.model)
+            && eq(&self.mapped_model, &log.mapped_model)
+            && eq(&self.provider, &log.provider)
+            && eq(&self.api_key, &log.api_key)
+            && eq(&self.replay_of, &log.replay_of)
+            && self
+                .min_attempts
+                .is_none_or(|min| log.attempts.unwrap_or(0) >= min)
+    }
+}
+
+/// 响应状态分类
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StatusClass {
+    #[serde(rename = "2xx")]
+    Success,
+    #[serde(rename = "3xx")]
+    Redirect,
+    #[serde(rename = "4xx")]
+    ClientError,
+    #[serde(rename = "5xx")]
+    ServerError,
+    /// 所有非 2xx/3xx 的请求
+    #[serde(rename = "error")]
+    Error,
+}
+
+/// 日志检索条件 (时间范围 + 多条件过滤 + 全文检索 + 游标分页)
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LogSearchQuery {
+    #[serde(flatten)]
+    pub filter: ProxyLogFilter,
+    /// 起始时间 (毫秒, 含)
+    pub start_ms: Option<i64>,
+    /// 结束时间 (毫秒, 不含)
+    pub end_ms: Option<i64>,
+    pub status_class: Option<StatusClass>,
+    /// URL / 路由子串
+    pub url_contains: Option<String>,
+    /// 错误信息子串
+    pub error_contains: Option<String>,
+    pub min_duration_ms: Option<u64>,
+    /// 请求/响应报文全文检索
+    pub text: Option<String>,
+    /// 上一页返回的 next_cursor
+    pub cursor: Option<String>,
+    /// 每页条数 (默认 50，最大 500)
+    pub limit: Option<usize>,
+}
+
+/// 日志检索结果页
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct LogSearchPage {
+    pub logs: Vec<ProxyRequestLog>,
+    /// 为 None 表示没有更多数据
+    pub next_cursor: Option<String>,
+}
+
+/// API Key 脱敏: 保留前 6 位与后 4 位，用于日志与用量统计中区分不同客户端
+pub fn mask_api_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 12 {
+        return "****".to_string();
+    }
+    let head: String = chars[..6].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+/// 上游提供方标识
+pub const PROVIDER_GOOGLE: &str = "google";
+pub const PROVIDER_ZAI: &str = "zai";
+
+/// 处理器记录的请求归因信息
+/// 通过响应扩展 (response extensions) 传递给 monitor_middleware 并写入日志
+#[derive(Debug, Clone, Default)]
+pub struct RequestAttribution {
+    pub account_email: Option<String>,
+    pub mapped_model: Option<String>,
+    pub provider: Option<String>,
+    pub attempts: u32,
+    pub rotations: u32,
+}
+
+impl RequestAttribution {
+    /// 记录一次上游尝试 (账号变化时计为一次轮换)
+    pub fn record_attempt(&mut self, provider: &str, account_email: Option<&str>, mapped_model: &str) {
+        self.attempts += 1;
+        if let (Some(prev), Some(current)) = (self.account_email.as_deref(), account_email) {
+            if prev != current {
+                self.rotations += 1;
+            }
+        }
+        if account_email.is_some() {
+            self.account_email = account_email.map(|s| s.to_string());
+        }
+        self.provider = Some(provider.to_string());
+        self.mapped_model = Some(mapped_model.to_string());
+    }
+
+    /// 更新最终使用的上游模型 (如重试时降级)
+    pub fn set_mapped_model(&mut self, mapped_model: &str) {
+        self.mapped_model = Some(mapped_model.to_string());
+    }
+
+    /// 将归因信息附加到响应扩展中
+    pub fn attach(self, response: impl axum::response::IntoResponse) -> axum::response::Response {
+        let mut response = response.into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+
+    /// 合并到请求日志
+    pub fn apply_to(&self, log: &mut ProxyRequestLog) {
+        log.account_email = self.account_email.clone();
+        log.mapped_model = self.mapped_model.clone();
+        log.provider = self.provider.clone();
+        if self.attempts > 0 {
+            log.attempts = Some(self.attempts);
+            log.rotations = Some(self.rotations);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -32,6 +208,8 @@ pub struct ProxyMonitor {
     pub stats: RwLock<ProxyStats>,
     pub max_logs: usize,
     pub enabled: AtomicBool,
+    /// 日志存储策略 (报文截断 / 压缩 / 保留期限)
+    storage: std::sync::RwLock<LogStorageConfig>,
     app_handle: Option<tauri::AppHandle>,
 }
 
@@ -47,10 +225,19 @@ impl ProxyMonitor {
             stats: RwLock::new(ProxyStats::default()),
             max_logs,
             enabled: AtomicBool::new(false), // Default to disabled
+            storage: std::sync::RwLock::new(LogStorageConfig::default()),
             app_handle,
         }
     }
 
+    pub fn set_storage_config(&self, config: LogStorageConfig) {
+        *self.storage.write().unwrap_or_else(|e| e.into_inner()) = config;
+    }
+
+    pub fn storage_config(&self) -> LogStorageConfig {
+        self.storage.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     pub fn set_enabled(&self, enabled: bool) {
         self.enabled.store(enabled, Ordering::Relaxed);
     }
@@ -59,10 +246,12 @@ impl ProxyMonitor {
         self.enabled.load(Ordering::Relaxed)
     }
 
-    pub async fn log_request(&self, log: ProxyRequestLog) {
+    pub async fn log_request(&self, mut log: ProxyRequestLog) {
         if !self.is_enabled() {
             return;
         }
+        let storage = self.storage_config();
+        crate::modules::proxy_log_storage::prepare_log(&mut log, &storage);
         tracing::info!("[Monitor] Logging request: {} {}", log.method, log.url);
         // Update stats
         {
@@ -84,13 +273,10 @@ impl ProxyMonitor {
             logs.push_front(log.clone());
         }
 
-        // Save to DB
-        let log_to_save = log.clone();
-        tokio::spawn(async move {
-            if let Err(e) = crate::modules::proxy_db::save_log(&log_to_save) {
-                tracing::error!("Failed to save proxy log to DB: {}", e);
-            }
-        });
+        // Save to DB (交给写线程批量写入，队列满时丢弃并计入 dropped 指标)
+        if let Err(e) = crate::modules::proxy_db::enqueue_log(log.clone(), storage.compression) {
+            tracing::error!("Failed to save proxy log to DB: {}", e);
+        }
 
         // Emit event
         if let Some(app) = &self.app_handle {
@@ -98,15 +284,15 @@ impl ProxyMonitor {
         }
     }
 
-    pub async fn get_logs(&self, limit: usize) -> Vec<ProxyRequestLog> {
+    pub async fn get_logs(&self, limit: usize, filter: &ProxyLogFilter) -> Vec<ProxyRequestLog> {
         // Try to get from DB first for true history
-        match crate::modules::proxy_db::get_logs(limit) {
+        match crate::modules::proxy_db::get_logs_filtered(filter, limit) {
             Ok(logs) => logs,
             Err(e) => {
                 tracing::error!("Failed to get logs from DB: {}", e);
                 // Fallback to memory
                 let logs = self.logs.read().await;
-                logs.iter().take(limit).cloned().collect()
+                logs.iter().filter(|l| filter.matches(l)).take(limit).cloned().collect()
             }
         }
     }
@@ -131,4 +317,71 @@ impl ProxyMonitor {
             tracing::error!("Failed to clear logs in DB: {}", e);
         }
     }
-}
\ No newline at end of file
+}
+
+/// 日志定期维护任务 (保留策略清理 + 增量 VACUUM)，drop 时停止
+pub struct LogMaintenance {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl LogMaintenance {
+    pub fn start(monitor: Arc<ProxyMonitor>) -> Self {
+        let task = tokio::spawn(async move {
+            loop {
+                let config = monitor.storage_config();
+                let interval = std::time::Duration::from_secs(config.maintenance_interval_minutes.max(1) * 60);
+                match tokio::task::spawn_blocking(move || {
+                    crate::modules::proxy_db::run_maintenance(&config, false)
+                })
+                .await
+                {
+                    Ok(Ok(report)) => tracing::debug!("Proxy log maintenance finished: {:?}", report),
+                    Ok(Err(e)) => tracing::warn!("Proxy log maintenance failed: {}", e),
+                    Err(e) => tracing::warn!("Proxy log maintenance task failed: {}", e),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        Self { task }
+    }
+}
+
+impl Drop for LogMaintenance {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribution_applied_and_filtered() {
+        let mut attribution = RequestAttribution::default();
+        attribution.record_attempt(PROVIDER_GOOGLE, Some("a@example.com"), "gemini-2.5-pro");
+        attribution.record_attempt(PROVIDER_GOOGLE, Some("b@example.com"), "gemini-2.5-pro");
+
+        let mut log = ProxyRequestLog {
+            model: Some("gpt-4o".to_string()),
+            ..Default::default()
+        };
+        attribution.apply_to(&mut log);
+        assert_eq!(log.account_email.as_deref(), Some("b@example.com"));
+        assert_eq!(log.attempts, Some(2));
+        assert_eq!(log.rotations, Some(1));
+
+        let filter = ProxyLogFilter {
+            provider: Some(PROVIDER_GOOGLE.to_string()),
+            min_attempts: Some(2),
+            ..Default::default()
+        };
+        assert!(filter.matches(&log));
+
+        let filter = ProxyLogFilter {
+            account_email: Some("a@example.com".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&log));
+    }
+}
@@ -8,9 +8,9 @@ use futures::StreamExt;
 use serde_json::Value;
 use tokio::time::Duration;
 
-use crate::proxy::server::AppState;
+use crate::proxy::runtime::RuntimeSnapshot;
 
-fn map_model_for_zai(original: &str, state: &crate::proxy::ZaiConfig) -> String {
+pub(crate) fn map_model_for_zai(original: &str, state: &crate::proxy::ZaiConfig) -> String {
     let m = original.to_lowercase();
     if let Some(mapped) = state.model_mapping.get(original) {
         return mapped.clone();
@@ -106,14 +106,15 @@ fn set_zai_auth(headers: &mut HeaderMap, incoming: &HeaderMap, api_key: &str) {
     }
 }
 
+/// runtime 为调用方在请求开始时取得的配置快照
 pub async fn forward_anthropic_json(
-    state: &AppState,
+    runtime: &RuntimeSnapshot,
     method: Method,
     path: &str,
     incoming_headers: &HeaderMap,
     mut body: Value,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let zai = runtime.config.zai.clone();
     if !zai.enabled || zai.dispatch_mode == crate::proxy::ZaiDispatchMode::Off {
         return (StatusCode::BAD_REQUEST, "z.ai is disabled").into_response();
     }
@@ -132,8 +133,8 @@ pub async fn forward_anthropic_json(
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
 
-    let timeout_secs = state.request_timeout.max(5);
-    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let timeout_secs = runtime.config.request_timeout.max(5);
+    let upstream_proxy = runtime.config.upstream_proxy.clone();
     let client = match build_client(Some(upstream_proxy), timeout_secs) {
         Ok(c) => c,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
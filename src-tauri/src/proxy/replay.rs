@@ -0,0 +1,320 @@
+// 请求日志重放
+// 将已记录的请求经进程内路由重新发出 (可强制指定账号 / 映射模型 / 提供方)，
+// 结果作为新日志写入并通过 replay_of 关联原始日志
+use axum::body::Body;
+use axum::http::{header, Method, Request};
+use axum::Router;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tower::Service;
+
+use crate::modules::proxy_log_storage;
+use crate::proxy::monitor::{ProxyRequestLog, PROVIDER_GOOGLE, PROVIDER_ZAI};
+
+/// 输出差异最多比较的行数 (超出部分不参与逐行比对)
+const MAX_DIFF_LINES: usize = 2000;
+
+tokio::task_local! {
+    static REPLAY: ReplayContext;
+}
+
+/// 重放时的强制参数 (None 表示沿用正常路由)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayOverrides {
+    pub account_email: Option<String>,
+    pub mapped_model: Option<String>,
+    /// "google" | "zai"
+    pub provider: Option<String>,
+}
+
+/// 当前请求所处的重放上下文 (通过 task-local 传递给处理器与监控中间件)
+#[derive(Debug, Clone)]
+pub struct ReplayContext {
+    /// 被重放的原始日志 ID
+    pub source_id: String,
+    /// 重放结果的日志 ID
+    pub log_id: String,
+    pub overrides: ReplayOverrides,
+}
+
+/// 当前请求的重放上下文 (非重放请求返回 None)
+pub fn current() -> Option<ReplayContext> {
+    REPLAY.try_with(|ctx| ctx.clone()).ok()
+}
+
+/// 重放时强制使用的账号
+pub fn forced_account() -> Option<String> {
+    REPLAY.try_with(|ctx| ctx.overrides.account_email.clone()).ok().flatten()
+}
+
+/// 重放时强制使用的上游模型 (跳过 resolve_model_route)
+pub fn forced_mapped_model() -> Option<String> {
+    REPLAY.try_with(|ctx| ctx.overrides.mapped_model.clone()).ok().flatten()
+}
+
+/// 重放时强制使用的提供方
+pub fn forced_provider() -> Option<String> {
+    REPLAY.try_with(|ctx| ctx.overrides.provider.clone()).ok().flatten()
+}
+
+/// 重放结果的单侧摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplaySide {
+    pub id: String,
+    pub status: u16,
+    pub duration: u64,
+    pub ttft_ms: Option<u64>,
+    pub account_email: Option<String>,
+    pub mapped_model: Option<String>,
+    pub provider: Option<String>,
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+    pub error: Option<String>,
+}
+
+impl From<&ProxyRequestLog> for ReplaySide {
+    fn from(log: &ProxyRequestLog) -> Self {
+        Self {
+            id: log.id.clone(),
+            status: log.status,
+            duration: log.duration,
+            ttft_ms: log.ttft_ms,
+            account_email: log.account_email.clone(),
+            mapped_model: log.mapped_model.clone(),
+            provider: log.provider.clone(),
+            input_tokens: log.input_tokens,
+            output_tokens: log.output_tokens,
+            error: log.error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// 原始请求与重放结果的对比
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayDiff {
+    pub original: ReplaySide,
+    pub replay: ReplaySide,
+    pub status_changed: bool,
+    /// 重放耗时 - 原始耗时 (ms)
+    pub duration_delta_ms: i64,
+    pub output_changed: bool,
+    /// 输出逐行差异 (原始 -> 重放)
+    pub output_diff: Vec<DiffLine>,
+}
+
+/// 重放一条已记录的请求，返回重放结果的日志 ID
+/// `api_key` 为反代自身的访问密钥，用于通过鉴权中间件
+pub async fn replay(
+    mut router: Router,
+    api_key: Option<&str>,
+    source: &ProxyRequestLog,
+    overrides: ReplayOverrides,
+) -> Result<String, String> {
+    let body = match &source.request_body {
+        Some(body) if proxy_log_storage::is_lossy(body) => {
+            return Err("原始请求报文已被截断或脱敏，无法重放".to_string())
+        }
+        Some(body) if body.starts_with("[Binary") => {
+            return Err("原始请求为二进制报文，无法重放".to_string())
+        }
+        Some(body) => body.clone(),
+        None if source.method == "POST" => {
+            return Err("原始请求报文未记录，无法重放".to_string())
+        }
+        None => String::new(),
+    };
+
+    if let Some(provider) = overrides.provider.as_deref() {
+        match provider {
+            PROVIDER_GOOGLE => {}
+            PROVIDER_ZAI if source.url.starts_with("/v1/messages") => {}
+            PROVIDER_ZAI => return Err("z.ai 仅支持 Anthropic 协议 (/v1/messages) 的请求".to_string()),
+            other => return Err(format!("未知的提供方: {}", other)),
+        }
+    }
+
+    let method = Method::from_bytes(source.method.as_bytes()).map_err(|e| e.to_string())?;
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(&source.url)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(key) = api_key.filter(|k| !k.is_empty()) {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", key));
+    }
+    let request = builder
+        .body(Body::from(body))
+        .map_err(|e| format!("构建重放请求失败: {}", e))?;
+
+    let ctx = ReplayContext {
+        source_id: source.id.clone(),
+        log_id: uuid::Uuid::new_v4().to_string(),
+        overrides,
+    };
+    let log_id = ctx.log_id.clone();
+
+    tracing::info!("[Replay] Replaying {} {} ({}) as {}", source.method, source.url, source.id, log_id);
+    // Router 始终就绪，无需 poll_ready
+    let response = REPLAY
+        .scope(ctx, router.call(request))
+        .await
+        .map_err(|e| format!("重放请求失败: {}", e))?;
+
+    // 读完响应，监控中间件在响应结束后才写入日志
+    let mut stream = response.into_body().into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        if let Err(e) = chunk {
+            tracing::warn!("[Replay] Response stream error: {}", e);
+            break;
+        }
+    }
+    Ok(log_id)
+}
+
+/// 对比原始日志与重放日志
+pub fn diff_logs(original: &ProxyRequestLog, replay: &ProxyRequestLog) -> ReplayDiff {
+    let before = output_text(original);
+    let after = output_text(replay);
+    ReplayDiff {
+        original: original.into(),
+        replay: replay.into(),
+        status_changed: original.status != replay.status,
+        duration_delta_ms: replay.duration as i64 - original.duration as i64,
+        output_changed: before != after,
+        output_diff: diff_lines(&before, &after),
+    }
+}
+
+/// 用于比较的输出文本: JSON 报文格式化后逐行比较，流式响应为还原后的消息
+fn output_text(log: &ProxyRequestLog) -> String {
+    let body = log.response_body.as_deref().or(log.error.as_deref()).unwrap_or_default();
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// 基于最长公共子序列的逐行差异
+fn diff_lines(before: &str, after: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = before.lines().take(MAX_DIFF_LINES).collect();
+    let b: Vec<&str> = after.lines().take(MAX_DIFF_LINES).collect();
+
+    // lcs[i][j] = a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine { op, text: text.to_string() };
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push(line(DiffOp::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(line(DiffOp::Delete, a[i]));
+            i += 1;
+        } else {
+            out.push(line(DiffOp::Insert, b[j]));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().map(|l| line(DiffOp::Delete, l)));
+    out.extend(b[j..].iter().map(|l| line(DiffOp::Insert, l)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let diff = diff_lines("a\nb\nc", "a\nx\nc\nd");
+        let ops: Vec<(DiffOp, &str)> = diff.iter().map(|l| (l.op, l.text.as_str())).collect();
+        assert_eq!(
+            ops,
+            vec![
+                (DiffOp::Equal, "a"),
+                (DiffOp::Delete, "b"),
+                (DiffOp::Insert, "x"),
+                (DiffOp::Equal, "c"),
+                (DiffOp::Insert, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_logs_compares_pretty_json() {
+        let original = ProxyRequestLog {
+            id: "a".into(),
+            status: 500,
+            duration: 900,
+            response_body: Some(r#"{"error":"boom"}"#.into()),
+            ..Default::default()
+        };
+        let replayed = ProxyRequestLog {
+            id: "b".into(),
+            status: 200,
+            duration: 400,
+            response_body: Some("{\n  \"error\": \"boom\"\n}".into()),
+            replay_of: Some("a".into()),
+            ..Default::default()
+        };
+        let diff = diff_logs(&original, &replayed);
+        assert!(diff.status_changed);
+        assert_eq!(diff.duration_delta_ms, -500);
+        assert!(!diff.output_changed);
+    }
+
+    #[tokio::test]
+    async fn test_overrides_visible_only_inside_scope() {
+        assert!(forced_account().is_none());
+        let ctx = ReplayContext {
+            source_id: "src".into(),
+            log_id: "new".into(),
+            overrides: ReplayOverrides {
+                account_email: Some("a@example.com".into()),
+                ..Default::default()
+            },
+        };
+        let seen = REPLAY.scope(ctx, async { (forced_account(), forced_mapped_model()) }).await;
+        assert_eq!(seen, (Some("a@example.com".to_string()), None));
+    }
+
+    #[tokio::test]
+    async fn test_lossy_body_is_rejected() {
+        let source = ProxyRequestLog {
+            method: "POST".into(),
+            url: "/v1/messages".into(),
+            request_body: Some("{\"model\":\"x\"…[truncated 10 bytes]".into()),
+            ..Default::default()
+        };
+        let err = replay(Router::new(), None, &source, ReplayOverrides::default())
+            .await
+            .unwrap_err();
+        assert!(err.contains("截断"));
+    }
+}
@@ -0,0 +1,138 @@
+// 运行时配置快照
+// 反代服务的处理器 / 中间件 / 上游客户端统一从同一份快照读取配置；
+// 保存配置时整体替换快照，进行中的请求继续使用取到的旧快照
+use crate::proxy::config::ProxyConfig;
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::ProxySecurityConfig;
+use std::sync::{Arc, RwLock};
+
+/// 某一时刻生效的完整运行时配置
+pub struct RuntimeSnapshot {
+    pub config: ProxyConfig,
+    /// 由 config 推导的安全策略 (auth / IP 访问控制 / 管理接口)
+    pub security: ProxySecurityConfig,
+    /// 按 config 中出站设置 (上游代理 / 连接超时) 构建的 HTTP 客户端
+    pub upstream: Arc<UpstreamClient>,
+}
+
+impl RuntimeSnapshot {
+    fn build(config: ProxyConfig, upstream: Option<Arc<UpstreamClient>>) -> Self {
+        let upstream = upstream.unwrap_or_else(|| {
+            Arc::new(UpstreamClient::new(
+                Some(config.upstream_proxy.clone()),
+                config.timeouts.connect_timeout(),
+            ))
+        });
+        Self {
+            security: ProxySecurityConfig::from_proxy_config(&config),
+            upstream,
+            config,
+        }
+    }
+}
+
+/// 替换快照时需要额外处理的变化
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeChanges {
+    /// 出站设置变化，上游客户端已重建
+    pub egress: bool,
+    /// 监听地址或端口变化，需要重新绑定
+    pub listener: bool,
+}
+
+impl RuntimeChanges {
+    pub fn between(old: &ProxyConfig, new: &ProxyConfig) -> Self {
+        Self {
+            egress: old.upstream_proxy != new.upstream_proxy
+                || old.timeouts.connect_secs != new.timeouts.connect_secs,
+            listener: old.port != new.port || old.get_bind_address() != new.get_bind_address(),
+        }
+    }
+}
+
+/// 可整体替换的运行时配置
+pub struct RuntimeConfig {
+    current: RwLock<Arc<RuntimeSnapshot>>,
+}
+
+impl RuntimeConfig {
+    pub fn new(config: ProxyConfig) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(RuntimeSnapshot::build(config, None))),
+        }
+    }
+
+    /// 当前快照 (请求处理期间应只取一次，保证前后一致)
+    pub fn load(&self) -> Arc<RuntimeSnapshot> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// 替换为新配置，出站设置变化时重建上游客户端，否则复用原客户端 (保留连接池)
+    pub fn apply(&self, config: ProxyConfig) -> RuntimeChanges {
+        let mut current = self.current.write().unwrap();
+        let changes = RuntimeChanges::between(&current.config, &config);
+        let upstream = (!changes.egress).then(|| current.upstream.clone());
+        *current = Arc::new(RuntimeSnapshot::build(config, upstream));
+        changes
+    }
+
+    /// 在当前配置基础上修改部分字段
+    pub fn update(&self, f: impl FnOnce(&mut ProxyConfig)) -> RuntimeChanges {
+        let mut config = self.load().config.clone();
+        f(&mut config);
+        self.apply(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_rebuilds_client_only_on_egress_change() {
+        let runtime = RuntimeConfig::new(ProxyConfig::default());
+        let before = runtime.load();
+
+        let changes = runtime.update(|c| {
+            c.custom_mapping.insert("gpt-4o".to_string(), "gemini-3-flash".to_string());
+            c.request_timeout = 30;
+        });
+        assert_eq!(changes, RuntimeChanges::default());
+        let after = runtime.load();
+        assert!(Arc::ptr_eq(&before.upstream, &after.upstream));
+        assert_eq!(after.config.request_timeout, 30);
+        // 已取到的旧快照不受影响
+        assert!(before.config.custom_mapping.is_empty());
+
+        let changes = runtime.update(|c| {
+            c.upstream_proxy.enabled = true;
+            c.upstream_proxy.url = "http://127.0.0.1:7890".to_string();
+        });
+        assert!(changes.egress && !changes.listener);
+        assert!(!Arc::ptr_eq(&after.upstream, &runtime.load().upstream));
+
+        // 连接超时作用于客户端，变化时同样重建；其余超时按请求读取
+        assert!(runtime.update(|c| c.timeouts.connect_secs = 5).egress);
+        assert!(!runtime.update(|c| c.timeouts.stream_idle_secs = 5).egress);
+    }
+
+    #[test]
+    fn test_listener_changes() {
+        let base = ProxyConfig::default();
+        let mut other = base.clone();
+        other.bind_address = "192.168.1.10".to_string();
+        // 未开启局域网访问时绑定地址不生效
+        assert!(!RuntimeChanges::between(&base, &other).listener);
+        other.allow_lan_access = true;
+        assert!(RuntimeChanges::between(&base, &other).listener);
+
+        let mut other = base.clone();
+        other.port = base.port + 1;
+        assert!(RuntimeChanges::between(&base, &other).listener);
+
+        let mut other = base.clone();
+        other.api_key = "sk-other".to_string();
+        other.enable_logging = true;
+        assert_eq!(RuntimeChanges::between(&base, &other), RuntimeChanges::default());
+    }
+}
@@ -0,0 +1,199 @@
+// 账号调度策略
+// 将 TokenManager 中的账号选择逻辑抽象为可插拔的 SchedulingPolicy，
+// 生产环境与离线模拟器共用同一套策略实现
+
+pub mod policies;
+pub mod simulator;
+
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::sticky_config::SchedulingMode;
+use crate::proxy::token_manager::ProxyToken;
+
+pub use policies::{
+    BalancePolicy, CacheFirstPolicy, PerformanceFirstPolicy, WeightedRoundRobinPolicy,
+};
+
+/// 全局锁定窗口：无会话 ID 的请求在该时间内复用上一次使用的账号
+pub const LAST_USED_WINDOW_MS: i64 = 60_000;
+
+/// 账号可用性查询接口
+/// 生产环境由 RateLimitTracker 提供，模拟器使用虚拟时钟实现
+pub trait AccountAvailability {
+    /// 距离限流解除还有多少秒 (0 表示未限流)
+    fn remaining_wait(&self, account_id: &str) -> u64;
+    /// 账号是否仍处于限流中
+    fn is_limited(&self, account_id: &str) -> bool;
+}
+
+impl AccountAvailability for RateLimitTracker {
+    fn remaining_wait(&self, account_id: &str) -> u64 {
+        self.get_remaining_wait(account_id)
+    }
+
+    fn is_limited(&self, account_id: &str) -> bool {
+        self.is_rate_limited(account_id)
+    }
+}
+
+/// 会话与账号的绑定信息
+#[derive(Debug, Clone)]
+pub struct SessionBinding {
+    pub account_id: String,
+    /// 建立绑定的时间戳 (毫秒)
+    pub bound_at_ms: i64,
+    /// 最近一次命中的时间戳 (毫秒)
+    pub last_hit_ms: i64,
+    /// 绑定后被复用的次数
+    pub hits: u64,
+}
+
+/// 对外展示的会话绑定快照
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionBindingInfo {
+    pub session_id: String,
+    pub account_id: String,
+    /// 账号已不在池中时为 None
+    pub email: Option<String>,
+    /// 绑定已存在的时长 (秒)
+    pub age_seconds: i64,
+    /// 距最近一次命中的时长 (秒)
+    pub idle_seconds: i64,
+    pub hits: u64,
+}
+
+/// 调度器的可变状态：会话绑定、60s 全局锁定、轮询游标与加权轮询权重
+#[derive(Default)]
+pub struct SchedulerState {
+    /// 会话与账号映射 (SessionID -> 绑定信息)
+    pub session_accounts: DashMap<String, SessionBinding>,
+    /// 最近使用的账号及时间戳 (毫秒)
+    pub last_used: Mutex<Option<(String, i64)>>,
+    /// 轮询游标
+    pub current_index: AtomicUsize,
+    /// 平滑加权轮询的当前权重 (AccountID -> current weight)
+    pub wrr_weights: Mutex<HashMap<String, i64>>,
+}
+
+impl SchedulerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 账号池重载时重置游标与全局锁定 (会话绑定保留)
+    pub fn reset_cursor(&self) {
+        self.current_index.store(0, Ordering::SeqCst);
+        *self.lock_last_used() = None;
+    }
+
+    /// 为会话建立 (或覆盖) 账号绑定
+    pub fn bind_session(&self, session_id: &str, account_id: &str, now_ms: i64) {
+        self.session_accounts.insert(
+            session_id.to_string(),
+            SessionBinding {
+                account_id: account_id.to_string(),
+                bound_at_ms: now_ms,
+                last_hit_ms: now_ms,
+                hits: 0,
+            },
+        );
+    }
+
+    /// 读取会话当前绑定的账号，不更新命中统计
+    pub fn bound_account(&self, session_id: &str) -> Option<String> {
+        self.session_accounts.get(session_id).map(|b| b.account_id.clone())
+    }
+
+    /// 记录一次会话绑定命中
+    pub fn record_hit(&self, session_id: &str, now_ms: i64) {
+        if let Some(mut binding) = self.session_accounts.get_mut(session_id) {
+            binding.hits += 1;
+            binding.last_hit_ms = now_ms;
+        }
+    }
+
+    /// 账号请求失败后解除全局锁定，避免下一次仍选中同一账号
+    pub fn release(&self, account_id: &str) {
+        let mut last_used = self.lock_last_used();
+        if matches!(&*last_used, Some((id, _)) if id == account_id) {
+            *last_used = None;
+        }
+    }
+
+    pub(crate) fn lock_last_used(&self) -> std::sync::MutexGuard<'_, Option<(String, i64)>> {
+        self.last_used.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 单次选择的上下文
+pub struct SelectionRequest<'a> {
+    /// 配额分组 ("claude" / "gemini" / "image_gen" ...)
+    pub quota_group: &'a str,
+    pub session_id: Option<&'a str>,
+    /// 是否强制轮换 (忽略粘性与锁定)
+    pub rotate: bool,
+    /// 本次请求中已尝试失败的账号
+    pub attempted: &'a HashSet<String>,
+    /// 缓存优先模式下的最大等待时间 (秒)
+    pub max_wait_seconds: u64,
+    /// 当前时间戳 (毫秒)，模拟器中为虚拟时间
+    pub now_ms: i64,
+}
+
+/// 策略的选择结果
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// 直接使用该账号
+    Account(ProxyToken),
+    /// 等待限流解除后复用该账号 (缓存优先模式)
+    WaitThenUse {
+        token: ProxyToken,
+        wait_seconds: u64,
+    },
+    /// 没有可用账号
+    Exhausted,
+}
+
+/// 可插拔的账号调度策略
+pub trait SchedulingPolicy: Send + Sync {
+    /// 策略名称 (用于日志与模拟报告)
+    fn name(&self) -> &'static str;
+
+    /// 从账号池 (已按订阅等级排序) 中为本次请求选择账号，并更新调度状态
+    fn select(
+        &self,
+        request: &SelectionRequest<'_>,
+        pool: &[ProxyToken],
+        state: &SchedulerState,
+        availability: &dyn AccountAvailability,
+    ) -> Selection;
+}
+
+/// 根据配置的调度模式获取对应策略
+pub fn policy_for_mode(mode: SchedulingMode) -> &'static dyn SchedulingPolicy {
+    match mode {
+        SchedulingMode::CacheFirst => &CacheFirstPolicy,
+        SchedulingMode::Balance => &BalancePolicy,
+        SchedulingMode::PerformanceFirst => &PerformanceFirstPolicy,
+        SchedulingMode::WeightedRoundRobin => &WeightedRoundRobinPolicy,
+    }
+}
+
+/// 根据订阅等级排序 (优先级: ULTRA > PRO > FREE)
+/// 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
+pub fn sort_by_tier(pool: &mut [ProxyToken]) {
+    pool.sort_by(|a, b| {
+        let tier_priority = |tier: &Option<String>| match tier.as_deref() {
+            Some("ULTRA") => 0,
+            Some("PRO") => 1,
+            Some("FREE") => 2,
+            _ => 3,
+        };
+        tier_priority(&a.subscription_tier).cmp(&tier_priority(&b.subscription_tier))
+    });
+}
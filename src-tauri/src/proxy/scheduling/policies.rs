@@ -0,0 +1,411 @@
+// 内置调度策略：缓存优先 / 平衡 / 性能优先 / 加权轮询
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use super::{
+    AccountAvailability, SchedulerState, SchedulingPolicy, Selection, SelectionRequest,
+    LAST_USED_WINDOW_MS,
+};
+use crate::proxy::token_manager::ProxyToken;
+
+/// 缓存优先 (Cache-first): 尽可能锁定同一账号，限流时优先等待
+pub struct CacheFirstPolicy;
+
+/// 平衡模式 (Balance): 锁定同一账号，限流时立即切换到备选账号
+pub struct BalancePolicy;
+
+/// 性能优先 (Performance-first): 不建立会话绑定，轮询分配
+pub struct PerformanceFirstPolicy;
+
+/// 加权轮询 (Weighted round-robin): 按优先级分层、同层按权重分配新会话，会话建立后保持粘性
+pub struct WeightedRoundRobinPolicy;
+
+impl SchedulingPolicy for CacheFirstPolicy {
+    fn name(&self) -> &'static str {
+        "CacheFirst"
+    }
+
+    fn select(
+        &self,
+        request: &SelectionRequest<'_>,
+        pool: &[ProxyToken],
+        state: &SchedulerState,
+        availability: &dyn AccountAvailability,
+    ) -> Selection {
+        if let Some(selection) = sticky_reuse(request, pool, state, availability, true) {
+            return selection;
+        }
+        locked_or_pick(request, pool, state, true, true, || {
+            pick_round_robin(request, pool, state, availability)
+        })
+    }
+}
+
+impl SchedulingPolicy for BalancePolicy {
+    fn name(&self) -> &'static str {
+        "Balance"
+    }
+
+    fn select(
+        &self,
+        request: &SelectionRequest<'_>,
+        pool: &[ProxyToken],
+        state: &SchedulerState,
+        availability: &dyn AccountAvailability,
+    ) -> Selection {
+        if let Some(selection) = sticky_reuse(request, pool, state, availability, false) {
+            return selection;
+        }
+        locked_or_pick(request, pool, state, true, true, || {
+            pick_round_robin(request, pool, state, availability)
+        })
+    }
+}
+
+impl SchedulingPolicy for PerformanceFirstPolicy {
+    fn name(&self) -> &'static str {
+        "PerformanceFirst"
+    }
+
+    fn select(
+        &self,
+        request: &SelectionRequest<'_>,
+        pool: &[ProxyToken],
+        state: &SchedulerState,
+        availability: &dyn AccountAvailability,
+    ) -> Selection {
+        locked_or_pick(request, pool, state, true, false, || {
+            pick_round_robin(request, pool, state, availability)
+        })
+    }
+}
+
+impl SchedulingPolicy for WeightedRoundRobinPolicy {
+    fn name(&self) -> &'static str {
+        "WeightedRoundRobin"
+    }
+
+    fn select(
+        &self,
+        request: &SelectionRequest<'_>,
+        pool: &[ProxyToken],
+        state: &SchedulerState,
+        availability: &dyn AccountAvailability,
+    ) -> Selection {
+        if let Some(selection) = sticky_reuse(request, pool, state, availability, false) {
+            return selection;
+        }
+        // 加权轮询模式下每个新请求都按权重分配，不复用 60s 全局锁定
+        locked_or_pick(request, pool, state, false, true, || {
+            with_drain_fallback(|allow_drain| {
+                let available = available_accounts(request, pool, availability, allow_drain);
+                let mut weights = state.wrr_weights.lock().unwrap_or_else(|e| e.into_inner());
+                pick_weighted(&available, &mut weights).cloned()
+            })
+        })
+    }
+}
+
+/// 模式 A: 粘性会话处理
+/// 会话已绑定账号时优先复用；绑定账号限流时按 `wait_on_limit` 决定等待还是解绑换号
+/// (已有会话即使账号处于排空状态也继续复用)
+fn sticky_reuse(
+    request: &SelectionRequest<'_>,
+    pool: &[ProxyToken],
+    state: &SchedulerState,
+    availability: &dyn AccountAvailability,
+    wait_on_limit: bool,
+) -> Option<Selection> {
+    if request.rotate {
+        return None;
+    }
+    let sid = request.session_id?;
+
+    // 1. 检查会话是否已绑定账号
+    let bound_id = state.bound_account(sid)?;
+
+    // 2. 检查绑定的账号是否限流 (使用精准的剩余时间接口)
+    let reset_sec = availability.remaining_wait(&bound_id);
+    if reset_sec > 0 {
+        if wait_on_limit && reset_sec <= request.max_wait_seconds {
+            // 缓存优先模式：限流时间短，执行精准避让等待后复用
+            let found = pool.iter().find(|t| t.account_id == bound_id)?;
+            tracing::warn!("Cache-first: Session {} bound to {} is limited. Executing precise wait for {}s to preserve cache...", sid, bound_id, reset_sec);
+            state.record_hit(sid, request.now_ms);
+            return Some(Selection::WaitThenUse {
+                token: found.clone(),
+                wait_seconds: reset_sec,
+            });
+        }
+        // 平衡模式或等待时间过长：断开绑定，准备换号
+        tracing::warn!("Avoidance/WaitTimeout: Session {} switching from {} (remaining wait: {}s > limit: {}s).", sid, bound_id, reset_sec, request.max_wait_seconds);
+        state.session_accounts.remove(sid);
+        return None;
+    }
+
+    // 3. 账号可用且未被标记为尝试失败，优先复用
+    if request.attempted.contains(&bound_id) {
+        return None;
+    }
+    let found = pool.iter().find(|t| t.account_id == bound_id)?;
+    tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", found.email, sid);
+    state.record_hit(sid, request.now_ms);
+    Some(Selection::Account(found.clone()))
+}
+
+/// 模式 B / C: 60s 全局锁定与新账号选择
+/// 非轮换请求 (且非生图) 在锁定窗口内复用上次账号，否则调用 `pick` 选择新账号并记录锁定；
+/// `bind_sessions` 为 true 时为首次出现的会话建立绑定
+fn locked_or_pick<F>(
+    request: &SelectionRequest<'_>,
+    pool: &[ProxyToken],
+    state: &SchedulerState,
+    reuse_window: bool,
+    bind_sessions: bool,
+    pick: F,
+) -> Selection
+where
+    F: FnOnce() -> Option<ProxyToken>,
+{
+    if request.rotate || request.quota_group == "image_gen" {
+        // 模式 C: 纯轮询或强制轮换
+        return match pick() {
+            Some(candidate) => {
+                if request.rotate {
+                    tracing::debug!("Force Rotation: Switched to account: {}", candidate.email);
+                }
+                Selection::Account(candidate)
+            }
+            None => Selection::Exhausted,
+        };
+    }
+
+    let mut last_used = state.lock_last_used();
+
+    // 尝试复用全局锁定账号 (排空中的账号不再复用)
+    if reuse_window {
+        if let Some((account_id, last_time)) = &*last_used {
+            if request.now_ms - last_time < LAST_USED_WINDOW_MS && !request.attempted.contains(account_id) {
+                if let Some(found) = pool.iter().find(|t| &t.account_id == account_id && !t.drain) {
+                    tracing::debug!("60s Window: Force reusing last account: {}", found.email);
+                    return Selection::Account(found.clone());
+                }
+            }
+        }
+    }
+
+    // 若无锁定，则选择新账号
+    let Some(candidate) = pick() else {
+        return Selection::Exhausted;
+    };
+    *last_used = Some((candidate.account_id.clone(), request.now_ms));
+
+    // 如果是会话首次分配且需要粘性，在此建立绑定
+    if let (true, Some(sid)) = (bind_sessions, request.session_id) {
+        state.bind_session(sid, &candidate.account_id, request.now_ms);
+        tracing::debug!("Sticky Session: Bound new account {} to session {}", candidate.email, sid);
+    }
+    Selection::Account(candidate)
+}
+
+/// 过滤出可用于新请求的账号 (跳过已尝试、限流中以及排空中的账号)
+fn available_accounts<'a>(
+    request: &SelectionRequest<'_>,
+    pool: &'a [ProxyToken],
+    availability: &dyn AccountAvailability,
+    allow_drain: bool,
+) -> Vec<&'a ProxyToken> {
+    pool.iter()
+        .filter(|t| !request.attempted.contains(&t.account_id))
+        .filter(|t| allow_drain || !t.drain)
+        // 主动避开限流或 5xx 锁定的账号
+        .filter(|t| !availability.is_limited(&t.account_id))
+        .collect()
+}
+
+/// 优先选择非排空账号；若整个池子都处于排空状态，则退而使用排空账号，避免请求直接失败
+fn with_drain_fallback<F>(pick: F) -> Option<ProxyToken>
+where
+    F: Fn(bool) -> Option<ProxyToken>,
+{
+    pick(false).or_else(|| {
+        let fallback = pick(true);
+        if let Some(t) = &fallback {
+            tracing::warn!("All available accounts are draining, falling back to draining account {}", t.email);
+        }
+        fallback
+    })
+}
+
+/// 基于轮询游标的顺序选择 (账号池已按订阅等级排序)
+fn pick_round_robin(
+    request: &SelectionRequest<'_>,
+    pool: &[ProxyToken],
+    state: &SchedulerState,
+    availability: &dyn AccountAvailability,
+) -> Option<ProxyToken> {
+    if pool.is_empty() {
+        return None;
+    }
+    with_drain_fallback(|allow_drain| {
+        let available = available_accounts(request, pool, availability, allow_drain);
+        if available.is_empty() {
+            return None;
+        }
+        let total = pool.len();
+        let start_idx = state.current_index.fetch_add(1, Ordering::SeqCst) % total;
+        (0..total)
+            .map(|offset| &pool[(start_idx + offset) % total])
+            .find(|t| available.iter().any(|a| a.account_id == t.account_id))
+            .cloned()
+    })
+}
+
+/// 优先级 + 平滑加权轮询 (Smooth Weighted Round-Robin, 与 Nginx 相同的算法)
+/// 只在优先级数值最小的一层账号中选择；每次选择时各账号当前权重 += 自身权重，
+/// 选出当前权重最大的账号后再减去该层总权重，使流量按权重比例平滑分布
+fn pick_weighted<'a>(
+    candidates: &[&'a ProxyToken],
+    current_weights: &mut HashMap<String, i64>,
+) -> Option<&'a ProxyToken> {
+    let top_priority = candidates.iter().map(|t| t.priority).min()?;
+    let tier: Vec<&ProxyToken> = candidates
+        .iter()
+        .copied()
+        .filter(|t| t.priority == top_priority)
+        .collect();
+
+    let total_weight: i64 = tier.iter().map(|t| t.weight.max(1) as i64).sum();
+    let mut best: Option<(&ProxyToken, i64)> = None;
+    for t in &tier {
+        let current = current_weights.entry(t.account_id.clone()).or_insert(0);
+        *current += t.weight.max(1) as i64;
+        if best.is_none_or(|(_, w)| *current > w) {
+            best = Some((t, *current));
+        }
+    }
+
+    let (chosen, _) = best?;
+    if let Some(current) = current_weights.get_mut(&chosen.account_id) {
+        *current -= total_weight;
+    }
+    Some(chosen)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    pub(crate) fn token(id: &str, weight: u32, priority: i32) -> ProxyToken {
+        ProxyToken {
+            account_id: id.to_string(),
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expires_in: 3600,
+            timestamp: 0,
+            email: format!("{}@example.com", id),
+            account_path: PathBuf::new(),
+            project_id: None,
+            subscription_tier: None,
+            groups: Vec::new(),
+            weight,
+            priority,
+            drain: false,
+        }
+    }
+
+    struct NoLimits;
+
+    impl AccountAvailability for NoLimits {
+        fn remaining_wait(&self, _account_id: &str) -> u64 {
+            0
+        }
+        fn is_limited(&self, _account_id: &str) -> bool {
+            false
+        }
+    }
+
+    fn request<'a>(session_id: Option<&'a str>, attempted: &'a HashSet<String>, now_ms: i64) -> SelectionRequest<'a> {
+        SelectionRequest {
+            quota_group: "claude",
+            session_id,
+            rotate: false,
+            attempted,
+            max_wait_seconds: 60,
+            now_ms,
+        }
+    }
+
+    fn selected_id(selection: Selection) -> String {
+        match selection {
+            Selection::Account(t) => t.account_id,
+            other => panic!("unexpected selection: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_weighted_pick_follows_weights() {
+        let a = token("a", 5, 0);
+        let b = token("b", 1, 0);
+        let c = token("c", 1, 0);
+        let candidates = vec![&a, &b, &c];
+        let mut weights = HashMap::new();
+
+        let picks: Vec<String> = (0..7)
+            .map(|_| pick_weighted(&candidates, &mut weights).unwrap().account_id.clone())
+            .collect();
+
+        assert_eq!(picks.iter().filter(|id| *id == "a").count(), 5);
+        assert_eq!(picks.iter().filter(|id| *id == "b").count(), 1);
+        assert_eq!(picks.iter().filter(|id| *id == "c").count(), 1);
+        // 平滑分布：高权重账号不会连续占满
+        assert_ne!(picks[..5], ["a", "a", "a", "a", "a"]);
+    }
+
+    #[test]
+    fn test_weighted_pick_prefers_lower_priority_value() {
+        let primary = token("primary", 1, 0);
+        let overflow = token("overflow", 10, 1);
+        let mut weights = HashMap::new();
+
+        for _ in 0..5 {
+            let picked = pick_weighted(&[&primary, &overflow], &mut weights).unwrap();
+            assert_eq!(picked.account_id, "primary");
+        }
+        let picked = pick_weighted(&[&overflow], &mut weights).unwrap();
+        assert_eq!(picked.account_id, "overflow");
+    }
+
+    #[test]
+    fn test_balance_keeps_session_binding() {
+        let pool = vec![token("a", 1, 0), token("b", 1, 0)];
+        let state = SchedulerState::new();
+        let attempted = HashSet::new();
+
+        let first = selected_id(BalancePolicy.select(&request(Some("sid-1"), &attempted, 0), &pool, &state, &NoLimits));
+        // 超出 60s 锁定窗口后，同一会话仍然复用绑定账号
+        let second = selected_id(BalancePolicy.select(&request(Some("sid-1"), &attempted, 120_000), &pool, &state, &NoLimits));
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_drained_account_keeps_existing_sessions_only() {
+        let mut pool = vec![token("a", 1, 0), token("b", 1, 0)];
+        let state = SchedulerState::new();
+        let attempted = HashSet::new();
+
+        let bound = selected_id(BalancePolicy.select(&request(Some("sid-1"), &attempted, 0), &pool, &state, &NoLimits));
+        pool.iter_mut().find(|t| t.account_id == bound).unwrap().drain = true;
+
+        let existing = selected_id(BalancePolicy.select(&request(Some("sid-1"), &attempted, 120_000), &pool, &state, &NoLimits));
+        assert_eq!(existing, bound);
+
+        for i in 0..4 {
+            let sid = format!("sid-new-{}", i);
+            let picked = selected_id(BalancePolicy.select(&request(Some(&sid), &attempted, 240_000 + i * 120_000), &pool, &state, &NoLimits));
+            assert_ne!(picked, bound);
+        }
+    }
+}
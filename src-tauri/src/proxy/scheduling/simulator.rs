@@ -0,0 +1,373 @@
+// 离线调度模拟器
+// 按时间顺序回放 request_logs 中记录的请求 (时间戳 / 模型 / 状态码)，
+// 在虚拟时钟下驱动 SchedulingPolicy，评估成功率、429 次数、缓存亲和度与账号负载，
+// 无需触碰真实流量即可调优调度策略
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::{
+    policy_for_mode, sort_by_tier, AccountAvailability, SchedulerState, Selection,
+    SelectionRequest,
+};
+use crate::models::Account;
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::session_manager::SessionManager;
+use crate::proxy::sticky_config::SchedulingMode;
+use crate::proxy::token_manager::ProxyToken;
+
+/// 模拟参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// 待评估的调度模式
+    pub mode: SchedulingMode,
+    /// 缓存优先模式下的最大等待时间 (秒)
+    pub max_wait_seconds: u64,
+    /// 单个账号对单个模型每分钟可承受的请求数，超出即视为上游返回 429
+    pub account_rpm: u32,
+    /// 账号触发 429 后的冷却时间 (秒)
+    pub rate_limit_cooldown_seconds: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            mode: SchedulingMode::Balance,
+            max_wait_seconds: 60,
+            account_rpm: 10,
+            rate_limit_cooldown_seconds: 60,
+        }
+    }
+}
+
+/// 单个账号的模拟负载
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct AccountLoad {
+    pub account_id: String,
+    pub email: String,
+    /// 分配到该账号的请求次数 (含失败重试)
+    pub requests: u64,
+    pub successes: u64,
+    pub rate_limited: u64,
+}
+
+/// 模拟报告
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct SimulationReport {
+    pub policy: String,
+    pub total_requests: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub success_rate: f64,
+    /// 模拟过程中上游返回 429 的次数 (含被轮换消化的)
+    pub rate_limited_count: u64,
+    /// 原始记录即为客户端/上游错误 (非 429) 的请求数，与调度无关，原样计为失败
+    pub replayed_errors: u64,
+    /// 无可用账号而直接失败的请求数
+    pub exhausted: u64,
+    /// 账号轮换次数
+    pub rotations: u64,
+    /// 缓存优先模式下的累计等待时间 (秒)
+    pub total_wait_seconds: u64,
+    /// 可识别会话、且会话此前已被服务过的请求数
+    pub session_followups: u64,
+    /// 其中仍由同一账号服务的请求数
+    pub cache_hits: u64,
+    /// 缓存亲和度 = cache_hits / session_followups
+    pub cache_affinity: f64,
+    pub accounts: Vec<AccountLoad>,
+}
+
+/// 虚拟时钟下的账号限流状态
+struct SimulatedLimits {
+    now_ms: i64,
+    limited_until: HashMap<String, i64>,
+}
+
+impl AccountAvailability for SimulatedLimits {
+    fn remaining_wait(&self, account_id: &str) -> u64 {
+        self.limited_until
+            .get(account_id)
+            .map(|until| ((until - self.now_ms).max(0) / 1000) as u64)
+            .unwrap_or(0)
+    }
+
+    fn is_limited(&self, account_id: &str) -> bool {
+        self.limited_until
+            .get(account_id)
+            .is_some_and(|until| *until > self.now_ms)
+    }
+}
+
+/// 将账号转换为模拟用的 ProxyToken (跳过被禁用或禁用反代的账号)
+pub fn tokens_from_accounts(accounts: &[Account]) -> Vec<ProxyToken> {
+    accounts
+        .iter()
+        .filter(|a| !a.disabled && !a.proxy_disabled)
+        .map(|a| ProxyToken {
+            account_id: a.id.clone(),
+            access_token: String::new(),
+            refresh_token: String::new(),
+            expires_in: 0,
+            timestamp: 0,
+            email: a.email.clone(),
+            account_path: Default::default(),
+            project_id: None,
+            subscription_tier: a.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+            groups: a.groups.clone(),
+            weight: a.weight.max(1),
+            priority: a.priority,
+            drain: a.drain,
+        })
+        .collect()
+}
+
+/// 从记录的请求体中还原会话指纹 (与线上处理器使用相同的指纹算法)
+fn session_key(log: &ProxyRequestLog) -> Option<String> {
+    let body = log.request_body.as_deref()?;
+    if log.url.contains("/v1/messages") {
+        let request = serde_json::from_str(body).ok()?;
+        Some(SessionManager::extract_session_id(&request))
+    } else if log.url.contains("/chat/completions") {
+        let request = serde_json::from_str(body).ok()?;
+        Some(SessionManager::extract_openai_session_id(&request))
+    } else if log.url.contains("/v1beta/models") {
+        let request: serde_json::Value = serde_json::from_str(body).ok()?;
+        Some(SessionManager::extract_gemini_session_id(&request, log.model.as_deref().unwrap_or("")))
+    } else {
+        None
+    }
+}
+
+/// 按模型名推断请求类型 (对应 get_token 的 quota_group 参数)
+fn request_type(log: &ProxyRequestLog) -> &'static str {
+    match log.model.as_deref() {
+        Some(m) if m.contains("image") => "image_gen",
+        _ => "agent",
+    }
+}
+
+/// 回放请求日志并生成模拟报告
+pub fn simulate(
+    logs: &[ProxyRequestLog],
+    accounts: Vec<ProxyToken>,
+    config: &SimulationConfig,
+) -> SimulationReport {
+    let policy = policy_for_mode(config.mode);
+    let mut pool = accounts;
+    sort_by_tier(&mut pool);
+
+    let mut report = SimulationReport {
+        policy: policy.name().to_string(),
+        ..Default::default()
+    };
+    let mut loads: HashMap<String, AccountLoad> = pool
+        .iter()
+        .map(|t| {
+            (
+                t.account_id.clone(),
+                AccountLoad {
+                    account_id: t.account_id.clone(),
+                    email: t.email.clone(),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+
+    let mut ordered: Vec<&ProxyRequestLog> = logs.iter().collect();
+    ordered.sort_by_key(|l| l.timestamp);
+
+    let state = SchedulerState::new();
+    let mut limits = SimulatedLimits {
+        now_ms: 0,
+        limited_until: HashMap::new(),
+    };
+    // (账号, 模型) -> 最近 60s 内的请求时间戳
+    let mut windows: HashMap<(String, String), VecDeque<i64>> = HashMap::new();
+    // 会话 -> 上一次成功服务该会话的账号
+    let mut session_owner: HashMap<String, String> = HashMap::new();
+    let rpm = config.account_rpm.max(1) as usize;
+
+    for log in ordered {
+        report.total_requests += 1;
+        limits.now_ms = log.timestamp;
+
+        let sid = session_key(log);
+        let model = log.model.clone().unwrap_or_default();
+        let quota_group = request_type(log);
+        let mut attempted: HashSet<String> = HashSet::new();
+        let mut served_by: Option<String> = None;
+
+        for attempt in 0..pool.len().max(1) {
+            let request = SelectionRequest {
+                quota_group,
+                session_id: sid.as_deref(),
+                rotate: attempt > 0,
+                attempted: &attempted,
+                max_wait_seconds: config.max_wait_seconds,
+                now_ms: limits.now_ms,
+            };
+            let token = match policy.select(&request, &pool, &state, &limits) {
+                Selection::Account(t) => t,
+                Selection::WaitThenUse { token, wait_seconds } => {
+                    report.total_wait_seconds += wait_seconds;
+                    limits.limited_until.remove(&token.account_id);
+                    token
+                }
+                Selection::Exhausted => break,
+            };
+            if attempt > 0 {
+                report.rotations += 1;
+            }
+
+            let load = loads.entry(token.account_id.clone()).or_default();
+            load.requests += 1;
+
+            let window = windows
+                .entry((token.account_id.clone(), model.clone()))
+                .or_default();
+            while window.front().is_some_and(|t| limits.now_ms - t >= 60_000) {
+                window.pop_front();
+            }
+
+            if window.len() >= rpm {
+                // 模拟上游 429：标记限流并轮换到下一个账号
+                load.rate_limited += 1;
+                report.rate_limited_count += 1;
+                limits.limited_until.insert(
+                    token.account_id.clone(),
+                    limits.now_ms + config.rate_limit_cooldown_seconds as i64 * 1000,
+                );
+                state.release(&token.account_id);
+                attempted.insert(token.account_id.clone());
+                continue;
+            }
+
+            window.push_back(limits.now_ms);
+            served_by = Some(token.account_id.clone());
+            break;
+        }
+
+        let Some(account_id) = served_by else {
+            report.exhausted += 1;
+            report.failed += 1;
+            continue;
+        };
+
+        if let Some(sid) = &sid {
+            if let Some(previous) = session_owner.insert(sid.clone(), account_id.clone()) {
+                report.session_followups += 1;
+                if previous == account_id {
+                    report.cache_hits += 1;
+                }
+            }
+        }
+
+        // 与调度无关的原始错误 (如 400 参数错误) 原样计为失败；原始 429 由模拟的配额模型重新判定
+        if log.status >= 400 && log.status != 429 {
+            report.replayed_errors += 1;
+            report.failed += 1;
+        } else {
+            report.succeeded += 1;
+            if let Some(load) = loads.get_mut(&account_id) {
+                load.successes += 1;
+            }
+        }
+    }
+
+    if report.total_requests > 0 {
+        report.success_rate = report.succeeded as f64 / report.total_requests as f64;
+    }
+    if report.session_followups > 0 {
+        report.cache_affinity = report.cache_hits as f64 / report.session_followups as f64;
+    }
+
+    let mut accounts: Vec<AccountLoad> = loads.into_values().collect();
+    accounts.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.email.cmp(&b.email)));
+    report.accounts = accounts;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::scheduling::policies::tests::token;
+
+    fn log(timestamp: i64, status: u16, prompt: &str) -> ProxyRequestLog {
+        let body = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": prompt }]
+        });
+        ProxyRequestLog {
+            id: format!("log-{}", timestamp),
+            timestamp,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status,
+            duration: 100,
+            model: Some("claude-sonnet-4-5".to_string()),
+            error: None,
+            request_body: Some(body.to_string()),
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            ..Default::default()
+        }
+    }
+
+    fn config(mode: SchedulingMode) -> SimulationConfig {
+        SimulationConfig {
+            mode,
+            account_rpm: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sticky_policy_keeps_cache_affinity() {
+        let logs: Vec<ProxyRequestLog> = (0..4)
+            .map(|i| log(i * 120_000, 200, "refactor the parser module please"))
+            .collect();
+        let accounts = vec![token("a", 1, 0), token("b", 1, 0)];
+
+        let report = simulate(&logs, accounts, &config(SchedulingMode::Balance));
+        assert_eq!(report.total_requests, 4);
+        assert_eq!(report.succeeded, 4);
+        assert_eq!(report.session_followups, 3);
+        assert_eq!(report.cache_affinity, 1.0);
+    }
+
+    #[test]
+    fn test_rate_limits_trigger_rotation() {
+        // 同一时刻的 3 个请求，每个账号每分钟只能承受 2 个
+        let logs: Vec<ProxyRequestLog> = (0..3)
+            .map(|i| log(1_000 + i, 200, &format!("independent prompt number {}", i)))
+            .collect();
+        let accounts = vec![token("a", 1, 0), token("b", 1, 0)];
+
+        let report = simulate(&logs, accounts, &config(SchedulingMode::Balance));
+        assert_eq!(report.succeeded, 3);
+        assert_eq!(report.rate_limited_count, 1);
+        assert_eq!(report.rotations, 1);
+        let total_load: u64 = report.accounts.iter().map(|a| a.successes).sum();
+        assert_eq!(total_load, 3);
+    }
+
+    #[test]
+    fn test_replayed_errors_and_exhaustion() {
+        let logs = vec![
+            log(0, 400, "a request that was malformed upstream"),
+            log(1, 200, "another independent request here"),
+            log(2, 200, "a third independent request here"),
+        ];
+        let accounts = vec![token("a", 1, 0)];
+
+        let report = simulate(&logs, accounts, &config(SchedulingMode::PerformanceFirst));
+        assert_eq!(report.replayed_errors, 1);
+        assert_eq!(report.exhausted, 1);
+        assert_eq!(report.succeeded, 1);
+        assert_eq!(report.failed, 2);
+    }
+}
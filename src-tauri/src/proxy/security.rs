@@ -1,10 +1,17 @@
+use crate::proxy::client_keys::{self, AuthenticatedKey, ClientApiKey};
 use crate::proxy::config::{ProxyAuthMode, ProxyConfig};
+use crate::proxy::ip_filter::IpFilter;
 
 #[derive(Debug, Clone)]
 pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
     pub api_key: String,
+    pub client_keys: Vec<ClientApiKey>,
     pub allow_lan_access: bool,
+    /// 实际监听地址 (未开放局域网访问时为 127.0.0.1)
+    pub bind_address: String,
+    pub admin_api_enabled: bool,
+    pub ip_filter: IpFilter,
 }
 
 impl ProxySecurityConfig {
@@ -12,49 +19,102 @@ impl ProxySecurityConfig {
         Self {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
+            client_keys: config.client_keys.clone(),
             allow_lan_access: config.allow_lan_access,
+            bind_address: config.get_bind_address().to_string(),
+            admin_api_enabled: config.enable_admin_api,
+            ip_filter: IpFilter::from_config(&config.ip_filter),
         }
     }
 
     pub fn effective_auth_mode(&self) -> ProxyAuthMode {
         match self.auth_mode {
             ProxyAuthMode::Auto => {
-                if self.allow_lan_access {
-                    ProxyAuthMode::AllExceptHealth
-                } else {
+                // 只有本机能连接时无需认证: 监听回环地址，或 IP 允许列表只放行回环地址；
+                // 其余情况 (局域网可达) 一律要求 API Key，允许列表中的局域网地址不能代替凭证
+                if !self.listens_beyond_loopback() || self.ip_filter.admits_only_loopback() {
                     ProxyAuthMode::Off
+                } else {
+                    ProxyAuthMode::AllExceptHealth
                 }
             }
             ref other => other.clone(),
         }
     }
+
+    /// 识别请求携带的 Key (主 API Key 或命名客户端 Key)
+    pub fn identify(&self, presented: &str) -> Option<AuthenticatedKey> {
+        client_keys::identify(&self.api_key, &self.client_keys, presented)
+    }
+
+    /// 是否配置了任何可用于认证的 Key
+    pub fn has_keys(&self) -> bool {
+        !self.api_key.is_empty() || self.client_keys.iter().any(|k| !k.key.is_empty())
+    }
+
+    /// 监听地址是否可被本机以外访问 (无法解析的主机名按可访问处理)
+    fn listens_beyond_loopback(&self) -> bool {
+        if !self.allow_lan_access {
+            return false;
+        }
+        match self.bind_address.parse::<std::net::IpAddr>() {
+            Ok(ip) => !ip.is_loopback(),
+            Err(_) => self.bind_address != "localhost",
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn auto_mode_resolves_off_for_local_only() {
-        let s = ProxySecurityConfig {
+    fn auto(allow_lan_access: bool, bind_address: &str, allowlist: &[&str]) -> ProxySecurityConfig {
+        ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
-            allow_lan_access: false,
-        };
-        assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
+            client_keys: vec![],
+            allow_lan_access,
+            bind_address: bind_address.to_string(),
+            admin_api_enabled: false,
+            ip_filter: IpFilter::try_from_config(&crate::proxy::ip_filter::IpFilterConfig {
+                allowlist: allowlist.iter().map(|s| s.to_string()).collect(),
+                denylist: vec![],
+            })
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn auto_mode_resolves_off_for_local_only() {
+        assert!(matches!(auto(false, "127.0.0.1", &[]).effective_auth_mode(), ProxyAuthMode::Off));
+        // 开放局域网访问但监听回环地址
+        assert!(matches!(auto(true, "::1", &[]).effective_auth_mode(), ProxyAuthMode::Off));
     }
 
     #[test]
     fn auto_mode_resolves_all_except_health_for_lan() {
-        let s = ProxySecurityConfig {
-            auth_mode: ProxyAuthMode::Auto,
-            api_key: "sk-test".to_string(),
-            allow_lan_access: true,
-        };
         assert!(matches!(
-            s.effective_auth_mode(),
+            auto(true, "0.0.0.0", &[]).effective_auth_mode(),
+            ProxyAuthMode::AllExceptHealth
+        ));
+        assert!(matches!(
+            auto(true, "192.168.1.5", &[]).effective_auth_mode(),
             ProxyAuthMode::AllExceptHealth
         ));
     }
-}
 
+    #[test]
+    fn auto_mode_skips_auth_only_when_allowlist_is_loopback() {
+        assert!(matches!(
+            auto(true, "0.0.0.0", &["127.0.0.1", "::1"]).effective_auth_mode(),
+            ProxyAuthMode::Off
+        ));
+        // 允许列表包含局域网地址时仍需认证
+        for allowlist in [&["192.168.1.20"][..], &["127.0.0.1", "192.168.1.0/24"][..]] {
+            assert!(matches!(
+                auto(true, "0.0.0.0", allowlist).effective_auth_mode(),
+                ProxyAuthMode::AllExceptHealth
+            ));
+        }
+    }
+}
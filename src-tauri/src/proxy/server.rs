@@ -7,120 +7,225 @@ use axum::{
     Router,
 };
 use std::sync::Arc;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 use tower_http::trace::TraceLayer;
 use tracing::{debug, error};
-use tokio::sync::RwLock;
-use std::sync::atomic::AtomicUsize;
+use crate::proxy::config::ProxyConfig;
+use crate::proxy::runtime::{RuntimeChanges, RuntimeConfig};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
 
 /// Axum 应用状态
 #[derive(Clone)]
 pub struct AppState {
     pub token_manager: Arc<TokenManager>,
-    pub anthropic_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    pub openai_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    pub custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    #[allow(dead_code)]
-    pub request_timeout: u64, // API 请求超时(秒)
+    /// 运行时配置快照 (模型映射 / 上游客户端 / z.ai / 安全策略 / 超时等)，保存配置时整体替换
+    pub runtime: Arc<RuntimeConfig>,
     #[allow(dead_code)]
     pub thought_signature_map: Arc<tokio::sync::Mutex<std::collections::HashMap<String, String>>>, // 思维链签名映射 (ID -> Signature)
-    #[allow(dead_code)]
-    pub upstream_proxy: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
-    pub upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
-    pub zai: Arc<RwLock<crate::proxy::ZaiConfig>>,
     pub provider_rr: Arc<AtomicUsize>,
     pub zai_vision_mcp: Arc<crate::proxy::zai_vision_mcp::ZaiVisionMcpState>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
+    pub key_limiter: Arc<crate::proxy::key_limits::KeyLimiter>,
+}
+
+/// 连接跟踪 (活跃连接数 / 是否正在停机排空)
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    active: AtomicUsize,
+    draining: AtomicBool,
+}
+
+impl ConnectionTracker {
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
 }
 
+/// 连接任务结束 (含被取消) 时计数减一
+struct ConnectionGuard(Arc<ConnectionTracker>);
+
+impl ConnectionGuard {
+    fn new(tracker: Arc<ConnectionTracker>) -> Self {
+        tracker.active.fetch_add(1, Ordering::Relaxed);
+        Self(tracker)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 监听切换请求 (新地址, 切换结果)
+type RebindRequest = (String, oneshot::Sender<Result<SocketAddr, String>>);
+
 /// Axum 服务器实例
 pub struct AxumServer {
-    shutdown_tx: Option<oneshot::Sender<()>>,
-    anthropic_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    openai_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
-    security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
-    zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
+    /// 停机信号，携带排空超时
+    shutdown_tx: Option<oneshot::Sender<Duration>>,
+    /// 切换监听地址 / 端口
+    rebind_tx: mpsc::UnboundedSender<RebindRequest>,
+    connections: Arc<ConnectionTracker>,
+    runtime: Arc<RuntimeConfig>,
+    /// 完整的路由 (含中间件)，用于进程内重放请求
+    router: Router,
+    token_manager: Arc<TokenManager>,
+    monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
+    key_limiter: Arc<crate::proxy::key_limits::KeyLimiter>,
+    /// 启用 TLS 时的证书 (支持热替换) 与证书文件监听
+    tls_store: Option<Arc<crate::proxy::tls::CertStore>>,
+    tls_watcher: std::sync::Mutex<Option<crate::proxy::tls::CertWatcher>>,
+    unix_socket_path: Option<std::path::PathBuf>,
 }
 
 impl AxumServer {
-    pub async fn update_mapping(&self, config: &crate::proxy::config::ProxyConfig) {
-        {
-            let mut m = self.anthropic_mapping.write().await;
-            *m = config.anthropic_mapping.clone();
+    /// 获取进程内路由，请求经过与外部请求相同的鉴权 / 监控 / 处理链路
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+
+    pub fn key_limiter(&self) -> Arc<crate::proxy::key_limits::KeyLimiter> {
+        self.key_limiter.clone()
+    }
+
+    pub fn runtime(&self) -> Arc<RuntimeConfig> {
+        self.runtime.clone()
+    }
+
+    /// 正在监听的 Unix socket 路径
+    pub fn unix_socket_path(&self) -> Option<&std::path::Path> {
+        self.unix_socket_path.as_deref()
+    }
+
+    /// 热应用完整配置: 替换运行时快照并同步各组件。
+    /// 端口 / 监听地址变化时切换监听，已建立的连接不受影响；
+    /// 切换失败时继续使用原监听并返回错误，其余配置照常生效
+    pub async fn apply_config(&self, config: &ProxyConfig) -> Result<(), String> {
+        let mut config = config.clone();
+        let current = self.runtime.load();
+
+        // Unix socket 监听与是否开放 TCP 需要重启服务后生效
+        if config.unix_socket != current.config.unix_socket {
+            tracing::warn!("Unix socket 监听配置已变更，重启反代服务后生效");
+            config.unix_socket = current.config.unix_socket.clone();
         }
-        {
-            let mut m = self.openai_mapping.write().await;
-            *m = config.openai_mapping.clone();
+        // 启用 / 关闭 TLS 与 HTTP/2 协商同样需要重启；运行时保留当前值，避免 local_base_url 等按未生效的配置报告协议
+        if config.tls.enabled != current.config.tls.enabled || config.tls.http2 != current.config.tls.http2 {
+            tracing::warn!("TLS 监听配置已变更，重启反代服务后生效");
+            config.tls.enabled = current.config.tls.enabled;
+            config.tls.http2 = current.config.tls.http2;
         }
-        {
-            let mut m = self.custom_mapping.write().await;
-            *m = config.custom_mapping.clone();
+
+        let mut rebind_error = None;
+        if config.tcp_enabled() && RuntimeChanges::between(&current.config, &config).listener {
+            let addr = listen_addr(config.get_bind_address(), config.port);
+            if let Err(e) = self.rebind(addr).await {
+                config.port = current.config.port;
+                config.allow_lan_access = current.config.allow_lan_access;
+                config.bind_address = current.config.bind_address.clone();
+                rebind_error = Some(e);
+            }
+        }
+
+        let changes = self.runtime.apply(config.clone());
+        if changes.egress {
+            tracing::info!("上游代理配置已变更，已重建上游 HTTP 客户端");
+        }
+        self.key_limiter.set_config(config.key_limits.clone());
+        self.update_tls(&config);
+        self.monitor.set_enabled(config.enable_logging);
+        self.monitor.set_storage_config(config.log_storage.clone());
+        self.token_manager.update_sticky_config(config.scheduling.clone()).await;
+        self.token_manager.update_group_config(config.account_groups.clone()).await;
+        tracing::info!("反代服务配置已热更新");
+
+        match rebind_error {
+            Some(e) => Err(format!("切换监听失败，继续使用原地址: {}", e)),
+            None => Ok(()),
         }
-        tracing::debug!("模型映射 (Anthropic/OpenAI/Custom) 已全量热更新");
     }
 
-    /// 更新代理配置
-    pub async fn update_proxy(&self, new_config: crate::proxy::config::UpstreamProxyConfig) {
-        let mut proxy = self.proxy_state.write().await;
-        *proxy = new_config;
-        tracing::info!("上游代理配置已热更新");
+    async fn rebind(&self, addr: String) -> Result<SocketAddr, String> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.rebind_tx
+            .send((addr, reply_tx))
+            .map_err(|_| "反代服务已停止".to_string())?;
+        reply_rx.await.map_err(|_| "反代服务已停止".to_string())?
     }
 
-    pub async fn update_security(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut sec = self.security_state.write().await;
-        *sec = crate::proxy::ProxySecurityConfig::from_proxy_config(config);
-        tracing::info!("反代服务安全配置已热更新");
+    /// 证书路径变化时切换证书
+    fn update_tls(&self, config: &ProxyConfig) {
+        let Some(store) = &self.tls_store else {
+            return;
+        };
+        let paths = (
+            std::path::PathBuf::from(&config.tls.cert_path),
+            std::path::PathBuf::from(&config.tls.key_path),
+        );
+        if store.paths() == paths {
+            return;
+        }
+        match store.set_paths(&config.tls) {
+            Ok(()) => {
+                tracing::info!("TLS 证书路径已热更新: {:?}", paths.0);
+                let watcher = crate::proxy::tls::CertWatcher::start(store.clone())
+                    .map_err(|e| tracing::warn!("{}", e))
+                    .ok();
+                *self.tls_watcher.lock().unwrap() = watcher;
+            }
+            Err(e) => tracing::error!("切换 TLS 证书失败，继续使用原证书: {}", e),
+        }
     }
 
-    pub async fn update_zai(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut zai = self.zai_state.write().await;
-        *zai = config.zai.clone();
-        tracing::info!("z.ai 配置已热更新");
+    /// 手动重新加载 TLS 证书
+    pub fn reload_tls(&self) -> Result<(), String> {
+        self.tls_store.as_ref().ok_or("反代服务未启用 TLS")?.reload()
     }
+
+    /// 仅更新模型映射 (Anthropic / OpenAI / Custom)
+    pub fn update_mapping(&self, config: &ProxyConfig) {
+        self.runtime.update(|c| {
+            c.anthropic_mapping = config.anthropic_mapping.clone();
+            c.openai_mapping = config.openai_mapping.clone();
+            c.custom_mapping = config.custom_mapping.clone();
+        });
+        tracing::debug!("模型映射 (Anthropic/OpenAI/Custom) 已全量热更新");
+    }
+
     /// 启动 Axum 服务器
     pub async fn start(
-        host: String,
-        port: u16,
+        config: ProxyConfig,
         token_manager: Arc<TokenManager>,
-        anthropic_mapping: std::collections::HashMap<String, String>,
-        openai_mapping: std::collections::HashMap<String, String>,
-        custom_mapping: std::collections::HashMap<String, String>,
-        _request_timeout: u64,
-        upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
-        security_config: crate::proxy::ProxySecurityConfig,
-        zai_config: crate::proxy::ZaiConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
-
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
-        let mapping_state = Arc::new(tokio::sync::RwLock::new(anthropic_mapping));
-        let openai_mapping_state = Arc::new(tokio::sync::RwLock::new(openai_mapping));
-        let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
-	        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
-	        let security_state = Arc::new(RwLock::new(security_config));
-	        let zai_state = Arc::new(RwLock::new(zai_config));
-	        let provider_rr = Arc::new(AtomicUsize::new(0));
-	        let zai_vision_mcp_state =
-	            Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
-
-	        let state = AppState {
-	            token_manager: token_manager.clone(),
-	            anthropic_mapping: mapping_state.clone(),
-	            openai_mapping: openai_mapping_state.clone(),
-	            custom_mapping: custom_mapping_state.clone(),
-	            request_timeout: 300, // 5分钟超时
+        let runtime = Arc::new(RuntimeConfig::new(config.clone()));
+        let provider_rr = Arc::new(AtomicUsize::new(0));
+        let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
+        let budgets_path = crate::modules::account::get_data_dir()
+            .ok()
+            .map(|dir| dir.join(crate::proxy::key_limits::BUDGETS_FILE));
+        let key_limiter = Arc::new(crate::proxy::key_limits::KeyLimiter::new(
+            config.key_limits.clone(),
+            budgets_path,
+        ));
+
+        let state = AppState {
+            token_manager: token_manager.clone(),
+            runtime: runtime.clone(),
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(
                 std::collections::HashMap::new(),
             )),
-            upstream_proxy: proxy_state.clone(),
-            upstream: Arc::new(crate::proxy::upstream::client::UpstreamClient::new(Some(
-                upstream_proxy.clone(),
-            ))),
-            zai: zai_state.clone(),
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
             monitor: monitor.clone(),
+            key_limiter: key_limiter.clone(),
         };
 
 
@@ -185,83 +290,295 @@ impl AxumServer {
             .route("/v1/api/event_logging/batch", post(silent_ok_handler))
             .route("/v1/api/event_logging", post(silent_ok_handler))
             .route("/healthz", get(health_check_handler))
+            .route("/admin/logs/search", post(handlers::admin::handle_search_logs))
+            .route("/admin/logs/export", post(handlers::admin::handle_export_logs))
             .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::key_limits::key_limits_middleware))
             .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::monitor::monitor_middleware))
             .layer(TraceLayer::new_for_http())
             .layer(axum::middleware::from_fn_with_state(
-                security_state.clone(),
+                runtime.clone(),
                 crate::proxy::middleware::auth_middleware,
             ))
+            .layer(axum::middleware::from_fn_with_state(
+                runtime.clone(),
+                crate::proxy::middleware::ip_filter_middleware,
+            ))
             .layer(crate::proxy::middleware::cors_layer())
             .with_state(state);
 
-        // 绑定地址
-        let addr = format!("{}:{}", host, port);
-        let listener = tokio::net::TcpListener::bind(&addr)
-            .await
-            .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
+        // 绑定地址 (独占 Unix socket 时不监听 TCP)
+        let addr = listen_addr(config.get_bind_address(), config.port);
+        let tcp = if config.tcp_enabled() {
+            Some(bind(&addr).await.map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?)
+        } else {
+            None
+        };
+        let unix = if config.unix_socket.enabled {
+            config.unix_socket.validate()?;
+            Some(crate::proxy::unix_socket::bind(&config.unix_socket).await?)
+        } else {
+            None
+        };
+        let unix_socket_path = unix.as_ref().map(|u| u.path().to_path_buf());
 
-        tracing::info!("反代服务器启动在 http://{}", addr);
+        // TLS (可选): 证书文件变化时自动热加载
+        let tls = &config.tls;
+        let (tls_store, tls_watcher, acceptor) = if tls.enabled {
+            let store = Arc::new(crate::proxy::tls::CertStore::load(tls)?);
+            let acceptor = crate::proxy::tls::build_acceptor(store.clone(), tls.http2)?;
+            let watcher = crate::proxy::tls::CertWatcher::start(store.clone())
+                .map_err(|e| tracing::warn!("{}", e))
+                .ok();
+            (Some(store), watcher, Some(acceptor))
+        } else {
+            (None, None, None)
+        };
+        let http2 = tls.http2;
 
-        // 创建关闭通道
-        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+        if tcp.is_some() {
+            tracing::info!(
+                "反代服务器启动在 {}://{}",
+                if acceptor.is_some() { "https" } else { "http" },
+                addr
+            );
+        }
+        if let Some(path) = &unix_socket_path {
+            tracing::info!("反代服务器监听 Unix socket {}", path.display());
+        }
+
+        // 创建关闭与切换监听通道
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<Duration>();
+        let (rebind_tx, rebind_rx) = mpsc::unbounded_channel();
+        let connections = Arc::new(ConnectionTracker::default());
 
         let server_instance = Self {
             shutdown_tx: Some(shutdown_tx),
-            anthropic_mapping: mapping_state.clone(),
-            openai_mapping: openai_mapping_state.clone(),
-            custom_mapping: custom_mapping_state.clone(),
-            proxy_state,
-            security_state,
-            zai_state,
+            rebind_tx,
+            connections: connections.clone(),
+            runtime,
+            router: app.clone(),
+            token_manager,
+            monitor,
+            key_limiter,
+            tls_store,
+            tls_watcher: std::sync::Mutex::new(tls_watcher),
+            unix_socket_path,
         };
 
         // 在新任务中启动服务器
-        let handle = tokio::spawn(async move {
-            use hyper::server::conn::http1;
-            use hyper_util::rt::TokioIo;
-            use hyper_util::service::TowerToHyperService;
-
-            loop {
-                tokio::select! {
-                    res = listener.accept() => {
-                        match res {
-                            Ok((stream, _)) => {
-                                let io = TokioIo::new(stream);
-                                let service = TowerToHyperService::new(app.clone());
-
-                                tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, service)
-                                        .with_upgrades() // 支持 WebSocket (如果以后需要)
-                                        .await
-                                    {
-                                        debug!("连接处理结束或出错: {:?}", err);
+        let listeners = Listeners { tcp, unix };
+        let handle = tokio::spawn(serve(listeners, app, acceptor, http2, connections, rebind_rx, shutdown_rx));
+
+        Ok((server_instance, handle))
+    }
+
+    pub fn connections(&self) -> Arc<ConnectionTracker> {
+        self.connections.clone()
+    }
+
+    /// 停止服务器: 不再接受新连接，进行中的请求最多等待 drain_timeout，之后取消剩余连接
+    pub fn stop(mut self, drain_timeout: Duration) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(drain_timeout);
+        }
+    }
+}
+
+/// 监听地址 (IPv6 地址加方括号)
+fn listen_addr(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+async fn bind(addr: &str) -> std::io::Result<tokio::net::TcpListener> {
+    tokio::net::TcpListener::bind(addr).await
+}
+
+/// 切换到新的监听地址，返回 (当前监听, 切换结果)。
+/// 同一端口更换网卡时新地址可能与原监听冲突，此时先释放原监听再绑定，失败则恢复原监听；
+/// 原监听也无法恢复时返回 None (不再接受新连接)
+async fn rebind(
+    listener: tokio::net::TcpListener,
+    addr: &str,
+) -> (Option<tokio::net::TcpListener>, Result<SocketAddr, String>) {
+    let err = match bind(addr).await {
+        Ok(new_listener) => {
+            let local = new_listener.local_addr().map_err(|e| e.to_string());
+            return (Some(new_listener), local);
+        }
+        Err(e) => e,
+    };
+    let old_addr = match listener.local_addr() {
+        Ok(old_addr) if err.kind() == std::io::ErrorKind::AddrInUse => old_addr,
+        _ => return (Some(listener), Err(format!("地址 {} 绑定失败: {}", addr, err))),
+    };
+    if !addr.ends_with(&format!(":{}", old_addr.port())) {
+        return (Some(listener), Err(format!("地址 {} 绑定失败: {}", addr, err)));
+    }
+
+    drop(listener);
+    match bind(addr).await {
+        Ok(new_listener) => {
+            let local = new_listener.local_addr().map_err(|e| e.to_string());
+            (Some(new_listener), local)
+        }
+        Err(e) => {
+            let restored = bind(&old_addr.to_string()).await;
+            if let Err(restore_err) = &restored {
+                error!("恢复原监听 {} 失败，停止接受新连接: {}", old_addr, restore_err);
+            }
+            (restored.ok(), Err(format!("地址 {} 绑定失败: {}", addr, e)))
+        }
+    }
+}
+
+/// 服务器监听 (TCP 与 Unix socket 至少其一)
+struct Listeners {
+    tcp: Option<tokio::net::TcpListener>,
+    unix: Option<crate::proxy::unix_socket::UnixSocketListener>,
+}
+
+/// HTTP/1.1 与 HTTP/2 自动识别 (TLS 下由 ALPN 协商)
+fn http_builder(http2: bool) -> hyper_util::server::conn::auto::Builder<hyper_util::rt::TokioExecutor> {
+    let mut builder = hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
+    if !http2 {
+        builder = builder.http1_only();
+    }
+    builder
+}
+
+/// 接受连接并处理请求，收到停机信号后排空连接
+async fn serve(
+    listeners: Listeners,
+    app: Router,
+    acceptor: Option<tokio_rustls::TlsAcceptor>,
+    http2: bool,
+    connections: Arc<ConnectionTracker>,
+    mut rebind_rx: mpsc::UnboundedReceiver<RebindRequest>,
+    mut shutdown_rx: oneshot::Receiver<Duration>,
+) {
+    use hyper_util::rt::TokioIo;
+    use hyper_util::server::graceful::GracefulShutdown;
+    use hyper_util::service::TowerToHyperService;
+    use tower::Layer;
+
+    let graceful = GracefulShutdown::new();
+    let mut tasks = tokio::task::JoinSet::new();
+    let Listeners { tcp: mut listener, unix } = listeners;
+
+    let drain_timeout = loop {
+        tokio::select! {
+            res = async { listener.as_ref().unwrap().accept().await }, if listener.is_some() => {
+                match res {
+                    Ok((stream, peer)) => {
+                        // 注入客户端地址 (ConnectInfo)，供按 IP 限流等使用
+                        let service = TowerToHyperService::new(
+                            axum::Extension(axum::extract::ConnectInfo(peer)).layer(app.clone()),
+                        );
+                        let acceptor = acceptor.clone();
+                        // 在接受连接时订阅停机信号，TLS 握手期间发起的停机同样生效
+                        let watcher = graceful.watcher();
+                        let guard = ConnectionGuard::new(connections.clone());
+
+                        tasks.spawn(async move {
+                            let _guard = guard;
+                            let builder = http_builder(http2);
+                            // 支持 WebSocket (如果以后需要)
+                            let result = match acceptor {
+                                Some(acceptor) => match acceptor.accept(stream).await {
+                                    Ok(tls_stream) => {
+                                        watcher
+                                            .watch(builder.serve_connection_with_upgrades(TokioIo::new(tls_stream), service))
+                                            .await
                                     }
-                                });
+                                    Err(e) => {
+                                        debug!("TLS 握手失败 ({}): {}", peer, e);
+                                        return;
+                                    }
+                                },
+                                None => {
+                                    watcher
+                                        .watch(builder.serve_connection_with_upgrades(TokioIo::new(stream), service))
+                                        .await
+                                }
+                            };
+                            if let Err(err) = result {
+                                debug!("连接处理结束或出错: {:?}", err);
                             }
-                            Err(e) => {
-                                error!("接收连接失败: {:?}", e);
+                        });
+                    }
+                    Err(e) => {
+                        error!("接收连接失败: {:?}", e);
+                    }
+                }
+            }
+            // Unix socket 连接: 由文件权限控制访问，没有客户端地址 (不注入 ConnectInfo)
+            res = async { unix.as_ref().unwrap().accept().await }, if unix.is_some() => {
+                match res {
+                    Ok(stream) => {
+                        let service = TowerToHyperService::new(app.clone());
+                        let watcher = graceful.watcher();
+                        let guard = ConnectionGuard::new(connections.clone());
+
+                        tasks.spawn(async move {
+                            let _guard = guard;
+                            let builder = http_builder(http2);
+                            let result = watcher
+                                .watch(builder.serve_connection_with_upgrades(TokioIo::new(stream), service))
+                                .await;
+                            if let Err(err) = result {
+                                debug!("Unix socket 连接处理结束或出错: {:?}", err);
                             }
-                        }
+                        });
                     }
-                    _ = &mut shutdown_rx => {
-                        tracing::info!("反代服务器停止监听");
-                        break;
+                    Err(e) => {
+                        error!("接收 Unix socket 连接失败: {:?}", e);
                     }
                 }
             }
-        });
+            // 回收已结束的连接任务
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+            // 切换监听: 原监听关闭后不再接受新连接，已建立的连接继续处理
+            Some((addr, reply)) = rebind_rx.recv() => {
+                let Some(current) = listener.take() else {
+                    let _ = reply.send(Err("反代服务已停止监听".to_string()));
+                    continue;
+                };
+                let (current, result) = rebind(current, &addr).await;
+                listener = current;
+                match &result {
+                    Ok(local) => tracing::info!("反代服务器已切换监听到 {}", local),
+                    Err(e) => tracing::warn!("{}", e),
+                }
+                let _ = reply.send(result);
+            }
+            res = &mut shutdown_rx => {
+                tracing::info!("反代服务器停止监听");
+                // 服务器实例被直接 drop 时不等待
+                break res.unwrap_or_default();
+            }
+        }
+    };
 
-        Ok((server_instance, handle))
+    // 停止接受新连接 (删除 socket 文件)，通知现有连接处理完进行中的请求后关闭 (HTTP/2 发送 GOAWAY)
+    drop(listener);
+    drop(unix);
+    connections.draining.store(true, Ordering::Relaxed);
+    let remaining = connections.active();
+    if remaining > 0 {
+        tracing::info!("等待 {} 个连接处理完成 (最长 {} 秒)", remaining, drain_timeout.as_secs());
     }
-
-    /// 停止服务器
-    pub fn stop(mut self) {
-        if let Some(tx) = self.shutdown_tx.take() {
-            let _ = tx.send(());
-        }
+    if tokio::time::timeout(drain_timeout, graceful.shutdown()).await.is_err() {
+        tracing::warn!("排空超时，取消剩余 {} 个连接", connections.active());
     }
+    // 取消仍未结束的连接 (排空超时或未进入 HTTP 处理的连接)
+    tasks.shutdown().await;
+    connections.draining.store(false, Ordering::Relaxed);
+    tracing::info!("反代服务器已停止");
 }
 
 // ===== API 处理器 (旧代码已移除，由 src/proxy/handlers/* 接管) =====
@@ -278,3 +595,164 @@ async fn health_check_handler() -> Response {
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+
+    /// 每 50ms 输出一个分块的流式接口
+    fn streaming_router() -> Router {
+        Router::new().route(
+            "/stream/:chunks",
+            get(|Path(chunks): Path<u64>| async move {
+                axum::body::Body::from_stream(async_stream::stream! {
+                    for i in 0..chunks {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        yield Ok::<_, std::io::Error>(format!("{}\n", i));
+                    }
+                })
+            }),
+        )
+    }
+
+    struct TestServer {
+        addr: SocketAddr,
+        connections: Arc<ConnectionTracker>,
+        rebind_tx: mpsc::UnboundedSender<RebindRequest>,
+        shutdown_tx: oneshot::Sender<Duration>,
+        handle: tokio::task::JoinHandle<()>,
+    }
+
+    async fn start_server() -> TestServer {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(ConnectionTracker::default());
+        let (rebind_tx, rebind_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let handle = tokio::spawn(serve(
+            Listeners { tcp: Some(listener), unix: None },
+            streaming_router(),
+            None,
+            true,
+            connections.clone(),
+            rebind_rx,
+            shutdown_rx,
+        ));
+        TestServer { addr, connections, rebind_tx, shutdown_tx, handle }
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stop_drains_in_flight_requests() {
+        let TestServer { addr, connections, shutdown_tx: tx, handle, .. } = start_server().await;
+        let request = tokio::spawn(client().get(format!("http://{}/stream/6", addr)).send());
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(connections.active(), 1);
+
+        tx.send(Duration::from_secs(5)).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(connections.is_draining());
+        // 停止后不再接受新连接
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+
+        // 进行中的流式响应完整结束
+        assert_eq!(response.text().await.unwrap().lines().count(), 6);
+        handle.await.unwrap();
+        assert_eq!(connections.active(), 0);
+        assert!(!connections.is_draining());
+    }
+
+    #[tokio::test]
+    async fn test_stop_cancels_after_drain_timeout() {
+        let TestServer { addr, connections, shutdown_tx: tx, handle, .. } = start_server().await;
+        let response = client().get(format!("http://{}/stream/1000", addr)).send().await.unwrap();
+
+        let started = std::time::Instant::now();
+        tx.send(Duration::from_millis(200)).unwrap();
+        handle.await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(connections.active(), 0);
+        // 被取消的响应以错误结束
+        assert!(response.text().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rebind_keeps_existing_connections() {
+        let server = start_server().await;
+        let response = client().get(format!("http://{}/stream/6", server.addr)).send().await.unwrap();
+
+        // 切换到新端口: 原端口不再接受连接，进行中的请求不受影响
+        let (reply_tx, reply_rx) = oneshot::channel();
+        server.rebind_tx.send(("127.0.0.1:0".to_string(), reply_tx)).unwrap();
+        let new_addr = reply_rx.await.unwrap().unwrap();
+        assert_ne!(new_addr, server.addr);
+        assert!(tokio::net::TcpStream::connect(server.addr).await.is_err());
+        let body = client().get(format!("http://{}/stream/2", new_addr)).send().await.unwrap();
+        assert_eq!(body.text().await.unwrap().lines().count(), 2);
+        assert_eq!(response.text().await.unwrap().lines().count(), 6);
+
+        // 同一端口更换监听地址 (与原监听冲突时先释放再绑定)
+        let (reply_tx, reply_rx) = oneshot::channel();
+        server.rebind_tx.send((format!("0.0.0.0:{}", new_addr.port()), reply_tx)).unwrap();
+        assert_eq!(reply_rx.await.unwrap().unwrap().port(), new_addr.port());
+        let body = client().get(format!("http://{}/stream/1", new_addr)).send().await.unwrap();
+        assert_eq!(body.text().await.unwrap().lines().count(), 1);
+
+        // 绑定失败时继续使用原监听
+        let (reply_tx, reply_rx) = oneshot::channel();
+        server.rebind_tx.send(("192.0.2.1:0".to_string(), reply_tx)).unwrap();
+        assert!(reply_rx.await.unwrap().is_err());
+        let body = client().get(format!("http://{}/stream/1", new_addr)).send().await.unwrap();
+        assert_eq!(body.text().await.unwrap().lines().count(), 1);
+
+        server.shutdown_tx.send(Duration::from_secs(1)).unwrap();
+        server.handle.await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_only() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = std::env::temp_dir().join(format!("ag-sock-{}", uuid::Uuid::new_v4().simple()));
+        let config = crate::proxy::unix_socket::UnixSocketConfig {
+            enabled: true,
+            path: dir.join("proxy.sock").to_string_lossy().to_string(),
+            exclusive: true,
+            ..Default::default()
+        };
+        let unix = crate::proxy::unix_socket::bind(&config).await.unwrap();
+        let path = unix.path().to_path_buf();
+        let (_rebind_tx, rebind_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let handle = tokio::spawn(serve(
+            Listeners { tcp: None, unix: Some(unix) },
+            streaming_router(),
+            None,
+            true,
+            Arc::new(ConnectionTracker::default()),
+            rebind_rx,
+            shutdown_rx,
+        ));
+
+        let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET /stream/2 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("1\n"));
+
+        shutdown_tx.send(Duration::from_secs(1)).unwrap();
+        handle.await.unwrap();
+        // 停止后删除 socket 文件
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
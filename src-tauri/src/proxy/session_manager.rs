@@ -1,12 +1,39 @@
 use sha2::{Sha256, Digest};
+use axum::http::HeaderMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use crate::proxy::mappers::claude::models::{ClaudeRequest, MessageContent};
 use crate::proxy::mappers::openai::models::{OpenAIRequest, OpenAIContent};
 use serde_json::Value;
 
+/// 客户端显式指定会话 ID 的请求头 (三种协议通用)，优先级高于内容指纹
+pub const SESSION_HEADER: &str = "x-session-id";
+
+/// 显式会话 ID 的最大长度，超出视为无效
+const MAX_SESSION_HEADER_LEN: usize = 128;
+
+/// 客户端 (如 Claude Code) 每轮都会变化的 system-reminder 片段，不参与指纹计算
+static SYSTEM_REMINDER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<system-reminder>.*?</system-reminder>").unwrap());
+
+/// 移除 system-reminder 片段，返回剩余的有效文本
+fn strip_system_reminders(text: &str) -> String {
+    SYSTEM_REMINDER_RE.replace_all(text, " ").trim().to_string()
+}
+
 /// 会话管理器工具
 pub struct SessionManager;
 
 impl SessionManager {
+    /// 从请求头中读取客户端显式指定的会话 ID
+    pub fn session_id_from_headers(headers: &HeaderMap) -> Option<String> {
+        let value = headers.get(SESSION_HEADER)?.to_str().ok()?.trim();
+        if value.is_empty() || value.len() > MAX_SESSION_HEADER_LEN {
+            return None;
+        }
+        Some(value.to_string())
+    }
+
     /// 根据 Claude 请求生成稳定的会话指纹 (Session Fingerprint)
     pub fn extract_session_id(request: &ClaudeRequest) -> String {
         // 1. 优先使用 metadata 中的 user_id
@@ -30,21 +57,22 @@ impl SessionManager {
             if msg.role != "user" { continue; }
             
             let text = match &msg.content {
-                MessageContent::String(s) => s.clone(),
+                MessageContent::String(s) => strip_system_reminders(s),
                 MessageContent::Array(blocks) => {
                     blocks.iter()
                         .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
+                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(strip_system_reminders(text)),
                             _ => None,
                         })
+                        .filter(|t| !t.is_empty())
                         .collect::<Vec<_>>()
                         .join(" ")
                 }
             };
 
             let clean_text = text.trim();
-            // 跳过过短的消息 (可能是 CLI 的探测消息) 或含有系统标签的消息
-            if clean_text.len() > 10 && !clean_text.contains("<system-reminder>") {
+            // 跳过过短的消息 (可能是 CLI 的探测消息)；system-reminder 片段每轮都会变化，已在上面剔除
+            if clean_text.len() > 10 {
                 hasher.update(clean_text.as_bytes());
                 content_found = true;
                 break; // 只取第一条关键消息作为锚点
@@ -52,9 +80,9 @@ impl SessionManager {
         }
 
         if !content_found {
-            // 如果没找到有意义的内容，退化为对最后一条消息进行哈希
-            if let Some(last_msg) = request.messages.last() {
-                hasher.update(format!("{:?}", last_msg.content).as_bytes());
+            // 如果没找到有意义的内容，退化为对第一条消息进行哈希 (后续轮次中保持不变)
+            if let Some(first_msg) = request.messages.first() {
+                hasher.update(format!("{:?}", first_msg.content).as_bytes());
             }
         }
 
@@ -75,20 +103,21 @@ impl SessionManager {
             if msg.role != "user" { continue; }
             if let Some(content) = &msg.content {
                 let text = match content {
-                    OpenAIContent::String(s) => s.clone(),
+                    OpenAIContent::String(s) => strip_system_reminders(s),
                     OpenAIContent::Array(blocks) => {
                         blocks.iter()
                             .filter_map(|block| match block {
-                                crate::proxy::mappers::openai::models::OpenAIContentBlock::Text { text } => Some(text.as_str()),
+                                crate::proxy::mappers::openai::models::OpenAIContentBlock::Text { text } => Some(strip_system_reminders(text)),
                                 _ => None,
                             })
+                            .filter(|t| !t.is_empty())
                             .collect::<Vec<_>>()
                             .join(" ")
                     }
                 };
 
                 let clean_text = text.trim();
-                if clean_text.len() > 10 && !clean_text.contains("<system-reminder>") {
+                if clean_text.len() > 10 {
                     hasher.update(clean_text.as_bytes());
                     content_found = true;
                     break;
@@ -97,8 +126,8 @@ impl SessionManager {
         }
 
         if !content_found {
-            if let Some(last_msg) = request.messages.last() {
-                hasher.update(format!("{:?}", last_msg.content).as_bytes());
+            if let Some(first_msg) = request.messages.first() {
+                hasher.update(format!("{:?}", first_msg.content).as_bytes());
             }
         }
 
@@ -122,13 +151,16 @@ impl SessionManager {
                     let mut text_parts = Vec::new();
                     for part in parts {
                         if let Some(text) = part.get("text").and_then(|v| v.as_str()) {
-                            text_parts.push(text);
+                            let text = strip_system_reminders(text);
+                            if !text.is_empty() {
+                                text_parts.push(text);
+                            }
                         }
                     }
                     
                     let combined_text = text_parts.join(" ");
                     let clean_text = combined_text.trim();
-                    if clean_text.len() > 10 && !clean_text.contains("<system-reminder>") {
+                    if clean_text.len() > 10 {
                         hasher.update(clean_text.as_bytes());
                         content_found = true;
                         break;
@@ -138,8 +170,12 @@ impl SessionManager {
         }
 
         if !content_found {
-             // 兜底：对整个 Body 的首个 user part 进行摘要
-             hasher.update(request.to_string().as_bytes());
+             // 兜底：对首条 content 进行摘要 (整个 Body 每轮都会增长，不能作为锚点)
+             let anchor = request
+                 .get("contents")
+                 .and_then(|c| c.get(0))
+                 .unwrap_or(request);
+             hasher.update(anchor.to_string().as_bytes());
         }
 
         let hash = format!("{:x}", hasher.finalize());
@@ -148,3 +184,75 @@ impl SessionManager {
         sid
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claude_request(messages: Value) -> ClaudeRequest {
+        serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": messages
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_claude_fingerprint_ignores_system_reminder_churn() {
+        let first_turn = claude_request(json!([
+            { "role": "user", "content": [
+                { "type": "text", "text": "<system-reminder>Today is Monday. Todo list is empty.</system-reminder>" },
+                { "type": "text", "text": "Please refactor the session manager module" }
+            ]}
+        ]));
+        let later_turn = claude_request(json!([
+            { "role": "user", "content": [
+                { "type": "text", "text": "<system-reminder>Today is Tuesday. 3 todos pending.</system-reminder>" },
+                { "type": "text", "text": "Please refactor the session manager module" }
+            ]},
+            { "role": "assistant", "content": "Sure, reading the file now." },
+            { "role": "user", "content": "<system-reminder>File changed</system-reminder> continue" }
+        ]));
+
+        assert_eq!(
+            SessionManager::extract_session_id(&first_turn),
+            SessionManager::extract_session_id(&later_turn)
+        );
+    }
+
+    #[test]
+    fn test_gemini_fingerprint_stable_across_turns() {
+        let first_turn = json!({
+            "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }]
+        });
+        let later_turn = json!({
+            "contents": [
+                { "role": "user", "parts": [{ "text": "hi" }] },
+                { "role": "model", "parts": [{ "text": "Hello!" }] },
+                { "role": "user", "parts": [{ "text": "ok" }] }
+            ]
+        });
+
+        assert_eq!(
+            SessionManager::extract_gemini_session_id(&first_turn, "gemini-2.5-flash"),
+            SessionManager::extract_gemini_session_id(&later_turn, "gemini-2.5-flash")
+        );
+    }
+
+    #[test]
+    fn test_session_id_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(SessionManager::session_id_from_headers(&headers), None);
+
+        headers.insert(SESSION_HEADER, " conversation-42 ".parse().unwrap());
+        assert_eq!(
+            SessionManager::session_id_from_headers(&headers),
+            Some("conversation-42".to_string())
+        );
+
+        headers.insert(SESSION_HEADER, "x".repeat(200).parse().unwrap());
+        assert_eq!(SessionManager::session_id_from_headers(&headers), None);
+    }
+}
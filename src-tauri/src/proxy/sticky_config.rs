@@ -9,6 +9,9 @@ pub enum SchedulingMode {
     Balance,
     /// 性能优先 (Performance-first): 纯轮询模式 (Round-robin)，账号负载最均衡，但不利用缓存
     PerformanceFirst,
+    /// 加权轮询 (Weighted round-robin): 按账号优先级分层，同层内按权重比例分配新会话，
+    /// 会话建立后与平衡模式一样保持粘性
+    WeightedRoundRobin,
 }
 
 impl Default for SchedulingMode {
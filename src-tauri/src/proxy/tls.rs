@@ -0,0 +1,319 @@
+// 反代监听 TLS 终止
+// - 从 PEM 文件加载证书链与私钥，证书文件变化时自动热加载 (无需重启服务，已建立的连接不受影响)
+// - ALPN 协商 HTTP/2 (h2) 与 HTTP/1.1
+// - 局域网使用时可生成自签名证书 (ECDSA P-256)
+use chrono::Datelike;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use rcgen::{
+    date_time_ymd, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair as RcgenKeyPair,
+    SerialNumber, PKCS_ECDSA_P256_SHA256,
+};
+use ring::rand::SystemRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+
+/// 证书目录 (位于数据目录)
+pub const TLS_DIR: &str = "proxy_tls";
+/// 证书文件连续写入时合并事件
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// 自签名证书有效期
+const SELF_SIGNED_VALID_DAYS: u64 = 825;
+const SELF_SIGNED_CN: &str = "Antigravity Tools Proxy";
+
+/// TLS 配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM 证书链路径
+    pub cert_path: String,
+    /// PEM 私钥路径 (PKCS#8 / PKCS#1 / SEC1)
+    pub key_path: String,
+    /// 通过 ALPN 提供 HTTP/2
+    pub http2: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: String::new(),
+            key_path: String::new(),
+            http2: true,
+        }
+    }
+}
+
+/// 从 PEM 文件加载证书与私钥
+pub fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<Arc<CertifiedKey>, String> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .map_err(|e| format!("读取证书失败 {:?}: {}", cert_path, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("解析证书失败 {:?}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("证书文件中没有证书: {:?}", cert_path));
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| format!("读取私钥失败 {:?}: {}", key_path, e))?;
+    let signing_key =
+        rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| format!("不支持的私钥类型: {}", e))?;
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified
+        .keys_match()
+        .map_err(|e| format!("证书与私钥不匹配: {}", e))?;
+    Ok(Arc::new(certified))
+}
+
+/// 当前使用的证书，支持热替换
+#[derive(Debug)]
+pub struct CertStore {
+    paths: RwLock<(PathBuf, PathBuf)>,
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertStore {
+    pub fn load(config: &TlsConfig) -> Result<Self, String> {
+        let paths = (PathBuf::from(&config.cert_path), PathBuf::from(&config.key_path));
+        let current = load_certified_key(&paths.0, &paths.1)?;
+        Ok(Self {
+            paths: RwLock::new(paths),
+            current: RwLock::new(current),
+        })
+    }
+
+    pub fn paths(&self) -> (PathBuf, PathBuf) {
+        self.paths.read().unwrap().clone()
+    }
+
+    /// 重新加载证书，失败时保留原证书
+    pub fn reload(&self) -> Result<(), String> {
+        let (cert, key) = self.paths();
+        let certified = load_certified_key(&cert, &key)?;
+        *self.current.write().unwrap() = certified;
+        tracing::info!("TLS 证书已重新加载: {:?}", cert);
+        Ok(())
+    }
+
+    /// 切换证书路径并加载 (失败时保持原路径与证书)
+    pub fn set_paths(&self, config: &TlsConfig) -> Result<(), String> {
+        let paths = (PathBuf::from(&config.cert_path), PathBuf::from(&config.key_path));
+        let certified = load_certified_key(&paths.0, &paths.1)?;
+        *self.paths.write().unwrap() = paths;
+        *self.current.write().unwrap() = certified;
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// 构造 TLS acceptor (ALPN: h2 + http/1.1 或仅 http/1.1)
+pub fn build_acceptor(store: Arc<CertStore>, http2: bool) -> Result<tokio_rustls::TlsAcceptor, String> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("初始化 TLS 失败: {}", e))?
+        .with_no_client_auth()
+        .with_cert_resolver(store);
+    config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(tokio_rustls::TlsAcceptor::from(Arc::new(config)))
+}
+
+/// 证书文件监听器，drop 时自动停止
+pub struct CertWatcher {
+    _watcher: RecommendedWatcher,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl CertWatcher {
+    pub fn start(store: Arc<CertStore>) -> Result<Self, String> {
+        let (cert, key) = store.paths();
+        let names: HashSet<_> = [&cert, &key].iter().filter_map(|p| p.file_name().map(|n| n.to_owned())).collect();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                if matches!(event.kind, EventKind::Access(_)) {
+                    return;
+                }
+                // 监听所在目录，兼容通过重命名 / 软链接替换证书的签发工具
+                if event.paths.iter().any(|p| p.file_name().is_some_and(|n| names.contains(n))) {
+                    let _ = tx.send(());
+                }
+            }
+            Err(e) => tracing::warn!("TLS cert watcher error: {}", e),
+        })
+        .map_err(|e| format!("创建证书监听失败: {}", e))?;
+
+        let dirs: HashSet<PathBuf> = [&cert, &key]
+            .iter()
+            .map(|p| p.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf())
+            .collect();
+        for dir in &dirs {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("监听证书目录失败 {:?}: {}", dir, e))?;
+        }
+
+        let task = tokio::spawn(async move {
+            while rx.recv().await.is_some() {
+                tokio::time::sleep(DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                if let Err(e) = store.reload() {
+                    tracing::warn!("TLS 证书热加载失败，继续使用原证书: {}", e);
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher, task })
+    }
+}
+
+impl Drop for CertWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 生成自签名证书，写入 `dir/cert.pem` 与 `dir/key.pem`
+/// `hosts` 为额外的主机名 / IP (localhost、127.0.0.1、::1 始终包含)
+pub fn generate_self_signed(dir: &Path, hosts: &[String]) -> Result<(PathBuf, PathBuf), String> {
+    let (cert_pem, key_pem) = self_signed_pem(hosts)?;
+    std::fs::create_dir_all(dir).map_err(|e| format!("创建证书目录失败: {}", e))?;
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    crate::utils::fs::write_private_file(&key_path, key_pem.as_bytes()).map_err(|e| format!("写入私钥失败: {}", e))?;
+    std::fs::write(&cert_path, cert_pem).map_err(|e| format!("写入证书失败: {}", e))?;
+    Ok((cert_path, key_path))
+}
+
+/// 生成自签名证书与 PKCS#8 私钥 (PEM)
+fn self_signed_pem(hosts: &[String]) -> Result<(String, String), String> {
+    let mut names: Vec<String> = vec!["localhost".into(), "127.0.0.1".into(), "::1".into()];
+    for host in hosts.iter().map(|h| h.trim()).filter(|h| !h.is_empty()) {
+        if !names.iter().any(|n| n == host) {
+            names.push(host.to_string());
+        }
+    }
+
+    // IP 形式的名称写入 iPAddress SAN，其余写入 dNSName
+    let mut params = CertificateParams::new(names).map_err(|e| format!("无效的主机名: {}", e))?;
+    params.distinguished_name.push(DnType::CommonName, SELF_SIGNED_CN);
+    params.is_ca = IsCa::ExplicitNoCa;
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+    let mut serial = [0u8; 16];
+    ring::rand::SecureRandom::fill(&SystemRandom::new(), &mut serial)
+        .map_err(|_| "生成随机数失败".to_string())?;
+    serial[0] &= 0x7f;
+    params.serial_number = Some(SerialNumber::from(serial.to_vec()));
+
+    let today = chrono::Utc::now().date_naive();
+    let date = |d: chrono::NaiveDate| date_time_ymd(d.year(), d.month() as u8, d.day() as u8);
+    params.not_before = date(today - chrono::Days::new(1));
+    params.not_after = date(today + chrono::Days::new(SELF_SIGNED_VALID_DAYS));
+
+    let key_pair = RcgenKeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).map_err(|e| format!("生成私钥失败: {}", e))?;
+    let cert = params.self_signed(&key_pair).map_err(|e| format!("签名证书失败: {}", e))?;
+    Ok((cert.pem(), key_pair.serialize_pem()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_self_signed_handshake_and_reload() {
+        let dir = std::env::temp_dir().join(format!("proxy-tls-{}", uuid::Uuid::new_v4()));
+        let (cert_path, key_path) = generate_self_signed(&dir, &["192.168.1.10".to_string()]).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&key_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let config = TlsConfig {
+            enabled: true,
+            cert_path: cert_path.to_string_lossy().to_string(),
+            key_path: key_path.to_string_lossy().to_string(),
+            http2: true,
+        };
+        let store = Arc::new(CertStore::load(&config).unwrap());
+        let acceptor = build_acceptor(store.clone(), true).unwrap();
+
+        // 以生成的证书作为信任根，完整校验证书签名、有效期与 SAN
+        let handshake = |cert_path: PathBuf, server_name: &'static str| {
+            let acceptor = acceptor.clone();
+            async move {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(&cert_path).unwrap() {
+                    roots.add(cert.unwrap()).unwrap();
+                }
+                let mut client = rustls::ClientConfig::builder_with_provider(Arc::new(
+                    rustls::crypto::ring::default_provider(),
+                ))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+                client.alpn_protocols = vec![b"h2".to_vec()];
+                let connector = tokio_rustls::TlsConnector::from(Arc::new(client));
+
+                let (client_io, server_io) = tokio::io::duplex(16 * 1024);
+                let server = tokio::spawn(async move {
+                    let mut stream = acceptor.accept(server_io).await?;
+                    let alpn = stream.get_ref().1.alpn_protocol().map(|p| p.to_vec());
+                    stream.write_all(b"ok").await?;
+                    stream.shutdown().await?;
+                    Ok::<_, std::io::Error>(alpn)
+                });
+                let name = rustls::pki_types::ServerName::try_from(server_name).unwrap();
+                let mut stream = connector.connect(name, client_io).await?;
+                let mut buf = Vec::new();
+                stream.read_to_end(&mut buf).await?;
+                assert_eq!(buf, b"ok");
+                server.await.unwrap()
+            }
+        };
+
+        let alpn = handshake(cert_path.clone(), "localhost").await.unwrap();
+        assert_eq!(alpn.as_deref(), Some(&b"h2"[..]));
+        assert!(handshake(cert_path.clone(), "192.168.1.10").await.is_ok());
+        assert!(handshake(cert_path.clone(), "example.com").await.is_err());
+
+        // 重新生成证书后热加载，旧证书不再被信任
+        let old_cert = dir.join("old.pem");
+        std::fs::copy(&cert_path, &old_cert).unwrap();
+        generate_self_signed(&dir, &[]).unwrap();
+        store.reload().unwrap();
+        assert!(handshake(cert_path.clone(), "localhost").await.is_ok());
+        assert!(handshake(old_cert, "localhost").await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_mismatched_key_rejected() {
+        let dir = std::env::temp_dir().join(format!("proxy-tls-{}", uuid::Uuid::new_v4()));
+        let (cert_a, _) = generate_self_signed(&dir.join("a"), &[]).unwrap();
+        let (_, key_b) = generate_self_signed(&dir.join("b"), &[]).unwrap();
+        assert!(load_certified_key(&cert_a, &key_b).unwrap_err().contains("不匹配"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,12 @@
 // 移除冗余的顶层导入，因为这些在代码中已由 full path 或局部导入处理
 use dashmap::DashMap;
 use std::collections::HashSet;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::proxy::account_groups::{self, AccountGroupConfig};
 use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::scheduling::{self, SchedulerState, Selection, SelectionRequest, SessionBindingInfo};
 use crate::proxy::sticky_config::StickySessionConfig;
 
 #[derive(Debug, Clone)]
@@ -19,16 +20,19 @@ pub struct ProxyToken {
     pub account_path: PathBuf,  // 账号文件路径，用于更新
     pub project_id: Option<String>,
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
+    pub groups: Vec<String>, // 账号分组标签
+    pub weight: u32,   // 加权轮询权重 (>= 1)
+    pub priority: i32, // 调度优先级，数值越小越优先
+    pub drain: bool,   // 排空中：不再接受新会话绑定
 }
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
-    current_index: Arc<AtomicUsize>,
-    last_used_account: Arc<tokio::sync::Mutex<Option<(String, std::time::Instant)>>>,
     data_dir: PathBuf,
     rate_limit_tracker: Arc<RateLimitTracker>,  // 新增: 限流跟踪器
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
-    session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
+    scheduler: Arc<SchedulerState>, // 调度状态：会话绑定、60s 锁定、轮询游标
+    group_config: Arc<tokio::sync::RwLock<AccountGroupConfig>>, // 账号分组路由配置
 }
 
 impl TokenManager {
@@ -36,12 +40,11 @@ impl TokenManager {
     pub fn new(data_dir: PathBuf) -> Self {
         Self {
             tokens: Arc::new(DashMap::new()),
-            current_index: Arc::new(AtomicUsize::new(0)),
-            last_used_account: Arc::new(tokio::sync::Mutex::new(None)),
             data_dir,
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
-            session_accounts: Arc::new(DashMap::new()),
+            scheduler: Arc::new(SchedulerState::new()),
+            group_config: Arc::new(tokio::sync::RwLock::new(AccountGroupConfig::default())),
         }
     }
     
@@ -53,18 +56,10 @@ impl TokenManager {
             return Err(format!("账号目录不存在: {:?}", accounts_dir));
         }
 
-        // Reload should reflect current on-disk state (accounts can be added/removed/disabled).
-        self.tokens.clear();
-        self.current_index.store(0, Ordering::SeqCst);
-        {
-            let mut last_used = self.last_used_account.lock().await;
-            *last_used = None;
-        }
-        
         let entries = std::fs::read_dir(&accounts_dir)
             .map_err(|e| format!("读取账号目录失败: {}", e))?;
         
-        let mut count = 0;
+        let mut loaded: std::collections::HashMap<String, ProxyToken> = std::collections::HashMap::new();
         
         for entry in entries {
             let entry = entry.map_err(|e| format!("读取目录项失败: {}", e))?;
@@ -77,9 +72,7 @@ impl TokenManager {
             // 尝试加载账号
             match self.load_single_account(&path).await {
                 Ok(Some(token)) => {
-                    let account_id = token.account_id.clone();
-                    self.tokens.insert(account_id, token);
-                    count += 1;
+                    loaded.insert(token.account_id.clone(), token);
                 },
                 Ok(None) => {
                     // 跳过无效账号
@@ -89,16 +82,77 @@ impl TokenManager {
                 }
             }
         }
+
+        // Reload should reflect current on-disk state (accounts can be added/removed/disabled).
+        // 原地同步而非先清空，避免重载期间并发请求看到空账号池
+        self.tokens.retain(|id, _| loaded.contains_key(id));
+        let count = loaded.len();
+        for (account_id, token) in loaded {
+            self.tokens.insert(account_id, token);
+        }
+        self.scheduler.reset_cursor();
         
         Ok(count)
     }
+
+    /// 账号文件目录
+    pub fn accounts_dir(&self) -> PathBuf {
+        self.data_dir.join("accounts")
+    }
+
+    /// 根据单个账号文件的变化增量更新账号池 (新增 / 更新 / 移除)
+    /// 只替换该账号的条目，其他账号、会话绑定与限流状态保持不变
+    pub async fn sync_account_file(&self, path: &Path) {
+        let file_id = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string();
+
+        if !path.exists() {
+            self.remove_account_entry(&file_id, path, "file removed");
+            return;
+        }
+
+        match self.load_single_account(&path.to_path_buf()).await {
+            Ok(Some(token)) => {
+                let email = token.email.clone();
+                let existed = self.tokens.insert(token.account_id.clone(), token).is_some();
+                tracing::info!(
+                    "Account hot reload: {} {}",
+                    if existed { "updated" } else { "added" },
+                    email
+                );
+            }
+            Ok(None) => {
+                self.remove_account_entry(&file_id, path, "disabled");
+            }
+            Err(e) => {
+                // 文件可能正处于写入过程中，保留现有条目，等待下一次变更事件
+                tracing::debug!("Account hot reload skipped {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// 从账号池移除账号，并清理指向该账号的会话绑定
+    fn remove_account_entry(&self, account_id: &str, path: &Path, reason: &str) {
+        let ids: Vec<String> = self
+            .tokens
+            .iter()
+            .filter(|e| e.key() == account_id || e.value().account_path == path)
+            .map(|e| e.key().clone())
+            .collect();
+
+        for id in ids {
+            if let Some((_, token)) = self.tokens.remove(&id) {
+                self.scheduler.session_accounts.retain(|_, binding| binding.account_id != id);
+                tracing::info!("Account hot reload: removed {} ({})", token.email, reason);
+            }
+        }
+    }
     
     /// 加载单个账号
     async fn load_single_account(&self, path: &PathBuf) -> Result<Option<ProxyToken>, String> {
         let content = std::fs::read_to_string(path)
             .map_err(|e| format!("读取文件失败: {}", e))?;
         
-        let account: serde_json::Value = serde_json::from_str(&content)
+        let mut account: serde_json::Value = serde_json::from_str(&content)
             .map_err(|e| format!("解析 JSON 失败: {}", e))?;
 
         if account
@@ -128,6 +182,8 @@ impl TokenManager {
             return Ok(None);
         }
 
+        crate::modules::token_vault::decrypt_account(&mut account)?;
+
         let account_id = account["id"].as_str()
             .ok_or("缺少 id 字段")?
             .to_string();
@@ -163,6 +219,25 @@ impl TokenManager {
             .and_then(|q| q.get("subscription_tier"))
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
+
+        // 账号分组标签 (可选)
+        let groups = account.get("groups")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|g| g.as_str()).map(|g| g.to_string()).collect())
+            .unwrap_or_default();
+
+        // 调度参数 (可选)：权重、优先级与排空标记
+        let weight = account.get("weight")
+            .and_then(|v| v.as_u64())
+            .map(|w| w.clamp(1, u32::MAX as u64) as u32)
+            .unwrap_or(1);
+        let priority = account.get("priority")
+            .and_then(|v| v.as_i64())
+            .map(|p| p.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+            .unwrap_or(0);
+        let drain = account.get("drain")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
         
         Ok(Some(ProxyToken {
             account_id,
@@ -174,6 +249,10 @@ impl TokenManager {
             account_path: path.clone(),
             project_id,
             subscription_tier,
+            groups,
+            weight,
+            priority,
+            drain,
         }))
     }
     
@@ -181,28 +260,57 @@ impl TokenManager {
     /// 参数 `quota_group` 用于区分 "claude" vs "gemini" 组
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
-    pub async fn get_token(&self, quota_group: &str, force_rotate: bool, session_id: Option<&str>) -> Result<(String, String, String), String> {
+    /// 参数 `account_group` 限定只从该分组中选择账号 (None 表示整个账号池)，
+    /// 分组内无可用账号时按配置回退到共享分组
+    pub async fn get_token(&self, quota_group: &str, force_rotate: bool, session_id: Option<&str>, account_group: Option<&str>) -> Result<(String, String, String), String> {
+        let result = self.get_token_in_group(quota_group, force_rotate, session_id, account_group).await;
+
+        let group = match (&result, account_group) {
+            (Err(_), Some(g)) => g,
+            _ => return result,
+        };
+
+        let fallback = self.group_config.read().await.fallback_group.clone();
+        match fallback {
+            Some(fallback) if fallback != group => {
+                tracing::warn!(
+                    "Account group '{}' has no usable accounts ({}), falling back to '{}'",
+                    group,
+                    result.as_ref().err().map(|e| e.as_str()).unwrap_or(""),
+                    fallback
+                );
+                self.get_token_in_group(quota_group, force_rotate, session_id, Some(&fallback)).await
+            }
+            _ => result,
+        }
+    }
+
+    async fn get_token_in_group(&self, quota_group: &str, force_rotate: bool, session_id: Option<&str>, account_group: Option<&str>) -> Result<(String, String, String), String> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
-        let total = tokens_snapshot.len();
-        if total == 0 {
+        if tokens_snapshot.is_empty() {
             return Err("Token pool is empty".to_string());
         }
 
+        // 请求重放可强制指定账号 (优先于分组路由)，否则按分组过滤候选账号
+        if let Some(email) = crate::proxy::replay::forced_account() {
+            tokens_snapshot.retain(|t| t.email == email);
+            if tokens_snapshot.is_empty() {
+                return Err(format!("Account '{}' is not in the token pool", email));
+            }
+        } else if let Some(group) = account_group {
+            tokens_snapshot.retain(|t| account_groups::account_in_group(&t.groups, group));
+            if tokens_snapshot.is_empty() {
+                return Err(format!("No accounts in group '{}'", group));
+            }
+        }
+        let total = tokens_snapshot.len();
+
         // ===== 【优化】根据订阅等级排序 (优先级: ULTRA > PRO > FREE) =====
-        // 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
-        tokens_snapshot.sort_by(|a, b| {
-            let tier_priority = |tier: &Option<String>| match tier.as_deref() {
-                Some("ULTRA") => 0,
-                Some("PRO") => 1,
-                Some("FREE") => 2,
-                _ => 3,
-            };
-            tier_priority(&a.subscription_tier).cmp(&tier_priority(&b.subscription_tier))
-        });
+        scheduling::sort_by_tier(&mut tokens_snapshot);
 
-        // 0. 读取当前调度配置
-        let scheduling = self.sticky_config.read().await.clone();
-        use crate::proxy::sticky_config::SchedulingMode;
+        // 0. 读取当前调度配置并取得对应调度策略
+        let sticky = self.sticky_config.read().await.clone();
+        let policy = scheduling::policy_for_mode(sticky.mode);
 
         let mut attempted: HashSet<String> = HashSet::new();
         let mut last_error: Option<String> = None;
@@ -210,117 +318,32 @@ impl TokenManager {
         for attempt in 0..total {
             let rotate = force_rotate || attempt > 0;
 
-            // ===== 【核心】粘性会话与智能调度逻辑 =====
-            let mut target_token: Option<ProxyToken> = None;
-            
-            // 模式 A: 粘性会话处理 (CacheFirst 或 Balance 且有 session_id)
-            if !rotate && session_id.is_some() && scheduling.mode != SchedulingMode::PerformanceFirst {
-                let sid = session_id.unwrap();
-                
-                // 1. 检查会话是否已绑定账号
-                if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
-                    // 2. 检查绑定的账号是否限流 (使用精准的剩余时间接口)
-                    let reset_sec = self.rate_limit_tracker.get_remaining_wait(&bound_id);
-                    if reset_sec > 0 {
-                        if scheduling.mode == SchedulingMode::CacheFirst && reset_sec <= scheduling.max_wait_seconds {
-                            // 缓存优先模式：限流时间短，执行精准精准避让等待
-                            tracing::warn!("Cache-first: Session {} bound to {} is limited. Executing precise wait for {}s to preserve cache...", sid, bound_id, reset_sec);
-                            tokio::time::sleep(std::time::Duration::from_secs(reset_sec)).await;
-                            
-                            // 等待后若账号可用，优先复用
-                            if let Some(found) = tokens_snapshot.iter().find(|t| t.account_id == bound_id) {
-                                tracing::debug!("Sticky Session: Successfully recovered and reusing bound account {} for session {}", found.email, sid);
-                                target_token = Some(found.clone());
-                            }
-                        } else {
-                            // 平衡模式或等待时间过长：断开绑定，准备换号
-                            tracing::warn!("Avoidance/WaitTimeout: Session {} switching from {} (remaining wait: {}s > limit: {}s).", sid, bound_id, reset_sec, scheduling.max_wait_seconds);
-                            self.session_accounts.remove(sid);
-                        }
-                    } else if !attempted.contains(&bound_id) {
-                        // 3. 账号可用且未被标记为尝试失败，优先复用
-                        if let Some(found) = tokens_snapshot.iter().find(|t| t.account_id == bound_id) {
-                            tracing::debug!("Sticky Session: Successfully reusing bound account {} for session {}", found.email, sid);
-                            target_token = Some(found.clone());
-                        }
-                    }
-                }
-            }
-
-            // 模式 B: 原子化 60s 全局锁定 (针对无 session_id 情况的默认保护)
-            if target_token.is_none() && !rotate && quota_group != "image_gen" {
-                let mut last_used = self.last_used_account.lock().await;
-                
-                // 尝试复用全局锁定账号
-                if let Some((account_id, last_time)) = &*last_used {
-                    if last_time.elapsed().as_secs() < 60 && !attempted.contains(account_id) {
-                        if let Some(found) = tokens_snapshot.iter().find(|t| &t.account_id == account_id) {
-                            tracing::debug!("60s Window: Force reusing last account: {}", found.email);
-                            target_token = Some(found.clone());
-                        }
-                    }
-                }
-                
-                // 若无锁定，则轮询选择新账号
-                if target_token.is_none() {
-                    let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                    for offset in 0..total {
-                        let idx = (start_idx + offset) % total;
-                        let candidate = &tokens_snapshot[idx];
-                        if attempted.contains(&candidate.account_id) {
-                            continue;
-                        }
-
-                        // 【新增】主动避开限流或 5xx 锁定的账号 (来自 PR #28 的高可用思路)
-                        if self.is_rate_limited(&candidate.account_id) {
-                            continue;
-                        }
-
-                        target_token = Some(candidate.clone());
-                        *last_used = Some((candidate.account_id.clone(), std::time::Instant::now()));
-                        
-                        // 如果是会话首次分配且需要粘性，在此建立绑定
-                        if let Some(sid) = session_id {
-                            if scheduling.mode != SchedulingMode::PerformanceFirst {
-                                self.session_accounts.insert(sid.to_string(), candidate.account_id.clone());
-                                tracing::debug!("Sticky Session: Bound new account {} to session {}", candidate.email, sid);
-                            }
-                        }
-                        break;
-                    }
-                }
-            } else if target_token.is_none() {
-                // 模式 C: 纯轮询模式 (Round-robin) 或强制轮换
-                let start_idx = self.current_index.fetch_add(1, Ordering::SeqCst) % total;
-                for offset in 0..total {
-                    let idx = (start_idx + offset) % total;
-                    let candidate = &tokens_snapshot[idx];
-                    if attempted.contains(&candidate.account_id) {
-                        continue;
-                    }
-
-                    // 【新增】主动避开限流或 5xx 锁定的账号
-                    if self.is_rate_limited(&candidate.account_id) {
-                        continue;
-                    }
-
-                    target_token = Some(candidate.clone());
-                    
-                    if rotate {
-                        tracing::debug!("Force Rotation: Switched to account: {}", candidate.email);
-                    }
-                    break;
+            // ===== 【核心】粘性会话与智能调度逻辑 (由 SchedulingPolicy 实现) =====
+            let request = SelectionRequest {
+                quota_group,
+                session_id,
+                rotate,
+                attempted: &attempted,
+                max_wait_seconds: sticky.max_wait_seconds,
+                now_ms: chrono::Utc::now().timestamp_millis(),
+            };
+            let selection = policy.select(&request, &tokens_snapshot, &self.scheduler, self.rate_limit_tracker.as_ref());
+
+            let mut token = match selection {
+                Selection::Account(t) => t,
+                Selection::WaitThenUse { token, wait_seconds } => {
+                    // 缓存优先模式：执行精准避让等待后复用绑定账号
+                    tokio::time::sleep(std::time::Duration::from_secs(wait_seconds)).await;
+                    tracing::debug!("Sticky Session: Successfully recovered and reusing bound account {}", token.email);
+                    token
                 }
-            }
-            
-            let mut token = match target_token {
-                Some(t) => t,
-                None => {
+                Selection::Exhausted => {
                     // 如果所有账号都被尝试过或都处于限流中，计算最短等待时间
                     let min_wait = tokens_snapshot.iter()
                         .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id))
                         .min()
                         .unwrap_or(60);
+                    crate::proxy::events::all_accounts_limited(quota_group, min_wait);
                     
                     return Err(format!("All accounts are currently limited or unhealthy. Please wait {}s.", min_wait));
                 }
@@ -372,10 +395,7 @@ impl TokenManager {
 
                         // 如果当前账号被锁定复用，刷新失败后必须解除锁定，避免下一次仍选中同一账号
                         if quota_group != "image_gen" {
-                            let mut last_used = self.last_used_account.lock().await;
-                            if matches!(&*last_used, Some((id, _)) if id == &token.account_id) {
-                                *last_used = None;
-                            }
+                            self.scheduler.release(&token.account_id);
                         }
                         continue;
                     }
@@ -401,10 +421,7 @@ impl TokenManager {
                         attempted.insert(token.account_id.clone());
 
                         if quota_group != "image_gen" {
-                            let mut last_used = self.last_used_account.lock().await;
-                            if matches!(&*last_used, Some((id, _)) if id == &token.account_id) {
-                                *last_used = None;
-                            }
+                            self.scheduler.release(&token.account_id);
                         }
                         continue;
                     }
@@ -440,6 +457,8 @@ impl TokenManager {
             .map_err(|e| format!("写入文件失败: {}", e))?;
 
         tracing::warn!("Account disabled: {} ({:?})", account_id, path);
+        let account = content.get("email").and_then(|v| v.as_str()).unwrap_or(account_id);
+        crate::proxy::events::account_disabled(account, reason);
         Ok(())
     }
 
@@ -454,7 +473,9 @@ impl TokenManager {
             &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
         ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
         
+        crate::modules::token_vault::decrypt_account(&mut content)?;
         content["token"]["project_id"] = serde_json::Value::String(project_id.to_string());
+        crate::modules::token_vault::encrypt_account(&mut content)?;
         
         std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
@@ -476,9 +497,11 @@ impl TokenManager {
         
         let now = chrono::Utc::now().timestamp();
         
+        crate::modules::token_vault::decrypt_account(&mut content)?;
         content["token"]["access_token"] = serde_json::Value::String(token_response.access_token.clone());
         content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
         content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
+        crate::modules::token_vault::encrypt_account(&mut content)?;
         
         std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
@@ -546,15 +569,61 @@ impl TokenManager {
         tracing::debug!("Scheduling configuration updated: {:?}", *config);
     }
 
+    // ===== 分组路由相关方法 =====
+
+    /// 获取当前分组路由配置
+    pub async fn get_group_config(&self) -> AccountGroupConfig {
+        self.group_config.read().await.clone()
+    }
+
+    /// 更新分组路由配置
+    pub async fn update_group_config(&self, new_config: AccountGroupConfig) {
+        let mut config = self.group_config.write().await;
+        *config = new_config;
+        tracing::debug!("Account group routing updated: {:?}", *config);
+    }
+
+    /// 根据客户端 Key / 模型 / 请求类型解析目标分组
+    pub async fn resolve_account_group(&self, key_group: Option<&str>, model: &str, request_type: &str) -> Option<String> {
+        let group = self.group_config.read().await.resolve_group(key_group, model, request_type);
+        if let Some(g) = &group {
+            tracing::debug!("Account group routing: model={} type={} -> group '{}'", model, request_type, g);
+        }
+        group
+    }
+
+    /// 当前活跃的会话绑定 (按最近命中时间倒序)
+    pub fn session_bindings(&self) -> Vec<SessionBindingInfo> {
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let mut bindings: Vec<SessionBindingInfo> = self
+            .scheduler
+            .session_accounts
+            .iter()
+            .map(|entry| {
+                let binding = entry.value();
+                SessionBindingInfo {
+                    session_id: entry.key().clone(),
+                    account_id: binding.account_id.clone(),
+                    email: self.tokens.get(&binding.account_id).map(|t| t.email.clone()),
+                    age_seconds: (now_ms - binding.bound_at_ms).max(0) / 1000,
+                    idle_seconds: (now_ms - binding.last_hit_ms).max(0) / 1000,
+                    hits: binding.hits,
+                }
+            })
+            .collect();
+        bindings.sort_by_key(|b| b.idle_seconds);
+        bindings
+    }
+
     /// 清除特定会话的粘性映射
     #[allow(dead_code)]
     pub fn clear_session_binding(&self, session_id: &str) {
-        self.session_accounts.remove(session_id);
+        self.scheduler.session_accounts.remove(session_id);
     }
 
     /// 清除所有会话的粘性映射
     pub fn clear_all_sessions(&self) {
-        self.session_accounts.clear();
+        self.scheduler.session_accounts.clear();
     }
 }
 
@@ -0,0 +1,198 @@
+// Unix domain socket 监听
+// - 与 TCP 同时监听，或独占监听 (不开放任何 TCP 端口)
+// - 通过 socket 文件权限控制访问，IP 访问控制与按 IP 限流不适用于此类连接
+// - 启动时清理残留的 socket 文件，停止时删除
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// 默认 socket 文件名 (位于数据目录)
+pub const DEFAULT_SOCKET_FILE: &str = "proxy.sock";
+
+/// Unix domain socket 配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UnixSocketConfig {
+    pub enabled: bool,
+    /// socket 文件路径，为空时使用数据目录下的 proxy.sock
+    pub path: String,
+    /// socket 文件权限 (八进制，如 "600" / "660")
+    pub mode: String,
+    /// 仅监听 Unix socket，不开放 TCP 端口
+    pub exclusive: bool,
+}
+
+impl Default for UnixSocketConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: String::new(),
+            mode: "600".to_string(),
+            exclusive: false,
+        }
+    }
+}
+
+impl UnixSocketConfig {
+    /// 解析权限位
+    pub fn parse_mode(&self) -> Result<u32, String> {
+        let mode = self.mode.trim();
+        let mode = mode.strip_prefix("0o").unwrap_or(mode);
+        match u32::from_str_radix(mode, 8) {
+            Ok(bits) if bits <= 0o777 => Ok(bits),
+            _ => Err(format!("无效的 socket 文件权限: {} (应为八进制，如 600)", self.mode)),
+        }
+    }
+
+    pub fn socket_path(&self) -> Result<PathBuf, String> {
+        match self.path.trim() {
+            "" => Ok(crate::modules::account::get_data_dir()?.join(DEFAULT_SOCKET_FILE)),
+            path => Ok(PathBuf::from(path)),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !cfg!(unix) {
+            return Err("当前平台不支持 Unix domain socket 监听".to_string());
+        }
+        self.parse_mode()?;
+        Ok(())
+    }
+}
+
+/// 已绑定的 Unix socket，drop 时删除 socket 文件
+pub struct UnixSocketListener {
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    #[cfg(not(unix))]
+    never: std::convert::Infallible,
+    path: PathBuf,
+    /// 绑定时 socket 文件的 inode，避免删除之后由其他实例创建的同名文件
+    #[cfg(unix)]
+    ino: u64,
+}
+
+#[cfg(unix)]
+pub type UnixStream = tokio::net::UnixStream;
+#[cfg(not(unix))]
+pub type UnixStream = tokio::io::DuplexStream;
+
+impl UnixSocketListener {
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    #[cfg(unix)]
+    pub async fn accept(&self) -> std::io::Result<UnixStream> {
+        self.listener.accept().await.map(|(stream, _)| stream)
+    }
+
+    #[cfg(not(unix))]
+    pub async fn accept(&self) -> std::io::Result<UnixStream> {
+        match self.never {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        use std::os::unix::fs::MetadataExt;
+        if std::fs::symlink_metadata(&self.path).is_ok_and(|m| m.ino() == self.ino) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// 绑定 socket 并设置文件权限
+#[cfg(unix)]
+pub async fn bind(config: &UnixSocketConfig) -> Result<UnixSocketListener, String> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+
+    let mode = config.parse_mode()?;
+    let path = config.socket_path()?;
+
+    if let Ok(meta) = std::fs::symlink_metadata(&path) {
+        if !meta.file_type().is_socket() {
+            return Err(format!("{} 已存在且不是 socket 文件", path.display()));
+        }
+        // 仍有进程在监听时不抢占，否则视为上次异常退出的残留文件
+        if tokio::net::UnixStream::connect(&path).await.is_ok() {
+            return Err(format!("{} 已被其他进程监听", path.display()));
+        }
+        std::fs::remove_file(&path).map_err(|e| format!("清理残留 socket 文件失败: {}", e))?;
+    }
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建 socket 目录失败: {}", e))?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Unix socket {} 绑定失败: {}", path.display(), e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("设置 socket 文件权限失败: {}", e))?;
+    let ino = std::fs::symlink_metadata(&path)
+        .map_err(|e| e.to_string())?
+        .ino();
+
+    Ok(UnixSocketListener { listener, path, ino })
+}
+
+#[cfg(not(unix))]
+pub async fn bind(_config: &UnixSocketConfig) -> Result<UnixSocketListener, String> {
+    Err("当前平台不支持 Unix domain socket 监听".to_string())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn config(path: &std::path::Path, mode: &str) -> UnixSocketConfig {
+        UnixSocketConfig {
+            enabled: true,
+            path: path.to_string_lossy().to_string(),
+            mode: mode.to_string(),
+            exclusive: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let mut config = UnixSocketConfig::default();
+        assert_eq!(config.parse_mode().unwrap(), 0o600);
+        config.mode = "0o660".to_string();
+        assert_eq!(config.parse_mode().unwrap(), 0o660);
+        config.mode = "1777".to_string();
+        assert!(config.parse_mode().is_err());
+        config.mode = "rw-".to_string();
+        assert!(config.parse_mode().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_sets_mode_and_cleans_up() {
+        let dir = std::env::temp_dir().join(format!("ag-sock-{}", uuid::Uuid::new_v4().simple()));
+        let path = dir.join("proxy.sock");
+
+        let listener = bind(&config(&path, "660")).await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        // 正在监听时拒绝重复绑定
+        assert!(bind(&config(&path, "600")).await.err().unwrap().contains("已被其他进程监听"));
+
+        drop(listener);
+        assert!(!path.exists());
+
+        // 残留的 socket 文件 (无进程监听) 会被替换
+        let stale = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        drop(stale);
+        assert!(path.exists());
+        let listener = bind(&config(&path, "600")).await.unwrap();
+        drop(listener);
+
+        // 非 socket 文件不覆盖
+        std::fs::write(&path, b"data").unwrap();
+        assert!(bind(&config(&path, "600")).await.err().unwrap().contains("不是 socket"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -19,15 +19,20 @@ pub struct UpstreamClient {
 }
 
 impl UpstreamClient {
-    pub fn new(proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>) -> Self {
+    /// 首字节 / 流式空闲 / 总时长超时由调用方按请求控制 (见 upstream::timeouts)
+    pub fn new(
+        proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>,
+        connect_timeout: Option<Duration>,
+    ) -> Self {
         let mut builder = Client::builder()
             // Connection settings (优化连接复用，减少建立开销)
-            .connect_timeout(Duration::from_secs(20))
             .pool_max_idle_per_host(16)                  // 每主机最多 16 个空闲连接
             .pool_idle_timeout(Duration::from_secs(90))  // 空闲连接保持 90 秒
             .tcp_keepalive(Duration::from_secs(60))      // TCP 保活探测 60 秒
-            .timeout(Duration::from_secs(600))
             .user_agent("antigravity/1.11.9 windows/amd64");
+        if let Some(timeout) = connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
 
         if let Some(config) = proxy_config {
             if config.enabled && !config.url.is_empty() {
@@ -135,6 +140,11 @@ impl UpstreamClient {
                             base_url,
                             method
                         );
+                        crate::proxy::events::upstream_failover(
+                            base_url,
+                            V1_INTERNAL_BASE_URL_FALLBACKS[idx + 1],
+                            &status.to_string(),
+                        );
                         last_err = Some(format!("Upstream {} returned {}", base_url, status));
                         continue;
                     }
@@ -151,6 +161,11 @@ impl UpstreamClient {
                     if !has_next {
                         break;
                     }
+                    crate::proxy::events::upstream_failover(
+                        base_url,
+                        V1_INTERNAL_BASE_URL_FALLBACKS[idx + 1],
+                        &e.to_string(),
+                    );
                     continue;
                 }
             }
@@ -208,6 +223,7 @@ impl UpstreamClient {
                 .post(&url)
                 .headers(headers.clone())
                 .json(&serde_json::json!({}))
+                .timeout(Duration::from_secs(30))
                 .send()
                 .await;
 
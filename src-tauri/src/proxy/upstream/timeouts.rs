@@ -0,0 +1,396 @@
+// 上游请求超时
+// - 连接超时: 作用于上游 HTTP 客户端 (全局)
+// - 首字节超时: 流式请求从发出到收到首个 SSE 分块
+// - 流式空闲超时: 相邻 SSE 分块之间的最大间隔
+// - 总时长: 单次尝试从发出请求到响应结束 (ProxyConfig.request_timeout)
+// 尚未向客户端输出任何内容时超时，由调用方轮换账号重试
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// 受超时保护的上游字节流
+pub type UpstreamStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+/// 上游超时配置 (单位秒，0 表示不限制)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub connect_secs: u64,
+    pub first_byte_secs: u64,
+    pub stream_idle_secs: u64,
+    /// 按模型覆盖 (key 为模型名，支持末尾 * 通配，如 "gemini-3-pro*")
+    pub per_model: HashMap<String, ModelTimeouts>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: 20,
+            first_byte_secs: 90,
+            stream_idle_secs: 60,
+            per_model: HashMap::new(),
+        }
+    }
+}
+
+/// 单个模型的超时覆盖 (未设置的项使用全局值；连接超时仅支持全局配置)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ModelTimeouts {
+    pub first_byte_secs: Option<u64>,
+    pub stream_idle_secs: Option<u64>,
+    pub total_secs: Option<u64>,
+}
+
+/// 解析后的单次请求超时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub first_byte: Option<Duration>,
+    pub stream_idle: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+fn secs(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
+
+impl TimeoutConfig {
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        secs(self.connect_secs)
+    }
+
+    /// 查找模型覆盖: 依次尝试各候选模型名 (如映射后模型、原始模型)，精确匹配优先，其次最长的通配前缀
+    fn model_override(&self, models: &[&str]) -> Option<&ModelTimeouts> {
+        models.iter().find_map(|model| {
+            self.per_model.get(*model).or_else(|| {
+                self.per_model
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        let prefix = key.strip_suffix('*')?;
+                        model.starts_with(prefix).then_some((prefix.len(), value))
+                    })
+                    .max_by_key(|(len, _)| *len)
+                    .map(|(_, value)| value)
+            })
+        })
+    }
+
+    pub fn resolve(&self, request_timeout: u64, models: &[&str]) -> Timeouts {
+        let model = self.model_override(models).cloned().unwrap_or_default();
+        Timeouts {
+            first_byte: secs(model.first_byte_secs.unwrap_or(self.first_byte_secs)),
+            stream_idle: secs(model.stream_idle_secs.unwrap_or(self.stream_idle_secs)),
+            total: secs(model.total_secs.unwrap_or(request_timeout)),
+        }
+    }
+}
+
+/// 等待上游时的失败原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpstreamWaitError {
+    /// 超时 (可轮换账号重试)
+    Timeout(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for UpstreamWaitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(msg) | Self::Failed(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Timeouts {
+    /// 开始一次尝试 (每次轮换账号重新计时)
+    pub fn start(self) -> AttemptTimer {
+        AttemptTimer {
+            timeouts: self,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// 单次上游尝试的计时器
+#[derive(Debug, Clone, Copy)]
+pub struct AttemptTimer {
+    timeouts: Timeouts,
+    started: Instant,
+}
+
+impl AttemptTimer {
+    fn total_deadline(&self) -> Option<Instant> {
+        self.timeouts.total.map(|t| self.started + t)
+    }
+
+    /// 首个分块的截止时间 (首字节与总时长取先到者)
+    fn first_byte_deadline(&self) -> Option<(Instant, String)> {
+        let first_byte = self.timeouts.first_byte.map(|t| {
+            (self.started + t, format!("Upstream timeout: no data within {}s (first byte)", t.as_secs()))
+        });
+        let total = self.timeouts.total.map(|t| {
+            (self.started + t, format!("Upstream timeout: request exceeded {}s (total)", t.as_secs()))
+        });
+        match (first_byte, total) {
+            (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn total_message(&self) -> String {
+        format!(
+            "Upstream timeout: request exceeded {}s (total)",
+            self.timeouts.total.unwrap_or_default().as_secs()
+        )
+    }
+
+    /// 等待响应头: 流式请求受首字节与总时长限制，非流式请求仅受总时长限制
+    pub async fn response<T>(
+        &self,
+        stream: bool,
+        fut: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, String> {
+        let deadline = if stream {
+            self.first_byte_deadline()
+        } else {
+            self.total_deadline().map(|d| (d, self.total_message()))
+        };
+        match deadline {
+            Some((deadline, message)) => tokio::time::timeout_at(deadline, fut)
+                .await
+                .unwrap_or(Err(message)),
+            None => fut.await,
+        }
+    }
+
+    /// 读取非流式响应体 (受总时长限制)
+    pub async fn body(&self, response: reqwest::Response) -> Result<Bytes, UpstreamWaitError> {
+        let body = async {
+            response
+                .bytes()
+                .await
+                .map_err(|e| UpstreamWaitError::Failed(format!("Failed to read body: {}", e)))
+        };
+        match self.total_deadline() {
+            Some(deadline) => tokio::time::timeout_at(deadline, body)
+                .await
+                .unwrap_or_else(|_| Err(UpstreamWaitError::Timeout(self.total_message()))),
+            None => body.await,
+        }
+    }
+
+    /// 等待流式响应的首个分块，之后返回受空闲间隔与总时长限制的流。
+    /// 首个分块到达前失败或超时都尚未向客户端输出内容，调用方可轮换账号重试。
+    /// commit_after: 超过该时间仍无首个分块时直接返回 (以便开始发送 SSE 保活)，
+    /// 此后的首字节超时以流内错误结束，不再返回给调用方重试
+    pub async fn stream(
+        self,
+        response: reqwest::Response,
+        commit_after: Option<Duration>,
+    ) -> Result<UpstreamStream, String> {
+        self.stream_from(
+            Box::pin(response.bytes_stream().map(|r| r.map_err(|e| e.to_string()))),
+            commit_after,
+        )
+        .await
+    }
+
+    async fn stream_from(
+        self,
+        mut upstream: Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>,
+        commit_after: Option<Duration>,
+    ) -> Result<UpstreamStream, String> {
+        let first_byte = self.first_byte_deadline();
+        let commit_at = commit_after.map(|d| Instant::now() + d);
+        let wait_until = match (first_byte.as_ref().map(|(d, _)| *d), commit_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        // None: 已到 commit_after 但首个分块仍未到达
+        let first = match wait_until {
+            Some(until) => match tokio::time::timeout_at(until, upstream.next()).await {
+                Ok(next) => Some(next),
+                Err(_) => match &first_byte {
+                    Some((deadline, message)) if *deadline <= until => return Err(message.clone()),
+                    _ => None,
+                },
+            },
+            None => Some(upstream.next().await),
+        };
+        let first = match first {
+            Some(Some(Ok(bytes))) => Some(bytes),
+            Some(Some(Err(e))) => return Err(e),
+            Some(None) => return Ok(Box::pin(futures::stream::empty())),
+            None => None,
+        };
+
+        let timer = self;
+        Ok(Box::pin(async_stream::stream! {
+            match first {
+                Some(bytes) => yield Ok(bytes),
+                None => {
+                    let next = match &first_byte {
+                        Some((deadline, message)) => match tokio::time::timeout_at(*deadline, upstream.next()).await {
+                            Ok(next) => next,
+                            Err(_) => {
+                                tracing::warn!("{}", message);
+                                yield Err(message.clone());
+                                return;
+                            }
+                        },
+                        None => upstream.next().await,
+                    };
+                    match next {
+                        Some(item) => yield item,
+                        None => return,
+                    }
+                }
+            }
+            loop {
+                let mut wait = timer.timeouts.stream_idle.map(|idle| {
+                    (Instant::now() + idle, format!("Upstream timeout: no data for {}s (stream idle)", idle.as_secs()))
+                });
+                if let Some(total) = timer.total_deadline() {
+                    if wait.as_ref().is_none_or(|(idle, _)| total < *idle) {
+                        wait = Some((total, timer.total_message()));
+                    }
+                }
+                let next = match wait {
+                    Some((deadline, message)) => match tokio::time::timeout_at(deadline, upstream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            tracing::warn!("{}", message);
+                            yield Err(message);
+                            break;
+                        }
+                    },
+                    None => upstream.next().await,
+                };
+                match next {
+                    Some(item) => yield item,
+                    None => break,
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(delays_ms: Vec<u64>) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
+        Box::pin(async_stream::stream! {
+            for (i, delay) in delays_ms.into_iter().enumerate() {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                yield Ok(Bytes::from(i.to_string()));
+            }
+        })
+    }
+
+    fn timer(first_byte: u64, idle: u64, total: u64) -> AttemptTimer {
+        let ms = |v: u64| (v > 0).then(|| Duration::from_millis(v));
+        Timeouts {
+            first_byte: ms(first_byte),
+            stream_idle: ms(idle),
+            total: ms(total),
+        }
+        .start()
+    }
+
+    async fn collect(stream: UpstreamStream) -> Vec<Result<Bytes, String>> {
+        stream.collect().await
+    }
+
+    #[test]
+    fn test_resolve_per_model() {
+        let mut config = TimeoutConfig::default();
+        config.per_model.insert(
+            "gemini-3-pro*".to_string(),
+            ModelTimeouts { first_byte_secs: Some(300), ..Default::default() },
+        );
+        config.per_model.insert(
+            "gemini-3-pro-high".to_string(),
+            ModelTimeouts { total_secs: Some(0), ..Default::default() },
+        );
+        config.per_model.insert(
+            "gemini*".to_string(),
+            ModelTimeouts { stream_idle_secs: Some(5), ..Default::default() },
+        );
+
+        let t = config.resolve(120, &["gemini-3-pro-low"]);
+        assert_eq!(t.first_byte, Some(Duration::from_secs(300)));
+        assert_eq!(t.stream_idle, Some(Duration::from_secs(60)));
+        assert_eq!(t.total, Some(Duration::from_secs(120)));
+
+        // 精确匹配优先，0 表示不限制
+        let t = config.resolve(120, &["gemini-3-pro-high"]);
+        assert_eq!(t.first_byte, Some(Duration::from_secs(90)));
+        assert_eq!(t.total, None);
+
+        // 映射后模型无覆盖时使用原始模型名匹配
+        let t = config.resolve(120, &["claude-sonnet-4-5", "gemini-2.5-flash"]);
+        assert_eq!(t.stream_idle, Some(Duration::from_secs(5)));
+        assert_eq!(config.resolve(120, &["claude-opus-4"]).first_byte, Some(Duration::from_secs(90)));
+    }
+
+    #[tokio::test]
+    async fn test_first_byte_timeout_is_retryable() {
+        let err = timer(100, 0, 0).stream_from(chunks(vec![300]), None).await.err().unwrap();
+        assert!(err.contains("first byte"));
+        // 总时长先于首字节到期
+        let err = timer(1000, 0, 100).stream_from(chunks(vec![300]), None).await.err().unwrap();
+        assert!(err.contains("total"));
+    }
+
+    #[tokio::test]
+    async fn test_commit_after_returns_before_first_byte() {
+        let commit = Some(Duration::from_millis(100));
+        // 超过 commit_after 先返回流，首个分块之后照常输出
+        let items = collect(timer(1000, 0, 0).stream_from(chunks(vec![300, 20]), commit).await.unwrap()).await;
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|i| i.is_ok()));
+
+        // 返回后首字节超时以流内错误结束
+        let items = collect(timer(300, 0, 0).stream_from(chunks(vec![600]), commit).await.unwrap()).await;
+        assert_eq!(items.len(), 1);
+        assert!(items[0].as_ref().unwrap_err().contains("first byte"));
+
+        // 首字节超时早于 commit_after 时仍可重试
+        assert!(timer(50, 0, 0).stream_from(chunks(vec![300]), commit).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_and_total_timeouts() {
+        let items = collect(timer(200, 200, 0).stream_from(chunks(vec![20, 20, 20]), None).await.unwrap()).await;
+        assert_eq!(items.len(), 3);
+        assert!(items.iter().all(|i| i.is_ok()));
+
+        // 第三个分块间隔超过空闲超时: 已输出的内容保留，之后以错误结束
+        let items = collect(timer(200, 200, 0).stream_from(chunks(vec![20, 20, 500, 20]), None).await.unwrap()).await;
+        assert_eq!(items.len(), 3);
+        assert!(items[2].as_ref().unwrap_err().contains("stream idle"));
+
+        let items = collect(timer(1000, 1000, 380).stream_from(chunks(vec![150, 150, 150, 150]), None).await.unwrap()).await;
+        assert_eq!(items.len(), 3);
+        assert!(items[2].as_ref().unwrap_err().contains("total"));
+    }
+
+    #[tokio::test]
+    async fn test_response_deadline() {
+        let t = timer(100, 0, 1000);
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            Ok::<_, String>(())
+        };
+        // 流式请求等待响应头受首字节限制，非流式只受总时长限制
+        assert!(t.response(true, slow()).await.is_err());
+        assert!(t.response(false, slow()).await.is_ok());
+    }
+}
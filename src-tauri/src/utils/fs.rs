@@ -0,0 +1,21 @@
+use std::io::Write;
+use std::path::Path;
+
+/// 写入仅当前用户可读写的文件 (私钥 / 密钥文件)
+/// 新建时直接以 0600 创建；覆盖已有文件时先收紧权限再写入，避免内容在任何时刻对其他用户可读
+pub fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let mut file = options.open(path)?;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents)
+    }
+    #[cfg(not(unix))]
+    {
+        options.open(path)?.write_all(contents)
+    }
+}
@@ -12,6 +12,8 @@ import { useConfigStore } from './stores/useConfigStore';
 import { useAccountStore } from './stores/useAccountStore';
 import { useTranslation } from 'react-i18next';
 import { listen } from '@tauri-apps/api/event';
+import { showToast } from './components/common/ToastContainer';
+import type { ProxyEvent } from './types/config';
 
 const router = createBrowserRouter([
   {
@@ -80,6 +82,14 @@ function App() {
       })
     );
 
+    // 监听反代事件总线 (账号禁用 / 全部限流 / 配额不足 / 上游切换)
+    unlistenPromises.push(
+      listen<ProxyEvent>('proxy://event', (event) => {
+        const { severity, message } = event.payload;
+        showToast(message, severity === 'info' ? 'info' : severity, 6000);
+      })
+    );
+
     // Cleanup
     return () => {
       Promise.all(unlistenPromises).then(unlisteners => {
@@ -0,0 +1,302 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+import { open, save } from '@tauri-apps/plugin-dialog';
+import { Lock, Unlock, Download, Upload, FolderOpen } from 'lucide-react';
+import ModalDialog from '../common/ModalDialog';
+import { showToast } from '../common/ToastContainer';
+import { useAccountStore } from '../../stores/useAccountStore';
+import {
+    getTokenVaultStatus,
+    enableTokenEncryption,
+    disableTokenEncryption,
+    unlockTokenVault,
+    exportAccountsBundle,
+    importAccountsBundle,
+} from '../../services/accountService';
+import { TokenVaultStatus, VaultKeySource } from '../../types/account';
+
+const inputClass =
+    'w-full px-4 py-3 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-transparent text-gray-900 dark:text-base-content bg-white dark:bg-base-100';
+const buttonClass =
+    'px-4 py-2 border border-gray-200 dark:border-base-300 text-gray-700 dark:text-gray-300 rounded-lg hover:bg-gray-100 dark:hover:bg-base-300 transition-colors disabled:opacity-50 flex items-center gap-2';
+const primaryButtonClass =
+    'px-4 py-2 bg-blue-500 text-white rounded-lg hover:bg-blue-600 transition-colors disabled:opacity-50 flex items-center gap-2';
+
+// 设置页 - 令牌加密 (启用 / 停用 / 解锁) 与加密导出包
+function TokenVaultSettings() {
+    const { t } = useTranslation();
+    const { fetchAccounts, fetchCurrentAccount } = useAccountStore();
+    const [status, setStatus] = useState<TokenVaultStatus | null>(null);
+    const [busy, setBusy] = useState(false);
+
+    // 启用 / 解锁
+    const [source, setSource] = useState<VaultKeySource>('passphrase');
+    const [passphrase, setPassphrase] = useState('');
+    const [confirmPassphrase, setConfirmPassphrase] = useState('');
+    const [keyFile, setKeyFile] = useState('');
+    const [isDisableOpen, setIsDisableOpen] = useState(false);
+
+    // 加密导出包
+    const [bundlePassphrase, setBundlePassphrase] = useState('');
+
+    useEffect(() => {
+        getTokenVaultStatus()
+            .then(setStatus)
+            .catch((e) => showToast(`${t('common.error')}: ${e}`, 'error'));
+    }, []);
+
+    const run = async (action: () => Promise<void>) => {
+        setBusy(true);
+        try {
+            await action();
+        } catch (e) {
+            showToast(`${t('common.error')}: ${e}`, 'error');
+        } finally {
+            setBusy(false);
+        }
+    };
+
+    const refreshAccounts = () => {
+        fetchAccounts();
+        fetchCurrentAccount();
+    };
+
+    const handleSelectKeyFile = async () => {
+        const selected = await open({ multiple: false, directory: false });
+        if (selected && typeof selected === 'string') {
+            setKeyFile(selected);
+        }
+    };
+
+    const handleEnable = () => {
+        if (source === 'passphrase') {
+            if (!passphrase) {
+                showToast(t('settings.vault.passphrase_required'), 'error');
+                return;
+            }
+            if (passphrase !== confirmPassphrase) {
+                showToast(t('settings.vault.passphrase_mismatch'), 'error');
+                return;
+            }
+        }
+        run(async () => {
+            const result = await enableTokenEncryption(
+                source,
+                source === 'passphrase' ? passphrase : undefined,
+                source === 'key_file' && keyFile ? keyFile : undefined
+            );
+            setStatus(result);
+            setPassphrase('');
+            setConfirmPassphrase('');
+            showToast(t('settings.vault.enabled_toast'), 'success');
+        });
+    };
+
+    const handleDisable = () => {
+        setIsDisableOpen(false);
+        run(async () => {
+            setStatus(await disableTokenEncryption());
+            showToast(t('settings.vault.disabled_toast'), 'success');
+        });
+    };
+
+    const handleUnlock = () => {
+        const usePassphrase = status?.source !== 'key_file';
+        if (usePassphrase && !passphrase) {
+            showToast(t('settings.vault.passphrase_required'), 'error');
+            return;
+        }
+        run(async () => {
+            setStatus(await unlockTokenVault(usePassphrase ? passphrase : undefined));
+            setPassphrase('');
+            showToast(t('settings.vault.unlocked_toast'), 'success');
+            refreshAccounts();
+        });
+    };
+
+    const handleExport = () => {
+        if (!bundlePassphrase) {
+            showToast(t('settings.vault.bundle_passphrase_required'), 'error');
+            return;
+        }
+        run(async () => {
+            const path = await save({
+                filters: [{ name: 'JSON', extensions: ['json'] }],
+                defaultPath: `antigravity_accounts_${new Date().toISOString().slice(0, 10)}.bundle.json`,
+            });
+            if (!path) return;
+            const count = await exportAccountsBundle(path, bundlePassphrase);
+            showToast(t('settings.vault.export_success', { count }), 'success');
+        });
+    };
+
+    const handleImport = () => {
+        if (!bundlePassphrase) {
+            showToast(t('settings.vault.bundle_passphrase_required'), 'error');
+            return;
+        }
+        run(async () => {
+            const selected = await open({
+                multiple: false,
+                filters: [{ name: 'JSON', extensions: ['json'] }],
+            });
+            if (!selected || typeof selected !== 'string') return;
+            const imported = await importAccountsBundle(selected, bundlePassphrase);
+            showToast(t('settings.vault.import_success', { count: imported.length }), 'success');
+            refreshAccounts();
+        });
+    };
+
+    if (!status) return null;
+
+    const locked = status.enabled && !status.unlocked;
+
+    return (
+        <div className="space-y-4">
+            {/* 令牌加密 */}
+            <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-lg border border-gray-100 dark:border-base-300 space-y-4">
+                <div className="flex items-center justify-between">
+                    <div>
+                        <div className="font-medium text-gray-900 dark:text-base-content flex items-center gap-2">
+                            {status.enabled ? <Lock className="w-4 h-4 text-green-500" /> : <Unlock className="w-4 h-4 text-gray-400" />}
+                            {t('settings.vault.title')}
+                        </div>
+                        <p className="text-sm text-gray-600 dark:text-gray-400 mt-1">{t('settings.vault.desc')}</p>
+                    </div>
+                    <span className={`text-xs font-medium px-2 py-1 rounded ${status.enabled ? (locked ? 'bg-amber-100 text-amber-700 dark:bg-amber-900/30 dark:text-amber-400' : 'bg-green-100 text-green-700 dark:bg-green-900/30 dark:text-green-400') : 'bg-gray-200 text-gray-600 dark:bg-base-300 dark:text-gray-400'}`}>
+                        {status.enabled ? (locked ? t('settings.vault.status_locked') : t('settings.vault.status_enabled')) : t('settings.vault.status_disabled')}
+                    </span>
+                </div>
+
+                {!status.enabled && (
+                    <div className="space-y-3">
+                        <div>
+                            <label className="block text-sm font-medium text-gray-900 dark:text-base-content mb-2">{t('settings.vault.source')}</label>
+                            <select
+                                className={inputClass}
+                                value={source}
+                                onChange={(e) => setSource(e.target.value as VaultKeySource)}
+                            >
+                                <option value="passphrase">{t('settings.vault.source_passphrase')}</option>
+                                <option value="key_file">{t('settings.vault.source_key_file')}</option>
+                            </select>
+                        </div>
+                        {source === 'passphrase' ? (
+                            <>
+                                <input
+                                    type="password"
+                                    className={inputClass}
+                                    placeholder={t('settings.vault.passphrase')}
+                                    value={passphrase}
+                                    onChange={(e) => setPassphrase(e.target.value)}
+                                />
+                                <input
+                                    type="password"
+                                    className={inputClass}
+                                    placeholder={t('settings.vault.passphrase_confirm')}
+                                    value={confirmPassphrase}
+                                    onChange={(e) => setConfirmPassphrase(e.target.value)}
+                                />
+                                <p className="text-xs text-gray-500 dark:text-gray-400">{t('settings.vault.passphrase_hint')}</p>
+                            </>
+                        ) : (
+                            <>
+                                <div className="flex gap-2">
+                                    <input
+                                        type="text"
+                                        className={inputClass}
+                                        placeholder={t('settings.vault.key_file_placeholder')}
+                                        value={keyFile}
+                                        onChange={(e) => setKeyFile(e.target.value)}
+                                    />
+                                    <button className={buttonClass} onClick={handleSelectKeyFile}>
+                                        <FolderOpen className="w-4 h-4" />
+                                    </button>
+                                </div>
+                                <p className="text-xs text-gray-500 dark:text-gray-400">{t('settings.vault.key_file_hint')}</p>
+                            </>
+                        )}
+                        <button className={primaryButtonClass} disabled={busy} onClick={handleEnable}>
+                            <Lock className="w-4 h-4" />
+                            {t('settings.vault.enable')}
+                        </button>
+                    </div>
+                )}
+
+                {locked && (
+                    <div className="space-y-3">
+                        {status.source === 'key_file' ? (
+                            <p className="text-sm text-gray-600 dark:text-gray-400">
+                                {t('settings.vault.unlock_desc_key_file', { path: status.key_file || '' })}
+                            </p>
+                        ) : (
+                            <input
+                                type="password"
+                                className={inputClass}
+                                placeholder={t('settings.vault.passphrase')}
+                                value={passphrase}
+                                onChange={(e) => setPassphrase(e.target.value)}
+                                onKeyDown={(e) => e.key === 'Enter' && handleUnlock()}
+                            />
+                        )}
+                        <button className={primaryButtonClass} disabled={busy} onClick={handleUnlock}>
+                            <Unlock className="w-4 h-4" />
+                            {status.source === 'key_file' ? t('settings.vault.retry') : t('settings.vault.unlock')}
+                        </button>
+                    </div>
+                )}
+
+                {status.enabled && !locked && (
+                    <div className="flex items-center justify-between">
+                        <p className="text-sm text-gray-600 dark:text-gray-400">
+                            {status.source === 'key_file'
+                                ? t('settings.vault.using_key_file', { path: status.key_file || '' })
+                                : t('settings.vault.using_passphrase')}
+                        </p>
+                        <button className={buttonClass} disabled={busy} onClick={() => setIsDisableOpen(true)}>
+                            <Unlock className="w-4 h-4" />
+                            {t('settings.vault.disable')}
+                        </button>
+                    </div>
+                )}
+            </div>
+
+            {/* 加密导出 / 导入 */}
+            <div className="p-4 bg-gray-50 dark:bg-base-200 rounded-lg border border-gray-100 dark:border-base-300 space-y-3">
+                <div>
+                    <div className="font-medium text-gray-900 dark:text-base-content">{t('settings.vault.bundle_title')}</div>
+                    <p className="text-sm text-gray-600 dark:text-gray-400 mt-1">{t('settings.vault.bundle_desc')}</p>
+                </div>
+                <input
+                    type="password"
+                    className={inputClass}
+                    placeholder={t('settings.vault.bundle_passphrase')}
+                    value={bundlePassphrase}
+                    onChange={(e) => setBundlePassphrase(e.target.value)}
+                />
+                <div className="flex gap-2">
+                    <button className={buttonClass} disabled={busy || locked} onClick={handleExport}>
+                        <Download className="w-4 h-4" />
+                        {t('settings.vault.export')}
+                    </button>
+                    <button className={buttonClass} disabled={busy || locked} onClick={handleImport}>
+                        <Upload className="w-4 h-4" />
+                        {t('settings.vault.import')}
+                    </button>
+                </div>
+            </div>
+
+            <ModalDialog
+                isOpen={isDisableOpen}
+                title={t('settings.vault.disable_confirm_title')}
+                message={t('settings.vault.disable_confirm_msg')}
+                type="confirm"
+                isDestructive
+                onConfirm={handleDisable}
+                onCancel={() => setIsDisableOpen(false)}
+            />
+        </div>
+    );
+}
+
+export default TokenVaultSettings;
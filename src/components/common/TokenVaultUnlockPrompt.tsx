@@ -0,0 +1,112 @@
+import { useEffect, useState } from 'react';
+import { createPortal } from 'react-dom';
+import { useTranslation } from 'react-i18next';
+import { Lock } from 'lucide-react';
+import { getTokenVaultStatus, unlockTokenVault } from '../../services/accountService';
+import { useAccountStore } from '../../stores/useAccountStore';
+import { TokenVaultStatus } from '../../types/account';
+import { showToast } from './ToastContainer';
+
+// 后端未就绪时的重试间隔与次数
+const POLL_INTERVAL_MS = 1000;
+const MAX_POLLS = 10;
+
+// 令牌库启用但未自动解锁时 (口令模式且未设置口令环境变量 / 密钥文件不可读)，启动后提示解锁
+export default function TokenVaultUnlockPrompt() {
+    const { t } = useTranslation();
+    const { fetchAccounts, fetchCurrentAccount } = useAccountStore();
+    const [status, setStatus] = useState<TokenVaultStatus | null>(null);
+    const [dismissed, setDismissed] = useState(false);
+    const [passphrase, setPassphrase] = useState('');
+    const [unlocking, setUnlocking] = useState(false);
+    const [error, setError] = useState<string | null>(null);
+
+    useEffect(() => {
+        let cancelled = false;
+        let timer: ReturnType<typeof setTimeout> | undefined;
+        const poll = async (attempt: number) => {
+            try {
+                const result = await getTokenVaultStatus();
+                if (!cancelled) setStatus(result);
+            } catch (e) {
+                if (!cancelled && attempt + 1 < MAX_POLLS) {
+                    timer = setTimeout(() => poll(attempt + 1), POLL_INTERVAL_MS);
+                }
+            }
+        };
+        poll(0);
+        return () => {
+            cancelled = true;
+            if (timer) clearTimeout(timer);
+        };
+    }, []);
+
+    if (!status || !status.enabled || status.unlocked || dismissed) return null;
+
+    const isPassphrase = status.source !== 'key_file';
+
+    const handleUnlock = async () => {
+        if (isPassphrase && !passphrase) return;
+        setUnlocking(true);
+        setError(null);
+        try {
+            const result = await unlockTokenVault(isPassphrase ? passphrase : undefined);
+            setStatus(result);
+            setPassphrase('');
+            showToast(t('settings.vault.unlocked_toast'), 'success');
+            fetchAccounts();
+            fetchCurrentAccount();
+        } catch (e) {
+            setError(String(e));
+        } finally {
+            setUnlocking(false);
+        }
+    };
+
+    return createPortal(
+        <div className="modal modal-open z-[100]">
+            <div data-tauri-drag-region className="fixed top-0 left-0 right-0 h-8 z-[110]" />
+            <div className="modal-box relative max-w-sm bg-white dark:bg-base-100 shadow-2xl rounded-2xl p-6">
+                <div className="flex flex-col items-center text-center">
+                    <div className="w-14 h-14 rounded-full flex items-center justify-center mb-4 shadow-sm bg-blue-50 dark:bg-blue-900/20">
+                        <Lock className="w-7 h-7 text-blue-500" />
+                    </div>
+                    <h3 className="text-xl font-bold text-gray-900 dark:text-base-content mb-2">{t('settings.vault.unlock_title')}</h3>
+                    <p className="text-gray-500 dark:text-gray-400 text-sm mb-4 leading-relaxed">
+                        {isPassphrase
+                            ? t('settings.vault.unlock_desc_passphrase')
+                            : t('settings.vault.unlock_desc_key_file', { path: status.key_file || '' })}
+                    </p>
+                    {isPassphrase && (
+                        <input
+                            type="password"
+                            autoFocus
+                            className="w-full px-4 py-3 mb-3 border border-gray-200 dark:border-base-300 rounded-lg focus:outline-none focus:ring-2 focus:ring-blue-500 text-gray-900 dark:text-base-content bg-gray-50 dark:bg-base-200"
+                            placeholder={t('settings.vault.passphrase')}
+                            value={passphrase}
+                            onChange={(e) => setPassphrase(e.target.value)}
+                            onKeyDown={(e) => e.key === 'Enter' && handleUnlock()}
+                        />
+                    )}
+                    {error && <p className="text-sm text-red-500 mb-3">{error}</p>}
+                    <div className="flex gap-3 w-full">
+                        <button
+                            className="flex-1 px-4 py-2.5 bg-gray-100 dark:bg-base-200 text-gray-700 dark:text-gray-300 font-medium rounded-xl hover:bg-gray-200 dark:hover:bg-base-300 transition-colors"
+                            onClick={() => setDismissed(true)}
+                        >
+                            {t('settings.vault.later')}
+                        </button>
+                        <button
+                            className="flex-1 px-4 py-2.5 bg-blue-500 text-white font-medium rounded-xl hover:bg-blue-600 transition-colors disabled:opacity-50"
+                            disabled={unlocking || (isPassphrase && !passphrase)}
+                            onClick={handleUnlock}
+                        >
+                            {isPassphrase ? t('settings.vault.unlock') : t('settings.vault.retry')}
+                        </button>
+                    </div>
+                </div>
+            </div>
+        </div>,
+        document.body
+    );
+}
@@ -3,6 +3,7 @@ import { getCurrentWindow } from '@tauri-apps/api/window';
 import Navbar from './Navbar';
 import BackgroundTaskRunner from '../common/BackgroundTaskRunner';
 import ToastContainer from '../common/ToastContainer';
+import TokenVaultUnlockPrompt from '../common/TokenVaultUnlockPrompt';
 
 function Layout() {
     return (
@@ -24,6 +25,7 @@ function Layout() {
             />
             <BackgroundTaskRunner />
             <ToastContainer />
+            <TokenVaultUnlockPrompt />
             <Navbar />
             <main className="flex-1 overflow-hidden flex flex-col relative">
                 <Outlet />
@@ -20,6 +20,15 @@ interface ProxyRequestLog {
     response_body?: string;
     input_tokens?: number;
     output_tokens?: number;
+    account_email?: string;
+    mapped_model?: string;
+    provider?: string;
+    attempts?: number;
+    rotations?: number;
+    api_key?: string;
+    ttft_ms?: number;
+    stream_duration_ms?: number;
+    replay_of?: string;
 }
 
 interface ProxyStats {
@@ -256,6 +265,30 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                                     <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-2">{t('monitor.details.model')}</span>
                                     <span className="font-mono font-black text-blue-600 dark:text-blue-400 break-all text-sm">{selectedLog.model || '-'}</span>
                                 </div>
+                                <div className="mt-4 grid grid-cols-2 gap-4 text-xs">
+                                    <div>
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-1">{t('monitor.details.mapped_model')}</span>
+                                        <span className="font-mono font-semibold text-gray-900 dark:text-white break-all">{selectedLog.mapped_model || '-'}</span>
+                                    </div>
+                                    <div>
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-1">{t('monitor.details.account')}</span>
+                                        <span className="font-mono font-semibold text-gray-900 dark:text-white break-all">{selectedLog.account_email || '-'}</span>
+                                    </div>
+                                    <div>
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-1">{t('monitor.details.provider')}</span>
+                                        <span className="font-mono font-semibold text-gray-900 dark:text-white">{selectedLog.provider || '-'}</span>
+                                    </div>
+                                    <div>
+                                        <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-1">{t('monitor.details.attempts')}</span>
+                                        <span className="font-mono font-semibold text-gray-900 dark:text-white">{selectedLog.attempts ?? '-'} ({selectedLog.rotations ?? 0})</span>
+                                    </div>
+                                    {selectedLog.stream_duration_ms !== undefined && selectedLog.stream_duration_ms !== null && (
+                                        <div>
+                                            <span className="block text-gray-500 dark:text-slate-400 uppercase font-black text-[10px] tracking-widest mb-1">{t('monitor.details.ttft')}</span>
+                                            <span className="font-mono font-semibold text-gray-900 dark:text-white">{selectedLog.ttft_ms ?? '-'}ms / {selectedLog.stream_duration_ms}ms</span>
+                                        </div>
+                                    )}
+                                </div>
                             </div>
 
                             {/* Payloads */}
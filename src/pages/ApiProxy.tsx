@@ -34,6 +34,9 @@ interface ProxyStatus {
     port: number;
     base_url: string;
     active_accounts: number;
+    active_connections?: number;
+    draining?: boolean;
+    unix_socket_path?: string | null;
 }
 
 
@@ -212,6 +215,8 @@ export default function ApiProxy() {
 
     const [appConfig, setAppConfig] = useState<AppConfig | null>(null);
     const [loading, setLoading] = useState(false);
+    // 端口输入草稿: 运行中修改端口会立即切换监听，因此在失焦 / 回车时才提交
+    const [portDraft, setPortDraft] = useState<string | null>(null);
     const [copied, setCopied] = useState<string | null>(null);
     const [selectedProtocol, setSelectedProtocol] = useState<'openai' | 'anthropic' | 'gemini'>('openai');
     const [selectedModelId, setSelectedModelId] = useState('gemini-3-flash');
@@ -265,6 +270,8 @@ export default function ApiProxy() {
         try {
             await invoke('save_config', { config: newConfig });
             setAppConfig(newConfig);
+            // 端口 / 监听地址热切换后同步访问地址
+            loadStatus();
         } catch (error) {
             console.error('保存配置失败:', error);
             showToast(`${t('common.error')}: ${error}`, 'error');
@@ -666,8 +673,8 @@ print(response.text)`;
                             </div>
                         </div>
                         <div className="p-3 space-y-3">
-                            {/* 监听端口、超时和自启动 */}
-                            <div className="grid grid-cols-1 md:grid-cols-3 gap-3">
+                            {/* 监听端口、超时、SSE 保活和自启动 */}
+                            <div className="grid grid-cols-1 md:grid-cols-4 gap-3">
                                 <div>
                                     <label className="block text-xs font-medium text-gray-700 dark:text-gray-300 mb-1">
                                         <span className="inline-flex items-center gap-1">
@@ -681,11 +688,18 @@ print(response.text)`;
                                     </label>
                                     <input
                                         type="number"
-                                        value={appConfig.proxy.port}
-                                        onChange={(e) => updateProxyConfig({ port: parseInt(e.target.value) })}
+                                        value={portDraft ?? appConfig.proxy.port}
+                                        onChange={(e) => setPortDraft(e.target.value)}
+                                        onBlur={() => {
+                                            const port = parseInt(portDraft ?? '');
+                                            setPortDraft(null);
+                                            if (port >= 1 && port <= 65535 && port !== appConfig.proxy.port) {
+                                                updateProxyConfig({ port });
+                                            }
+                                        }}
+                                        onKeyDown={(e) => e.key === 'Enter' && e.currentTarget.blur()}
                                         min={8000}
                                         max={65535}
-                                        disabled={status.running}
                                         className="w-full px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg bg-white dark:bg-base-200 text-xs text-gray-900 dark:text-base-content focus:ring-2 focus:ring-blue-500 focus:border-transparent disabled:opacity-50 disabled:cursor-not-allowed"
                                     />
                                     <p className="mt-0.5 text-[10px] text-gray-500 dark:text-gray-400">
@@ -713,13 +727,47 @@ print(response.text)`;
                                         }}
                                         min={30}
                                         max={600}
-                                        disabled={status.running}
                                         className="w-full px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg bg-white dark:bg-base-200 text-xs text-gray-900 dark:text-base-content focus:ring-2 focus:ring-blue-500 focus:border-transparent disabled:opacity-50 disabled:cursor-not-allowed"
                                     />
                                     <p className="mt-0.5 text-[10px] text-gray-500 dark:text-gray-400">
                                         {t('proxy.config.request_timeout_hint')}
                                     </p>
                                 </div>
+                                <div>
+                                    <label className="block text-xs font-medium text-gray-700 dark:text-gray-300 mb-1">
+                                        <span className="inline-flex items-center gap-1">
+                                            {t('proxy.config.sse_keepalive')}
+                                            <HelpTooltip
+                                                text={t('proxy.config.sse_keepalive_tooltip')}
+                                                ariaLabel={t('proxy.config.sse_keepalive')}
+                                                placement="top"
+                                            />
+                                        </span>
+                                    </label>
+                                    <input
+                                        type="number"
+                                        value={appConfig.proxy.sse_keepalive_secs ?? 0}
+                                        onChange={(e) => {
+                                            const value = parseInt(e.target.value) || 0;
+                                            updateProxyConfig({ sse_keepalive_secs: Math.max(0, Math.min(300, value)) });
+                                        }}
+                                        min={0}
+                                        max={300}
+                                        className="w-full px-2.5 py-1.5 border border-gray-300 dark:border-base-200 rounded-lg bg-white dark:bg-base-200 text-xs text-gray-900 dark:text-base-content focus:ring-2 focus:ring-blue-500 focus:border-transparent"
+                                    />
+                                    {(() => {
+                                        const keepalive = appConfig.proxy.sse_keepalive_secs ?? 0;
+                                        const firstByte = appConfig.proxy.timeouts?.first_byte_secs ?? 90;
+                                        const overridesRotation = keepalive > 0 && firstByte > 0 && keepalive < firstByte;
+                                        return (
+                                            <p className={`mt-0.5 text-[10px] ${overridesRotation ? 'text-amber-600 dark:text-amber-400' : 'text-gray-500 dark:text-gray-400'}`}>
+                                                {overridesRotation
+                                                    ? t('proxy.config.sse_keepalive_rotation_warning', { keepalive, firstByte })
+                                                    : t('proxy.config.sse_keepalive_hint')}
+                                            </p>
+                                        );
+                                    })()}
+                                </div>
                                 <div className="flex items-center">
                                     <label className="flex items-center cursor-pointer gap-3">
                                         <input
@@ -772,11 +820,6 @@ print(response.text)`;
                                                 {t('proxy.config.allow_lan_access_warning')}
                                             </p>
                                         )}
-                                        {status.running && (
-                                            <p className="text-[10px] text-blue-600 dark:text-blue-400">
-                                                {t('proxy.config.allow_lan_access_restart_hint')}
-                                            </p>
-                                        )}
                                     </div>
 
                                     {/* 访问授权 */}
@@ -1155,7 +1198,7 @@ print(response.text)`;
                                                 </button>
                                             </div>
                                             <div className="grid grid-cols-1 gap-2">
-                                                {(['CacheFirst', 'Balance', 'PerformanceFirst'] as const).map(mode => (
+                                                {(['CacheFirst', 'Balance', 'PerformanceFirst', 'WeightedRoundRobin'] as const).map(mode => (
                                                     <label
                                                         key={mode}
                                                         className={`flex items-start gap-3 p-3 rounded-xl border cursor-pointer transition-all duration-200 ${(appConfig.proxy.scheduling?.mode || 'Balance') === mode
@@ -1177,7 +1220,8 @@ print(response.text)`;
                                                                 {t(`proxy.config.scheduling.modes_desc.${mode}`, {
                                                                     defaultValue: mode === 'CacheFirst' ? 'Binds session to account, waits precisely if limited (Maximizes Prompt Cache hits).' :
                                                                         mode === 'Balance' ? 'Binds session, auto-switches to available account if limited (Balanced cache & availability).' :
-                                                                            'No session binding, pure round-robin rotation (Best for high concurrency).'
+                                                                            mode === 'WeightedRoundRobin' ? 'Distributes new sessions by account priority and weight, then keeps them sticky.' :
+                                                                                'No session binding, pure round-robin rotation (Best for high concurrency).'
                                                                 })}
                                                             </div>
                                                         </div>
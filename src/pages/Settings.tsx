@@ -6,6 +6,7 @@ import { useConfigStore } from '../stores/useConfigStore';
 import { AppConfig } from '../types/config';
 import ModalDialog from '../components/common/ModalDialog';
 import { showToast } from '../components/common/ToastContainer';
+import TokenVaultSettings from '../components/accounts/TokenVaultSettings';
 
 import { useTranslation } from 'react-i18next';
 
@@ -352,6 +353,9 @@ function Settings() {
                                     />
                                 </div>
                             )}
+
+                            {/* 令牌加密与加密导出 */}
+                            <TokenVaultSettings />
                         </div>
                     )}
 
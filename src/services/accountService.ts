@@ -1,5 +1,5 @@
 import { request as invoke } from '../utils/request';
-import { Account, QuotaData } from '../types/account';
+import { Account, QuotaData, TokenVaultStatus, VaultKeySource } from '../types/account';
 
 // 检查 Tauri 环境
 function ensureTauriEnvironment() {
@@ -109,3 +109,28 @@ export async function syncAccountFromDb(): Promise<Account | null> {
 export async function toggleProxyStatus(accountId: string, enable: boolean, reason?: string): Promise<void> {
     return await invoke('toggle_proxy_status', { accountId, enable, reason });
 }
+
+// 令牌加密
+export async function getTokenVaultStatus(): Promise<TokenVaultStatus> {
+    return await invoke('get_token_vault_status');
+}
+
+export async function enableTokenEncryption(source: VaultKeySource, passphrase?: string, keyFile?: string): Promise<TokenVaultStatus> {
+    return await invoke('enable_token_encryption', { source, passphrase, keyFile });
+}
+
+export async function disableTokenEncryption(): Promise<TokenVaultStatus> {
+    return await invoke('disable_token_encryption');
+}
+
+export async function unlockTokenVault(passphrase?: string): Promise<TokenVaultStatus> {
+    return await invoke('unlock_token_vault', { passphrase });
+}
+
+export async function exportAccountsBundle(path: string, passphrase: string): Promise<number> {
+    return await invoke('export_accounts_bundle', { path, passphrase });
+}
+
+export async function importAccountsBundle(path: string, passphrase: string): Promise<Account[]> {
+    return await invoke('import_accounts_bundle', { path, passphrase });
+}
@@ -10,6 +10,10 @@ export interface Account {
     proxy_disabled?: boolean;
     proxy_disabled_reason?: string;
     proxy_disabled_at?: number;
+    groups?: string[];
+    weight?: number;
+    priority?: number;
+    drain?: boolean;
     created_at: number;
     last_used: number;
 }
@@ -35,3 +39,12 @@ export interface ModelQuota {
     percentage: number;
     reset_time: string;
 }
+
+export type VaultKeySource = 'passphrase' | 'key_file';
+
+export interface TokenVaultStatus {
+    enabled: boolean;
+    unlocked: boolean;
+    source?: VaultKeySource;
+    key_file?: string;
+}
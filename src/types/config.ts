@@ -6,27 +6,160 @@ export interface UpstreamProxyConfig {
 export interface ProxyConfig {
     enabled: boolean;
     allow_lan_access?: boolean;
+    bind_address?: string;
+    ip_filter?: IpFilterConfig;
+    tls?: TlsConfig;
+    unix_socket?: UnixSocketConfig;
     auth_mode?: 'off' | 'strict' | 'all_except_health' | 'auto';
     port: number;
     api_key: string;
+    client_keys?: ClientApiKey[];
     auto_start: boolean;
     anthropic_mapping?: Record<string, string>;
     openai_mapping?: Record<string, string>;
     custom_mapping?: Record<string, string>;
     request_timeout: number;
+    timeouts?: TimeoutConfig;
+    sse_keepalive_secs?: number; // SSE 保活间隔，0 表示关闭
+    drain_timeout_secs?: number;
     enable_logging: boolean;
+    log_storage?: LogStorageConfig;
+    redaction?: RedactionConfig;
+    enable_admin_api?: boolean;
     upstream_proxy: UpstreamProxyConfig;
     zai?: ZaiConfig;
     scheduling?: StickySessionConfig;
+    account_groups?: AccountGroupConfig;
+    events?: EventsConfig;
+    key_limits?: KeyLimitsConfig;
 }
 
-export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
+// 主 API Key 之外的命名客户端 Key，name 用于限额与用量统计 ("default" 保留给主 Key)
+export interface ClientApiKey {
+    name: string;
+    key: string;
+    group?: string; // 路由到的账号分组，优先于模型 / 请求类型规则
+}
+
+export interface RedactionConfig {
+    enabled: boolean;
+    api_keys: boolean;
+    jwts: boolean;
+    emails: boolean;
+    custom_patterns: string[];
+}
+
+export interface TlsConfig {
+    enabled: boolean;
+    cert_path: string;
+    key_path: string;
+    http2: boolean;
+}
+
+export interface UnixSocketConfig {
+    enabled: boolean;
+    path: string;      // 为空时使用数据目录下的 proxy.sock
+    mode: string;      // 八进制权限，如 "600"
+    exclusive: boolean; // 仅监听 Unix socket，不开放 TCP 端口
+}
+
+export interface TimeoutConfig {
+    connect_secs: number;      // 连接超时 (全局)
+    first_byte_secs: number;   // 流式请求首字节超时
+    stream_idle_secs: number;  // 流式分块间最大间隔
+    per_model: Record<string, ModelTimeouts>; // key 支持末尾 * 通配
+}
+
+export interface ModelTimeouts {
+    first_byte_secs?: number;
+    stream_idle_secs?: number;
+    total_secs?: number;       // 覆盖 request_timeout
+}
+
+export interface IpFilterConfig {
+    allowlist: string[];
+    denylist: string[];
+}
+
+// 0 表示不限制
+export interface KeyLimit {
+    requests_per_minute: number;
+    burst: number;
+    tokens_per_day: number;
+    tokens_per_month: number;
+}
+
+export interface KeyLimitsConfig {
+    enabled: boolean;
+    per_ip: boolean;
+    default_limits: KeyLimit;
+    keys: Record<string, KeyLimit>; // 按 Key 名称 ("default" 为主 API Key，其余见 client_keys)
+}
+
+export interface BudgetUsage {
+    label: string;
+    day: string;
+    day_tokens: number;
+    month: string;
+    month_tokens: number;
+}
+
+export type ProxyEventKind = 'account_disabled' | 'all_accounts_limited' | 'quota_low' | 'upstream_failover' | 'test';
+
+export interface WebhookConfig {
+    enabled: boolean;
+    url: string;
+    secret: string;
+    events: ProxyEventKind[];
+}
+
+export interface EventsConfig {
+    enabled: boolean;
+    throttle_seconds: number;
+    throttle_overrides: Record<string, number>;
+    quota_low_threshold: number;
+    tauri_events: boolean;
+    jsonl_log: boolean;
+    webhooks: WebhookConfig[];
+}
+
+export interface ProxyEvent {
+    id: string;
+    kind: ProxyEventKind;
+    severity: 'info' | 'warning' | 'error';
+    timestamp: number;
+    message: string;
+    data: Record<string, unknown>;
+}
+
+export type LogCompression = 'none' | 'gzip' | 'zstd';
+
+export interface LogStorageConfig {
+    max_age_days: number;
+    max_rows: number;
+    max_db_size_mb: number;
+    request_body_limit_kb: number;
+    response_body_limit_kb: number;
+    redact_base64: boolean;
+    compression: LogCompression;
+    maintenance_interval_minutes: number;
+}
+
+export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst' | 'WeightedRoundRobin';
 
 export interface StickySessionConfig {
     mode: SchedulingMode;
     max_wait_seconds: number;
 }
 
+export interface AccountGroupConfig {
+    enabled: boolean;
+    model_groups?: Record<string, string>;
+    request_type_groups?: Record<string, string>;
+    default_group?: string;
+    fallback_group?: string;
+}
+
 export type ZaiDispatchMode = 'off' | 'exclusive' | 'pooled' | 'fallback';
 
 export interface ZaiMcpConfig {